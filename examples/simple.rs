@@ -0,0 +1,64 @@
+#[cfg(feature = "hooks")]
+use async_err::hooks::{enable_hook_timestamps, register_hook, AsyncErrorHookDefault};
+use async_err::prelude::*;
+use std::io;
+#[cfg(feature = "hooks")]
+use std::sync::Arc;
+
+#[cfg(feature = "hooks")]
+struct LoggingHook;
+
+#[cfg(feature = "hooks")]
+impl AsyncErrorHook<io::Error> for LoggingHook {
+    fn on_error(&self, error: &AsyncError<io::Error>) {
+        <Self as AsyncErrorHookDefault<io::Error>>::on_error(self, error);
+    }
+}
+
+async fn step1(val: i32) -> Result<i32, io::Error> {
+    Ok(val + 1)
+}
+
+async fn step2(val: i32) -> Result<i32, io::Error> {
+    if val % 2 == 0 {
+        Ok(val * 2)
+    } else {
+        Err(io::Error::other("Odd value at step2"))
+    }
+}
+
+async fn step3(val: i32) -> Result<i32, io::Error> {
+    if val < 10 {
+        Ok(val + 5)
+    } else {
+        Err(io::Error::other("Value too large at step3"))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), AsyncError<io::Error>> {
+    #[cfg(feature = "hooks")]
+    {
+        register_hook::<io::Error>(Arc::new(LoggingHook));
+        enable_hook_timestamps();
+    }
+
+    let result = step1(2)
+        .with_context(|_| "Failed at step1".to_string())
+        .and_then_async(|v| step2(v).with_context(|_| "Failed at step2".to_string()))
+        .and_then_async(|v| step3(v).with_context(|_| "Failed at step3".to_string()))
+        .await;
+
+    match &result {
+        Ok(val) => println!("Success! Result: {}", val),
+        Err(e) => {
+            eprintln!("Error occurred:");
+            if let Some(ctx) = e.context() {
+                eprintln!("  Context: {}", ctx);
+            }
+            eprintln!("  Error: {}", e.inner_error());
+        }
+    }
+
+    result.map(|_| ())
+}
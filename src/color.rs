@@ -0,0 +1,84 @@
+#![cfg(feature = "color")]
+use crate::error::{AsyncError, Severity};
+use core::error::Error;
+use std::io::IsTerminal;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[33m";
+
+/// When `AsyncError::colored_display()` emits ANSI color codes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color only if stderr is a terminal. This is the default.
+    #[default]
+    Auto,
+    /// Always color, e.g. when output is piped to a pager that understands ANSI codes.
+    Always,
+    /// Never color.
+    Never,
+}
+
+impl ColorMode {
+    /// Resolves `Auto` against the current stderr.
+    fn enabled(self) -> bool {
+        match self {
+            ColorMode::Auto => std::io::stderr().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// Returns the ANSI color for a severity label, if it is highlighted.
+fn severity_color(severity: Severity) -> Option<&'static str> {
+    match severity {
+        Severity::Warning => Some(YELLOW),
+        Severity::Error => None,
+        Severity::Critical => Some(RED),
+    }
+}
+
+impl<E: Error + 'static> AsyncError<E> {
+    /// Renders the error for a terminal, prefixed with its severity like the default hook.
+    ///
+    /// With color enabled, the severity label is colored (red for critical, yellow for
+    /// warnings) and each context is bold. Without color, the output is
+    /// `[SEVERITY] <Display>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{color::ColorMode, AsyncError, Severity};
+    /// # use std::io;
+    /// let err = AsyncError::new(io::Error::other("disk full"))
+    ///     .append_context("saving".to_string())
+    ///     .with_severity(Severity::Critical);
+    ///
+    /// assert_eq!(err.colored_display(ColorMode::Never), "[CRITICAL] saving: disk full");
+    /// assert_eq!(
+    ///     err.colored_display(ColorMode::Always),
+    ///     "[\x1b[1;31mCRITICAL\x1b[0m] \x1b[1msaving\x1b[0m: disk full"
+    /// );
+    /// ```
+    pub fn colored_display(&self, mode: ColorMode) -> String {
+        if !mode.enabled() {
+            return format!("[{}] {}", self.severity().label(), self);
+        }
+        let label = self.severity().label();
+        let mut out = match severity_color(self.severity()) {
+            Some(color) => format!("[{}{}{}] ", color, label, RESET),
+            None => format!("[{}] ", label),
+        };
+        for op in self.operations() {
+            out.push_str(&format!("{}{}{}: ", BOLD, op, RESET));
+        }
+        for ctx in self.rendered_contexts().rev() {
+            if !ctx.trim().is_empty() {
+                out.push_str(&format!("{}{}{}: ", BOLD, ctx, RESET));
+            }
+        }
+        let _ = self.write_inner(&mut out);
+        out
+    }
+}
@@ -1,45 +1,1162 @@
-use std::error::Error;
+use core::any::{Any, TypeId};
+use core::error::Error;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::borrow::Cow;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Display, Formatter};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::hash::Hash;
+use std::ops::Deref;
+use std::process::{ExitCode, Termination};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+static MAX_CONTEXT_DEPTH: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Set the maximum number of contexts an `AsyncError` keeps on its stack.
+///
+/// When a push would exceed the limit, the oldest contexts are collapsed into a single
+/// `"…(N earlier contexts)"` marker at the bottom of the stack; the newest context is
+/// always kept. The marker counts towards the limit, so limits below 2 are raised to 2.
+/// Pass `usize::MAX` to restore the default, unlimited behaviour.
+pub fn set_max_context_depth(n: usize) {
+    MAX_CONTEXT_DEPTH.store(n.max(2), Ordering::SeqCst);
+}
+
+/// Returns the currently configured maximum context depth.
+///
+/// Inside a hook, this is the depth configured when the hook invocation started.
+pub fn max_context_depth() -> usize {
+    #[cfg(feature = "hooks")]
+    if let Some(depth) = crate::hooks::pinned_max_context_depth() {
+        return depth;
+    }
+    MAX_CONTEXT_DEPTH.load(Ordering::SeqCst)
+}
+
+static APP_VERSION: RwLock<Option<&'static str>> = RwLock::new(None);
+
+/// Set the application version captured by every `AsyncError` created afterwards.
+///
+/// Intended to be called once at startup, e.g. with `env!("CARGO_PKG_VERSION")` or a git
+/// describe string, so crash reports identify the build. Read it back via
+/// `AsyncError::version`.
+pub fn set_app_version(version: &'static str) {
+    *APP_VERSION.write() = Some(version);
+}
+
+/// Returns the version set by `set_app_version`, if any.
+fn app_version() -> Option<&'static str> {
+    *APP_VERSION.read()
+}
+
+static DEDUP_ADJACENT_CONTEXTS: AtomicBool = AtomicBool::new(false);
+
+/// Make `append_context` skip a context equal to the most recent one on the stack.
+///
+/// Off by default. Useful when layered code, e.g. a middleware and its handler, both
+/// forward the same context.
+///
+/// # Example
+/// ```
+/// # use async_err::{error::set_dedup_adjacent_contexts, AsyncError};
+/// # use std::io;
+/// set_dedup_adjacent_contexts(true);
+/// let err = AsyncError::new(io::Error::other("timeout"))
+///     .append_context("request failed".to_string())
+///     .append_context("request failed".to_string());
+/// assert_eq!(err.contexts(), ["request failed"]);
+/// ```
+pub fn set_dedup_adjacent_contexts(enabled: bool) {
+    DEDUP_ADJACENT_CONTEXTS.store(enabled, Ordering::SeqCst);
+}
+
+static COUNT_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Per-error-type counters, each updated without taking the write lock once created.
+static ERROR_COUNTS: Lazy<RwLock<HashMap<TypeId, Arc<AtomicU64>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Enable or disable built-in per-error-type counting. Disabled by default.
+///
+/// While enabled, every `AsyncError<E>` receiving its first context via `with_context`
+/// (including the `.with_context()` and `.with_context_deferred()` future combinators)
+/// increments the counter for `E`, so error rates can be read via `error_counts()` without
+/// registering a hook.
+pub fn set_count_errors(enabled: bool) {
+    COUNT_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns the number of errors counted per inner error type.
+///
+/// # Example
+/// ```
+/// # use async_err::{error_counts, set_count_errors, AsyncError};
+/// # use std::{any::TypeId, fmt, io};
+/// set_count_errors(true);
+/// for _ in 0..3 {
+///     let _ = AsyncError::new(io::Error::other("io")).with_context("reading".to_string());
+/// }
+/// let _ = AsyncError::new(fmt::Error).with_context("rendering".to_string());
+///
+/// let counts = error_counts();
+/// assert_eq!(counts[&TypeId::of::<io::Error>()], 3);
+/// assert_eq!(counts[&TypeId::of::<fmt::Error>()], 1);
+/// ```
+pub fn error_counts() -> HashMap<TypeId, u64> {
+    ERROR_COUNTS
+        .read()
+        .iter()
+        .map(|(&type_id, count)| (type_id, count.load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// Clears all counters reported by `error_counts`.
+pub fn reset_error_counts() {
+    ERROR_COUNTS.write().clear();
+}
+
+/// Increments the counter for `E` if counting is enabled.
+pub(crate) fn count_error<E: 'static>() {
+    if !COUNT_ERRORS.load(Ordering::Relaxed) {
+        return;
+    }
+    let type_id = TypeId::of::<E>();
+    if let Some(count) = ERROR_COUNTS.read().get(&type_id) {
+        count.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    ERROR_COUNTS
+        .write()
+        .entry(type_id)
+        .or_default()
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Snapshot of the global settings defined in this module.
+#[cfg(feature = "test-util")]
+pub(crate) struct ErrorState {
+    max_context_depth: usize,
+    app_version: Option<&'static str>,
+    dedup_adjacent_contexts: bool,
+    count_errors: bool,
+    error_counts: HashMap<TypeId, Arc<AtomicU64>>,
+}
+
+/// Takes the global settings defined in this module, leaving their defaults in place.
+#[cfg(feature = "test-util")]
+pub(crate) fn take_error_state() -> ErrorState {
+    ErrorState {
+        max_context_depth: MAX_CONTEXT_DEPTH.swap(usize::MAX, Ordering::SeqCst),
+        app_version: APP_VERSION.write().take(),
+        dedup_adjacent_contexts: DEDUP_ADJACENT_CONTEXTS.swap(false, Ordering::SeqCst),
+        count_errors: COUNT_ERRORS.swap(false, Ordering::Relaxed),
+        error_counts: std::mem::take(&mut *ERROR_COUNTS.write()),
+    }
+}
+
+/// Replaces the global settings defined in this module with a previously taken snapshot.
+#[cfg(feature = "test-util")]
+pub(crate) fn restore_error_state(state: ErrorState) {
+    MAX_CONTEXT_DEPTH.store(state.max_context_depth, Ordering::SeqCst);
+    *APP_VERSION.write() = state.app_version;
+    DEDUP_ADJACENT_CONTEXTS.store(state.dedup_adjacent_contexts, Ordering::SeqCst);
+    COUNT_ERRORS.store(state.count_errors, Ordering::Relaxed);
+    *ERROR_COUNTS.write() = state.error_counts;
+}
+
+/// How serious an error is, used by hooks to pick an output level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Recoverable problem worth reporting, but not a failure.
+    Warning,
+    /// Regular failure. This is the default.
+    #[default]
+    Error,
+    /// Failure requiring immediate attention.
+    Critical,
+}
+
+impl Severity {
+    /// Returns the short upper-case label used in log output.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Warning => "WARN",
+            Severity::Error => "ERROR",
+            Severity::Critical => "CRITICAL",
+        }
+    }
+
+    /// Returns the process exit code used for errors of this severity.
+    ///
+    /// Warnings and regular errors exit with `1`, critical errors with `2`, so scripts can
+    /// tell them apart without parsing output.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{AsyncError, Severity};
+    /// let err = AsyncError::new(std::fmt::Error).with_severity(Severity::Critical);
+    /// assert_eq!(err.severity().exit_code(), 2);
+    /// assert_eq!(Severity::default().exit_code(), 1);
+    /// ```
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Severity::Warning | Severity::Error => 1,
+            Severity::Critical => 2,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
 
 /// Wraps an error with optional context.
+///
+/// The inner error only needs to implement `core::error::Error`, of which
+/// `std::error::Error` is a re-export, so the crate's core types also accept errors
+/// defined in `no_std` crates.
 #[derive(Debug)]
 pub struct AsyncError<E: Error + 'static> {
     error: E,
-    context: Option<String>,
+    contexts: Vec<Cow<'static, str>>,
+    /// Parallel to `contexts`: how each entry is rendered.
+    kinds: Vec<ContextKind>,
+    dropped_contexts: usize,
+    severity: Severity,
+    debug_rendering: bool,
+    pretty_report: bool,
+    /// Rarely set fields, allocated on first use to keep `Result<T, AsyncError<E>>` small.
+    extras: Option<Box<Extras>>,
     hooks_invoked: AtomicBool,
+    observed: AtomicBool,
+}
+
+/// Optional parts of an `AsyncError`.
+#[derive(Debug, Default)]
+struct Extras {
+    metadata: BTreeMap<String, String>,
+    operations: Vec<String>,
+    help: Option<String>,
+    code: Option<String>,
+    version: Option<&'static str>,
+    /// Shared rather than boxed so `detached_copy` can carry it.
+    category: Option<Arc<dyn Any + Send + Sync>>,
+    sources: Vec<Arc<dyn Error + Send + Sync>>,
+    /// Formatted `Display` output, if caching was enabled via `with_cached_display`.
+    display_cache: Option<OnceLock<String>>,
 }
 
+impl Extras {
+    /// Drops the cached `Display` output, keeping caching enabled.
+    fn invalidate_display(&mut self) {
+        if let Some(cache) = &mut self.display_cache {
+            *cache = OnceLock::new();
+        }
+    }
+}
+
+/// How a context stack entry is rendered.
+#[derive(Debug)]
+enum ContextKind {
+    /// Rendered as-is.
+    Plain,
+    /// A template whose `{key}` tokens are filled from metadata.
+    Template,
+    /// Formatted on first read; the stack entry itself is empty.
+    Lazy(Box<LazyContext>),
+}
+
+/// Context produced by a closure the first time it is read.
+struct LazyContext {
+    init: parking_lot::Mutex<Option<Box<dyn FnOnce() -> String + Send>>>,
+    value: OnceLock<String>,
+}
+
+impl LazyContext {
+    /// Returns the context, running the closure if this is the first read.
+    fn get(&self) -> &str {
+        self.value.get_or_init(|| match self.init.lock().take() {
+            Some(f) => f(),
+            None => String::new(),
+        })
+    }
+}
+
+impl fmt::Debug for LazyContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(value) => fmt::Debug::fmt(value, f),
+            None => f.write_str("<pending>"),
+        }
+    }
+}
+
+/// Returned by `AsyncError::metadata` for errors without metadata.
+static NO_METADATA: BTreeMap<String, String> = BTreeMap::new();
+
 impl<E: Error + 'static> AsyncError<E> {
     /// Creates a new error wrapper without context.
+    ///
+    /// With the `operations` feature, the operations entered via `with_operation` on the
+    /// current task are captured and rendered as a prefix of the error's contexts. The
+    /// version set via `set_app_version`, if any, is captured as well.
     pub fn new(error: E) -> Self {
         Self {
             error,
-            context: None,
+            contexts: Vec::new(),
+            kinds: Vec::new(),
+            dropped_contexts: 0,
+            severity: Severity::default(),
+            debug_rendering: false,
+            pretty_report: false,
+            extras: captured_extras(),
             hooks_invoked: AtomicBool::new(false),
+            observed: AtomicBool::new(false),
         }
     }
 
     /// Adds context to the error.
     ///
+    /// The context is pushed onto the context stack.
     /// If the `hooks` feature is enabled, hooks may be triggered.
+    ///
+    /// By default hooks run synchronously on the calling thread: all of them have
+    /// completed by the time `with_context` returns. `hooks::set_hook_dispatch_mode` can
+    /// move them to a background thread instead. To run them at a later point, push the
+    /// context with `append_context` and call `hooks::invoke_hooks` when ready, or use
+    /// `.with_context_deferred()` on futures.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "hooks")]
+    /// # {
+    /// # use async_err::{register_hook, AsyncError, AsyncErrorHook};
+    /// # use std::{io, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread, time::Duration};
+    /// struct SlowHook(AtomicBool);
+    ///
+    /// impl AsyncErrorHook<io::Error> for SlowHook {
+    ///     fn on_error(&self, _error: &AsyncError<io::Error>) {
+    ///         thread::sleep(Duration::from_millis(20));
+    ///         self.0.store(true, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// let hook = Arc::new(SlowHook(AtomicBool::new(false)));
+    /// register_hook::<io::Error>(hook.clone());
+    ///
+    /// let _err = AsyncError::new(io::Error::other("timeout")).with_context("polling".to_string());
+    /// assert!(hook.0.load(Ordering::SeqCst));
+    /// # }
+    /// ```
     pub fn with_context(mut self, context: String) -> Self {
-        self.context = Some(context);
-        #[cfg(feature = "hooks")]
+        self.push_reported_context(context.into());
+        self
+    }
+
+    /// Pushes an outer context onto the context stack without triggering hooks.
+    ///
+    /// If enabled via `set_dedup_adjacent_contexts`, a context equal to the most recent one
+    /// is skipped.
+    pub fn append_context(mut self, context: String) -> Self {
+        if DEDUP_ADJACENT_CONTEXTS.load(Ordering::SeqCst)
+            && self.rendered_contexts().next_back().as_deref() == Some(context.as_str())
         {
-            crate::hooks::invoke_hooks(&self);
+            return self;
         }
+        self.push_context(context.into());
         self
     }
 
+    /// Like `with_context`, but stores a string literal without copying it.
+    ///
+    /// Useful on allocation-sensitive paths where the context is always static: the
+    /// literal is kept as a borrowed `Cow`, so no string is allocated for it. The context
+    /// stack itself still allocates when it grows, so the call only avoids allocating
+    /// entirely once the stack has spare capacity, e.g. after `clear_context`.
+    pub fn context_str_static(mut self, context: &'static str) -> Self {
+        self.push_reported_context(Cow::Borrowed(context));
+        self
+    }
+
+    /// Replaces the outermost context in place, without triggering hooks.
+    ///
+    /// If the error has no context yet, the context is pushed instead.
+    pub fn set_context(&mut self, context: impl Into<String>) {
+        let context = Cow::Owned(context.into());
+        match self.contexts.last_mut() {
+            Some(outer) => {
+                *outer = context;
+                *self.kinds.last_mut().expect("context stacks out of sync") = ContextKind::Plain;
+            }
+            None => self.push_context(context),
+        }
+        self.invalidate_display();
+    }
+
+    /// Removes all contexts in place, without triggering hooks.
+    pub fn clear_context(&mut self) {
+        self.contexts.clear();
+        self.kinds.clear();
+        self.dropped_contexts = 0;
+        self.invalidate_display();
+    }
+
+    /// Adds a context template rendered from metadata, without triggering hooks.
+    ///
+    /// `{key}` tokens in the template are replaced with the matching metadata value when
+    /// the error is displayed or reported by the default hook, so metadata attached later
+    /// is still picked up. Tokens without a matching entry are left as-is.
+    pub fn with_context_template(mut self, template: impl Into<String>) -> Self {
+        self.push_entry(Cow::Owned(template.into()), ContextKind::Template);
+        self
+    }
+
+    /// Adds a context formatted by `f` the first time it is read, without triggering hooks.
+    ///
+    /// For hot paths where formatting the context is wasteful unless someone looks at it:
+    /// `f` runs at most once, when the context is first read via `context()`, `Display` or
+    /// a hook, and the result is kept. Until then, `contexts()` shows the entry as empty.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::{io, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let counter = calls.clone();
+    /// let err = AsyncError::new(io::Error::other("timed out")).with_lazy_stored_context(move || {
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    ///     "fetching user 42".to_string()
+    /// });
+    /// assert_eq!(calls.load(Ordering::SeqCst), 0);
+    ///
+    /// assert_eq!(err.context(), Some("fetching user 42"));
+    /// assert_eq!(err.to_string(), "fetching user 42: timed out");
+    /// assert_eq!(calls.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn with_lazy_stored_context(mut self, f: impl FnOnce() -> String + Send + 'static) -> Self {
+        let lazy = LazyContext {
+            init: parking_lot::Mutex::new(Some(Box::new(f))),
+            value: OnceLock::new(),
+        };
+        self.push_entry(Cow::Borrowed(""), ContextKind::Lazy(Box::new(lazy)));
+        self
+    }
+
+    /// Attaches a suggested remediation, e.g. "try running with --force".
+    ///
+    /// The hint is kept separate from the technical context: it is shown by
+    /// `display_chain()` but not by the single-line `Display`.
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.extras_mut().help = Some(help.into());
+        self
+    }
+
+    /// Attaches a machine-readable error code, e.g. `"E1042"` or `"404"`.
+    ///
+    /// The code is not part of `Display`, and `map` keeps it.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// let err = AsyncError::new(io::Error::other("no such user")).with_code("E1042");
+    /// assert_eq!(err.code(), Some("E1042"));
+    /// assert_eq!(err.to_string(), "no such user");
+    /// ```
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.extras_mut().code = Some(code.into());
+        self
+    }
+
+    /// Renders the inner error with `{:?}` instead of `{}` in this error's `Display`.
+    ///
+    /// Useful for opaque errors whose `Display` is too generic to act on, such as
+    /// "operation failed". Contexts are rendered as usual.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// let err = AsyncError::new(io::Error::from(io::ErrorKind::NotFound))
+    ///     .append_context("loading config".to_string());
+    /// assert_eq!(err.to_string(), "loading config: entity not found");
+    ///
+    /// let err = err.with_debug_rendering();
+    /// assert_eq!(err.to_string(), "loading config: Kind(NotFound)");
+    /// ```
+    pub fn with_debug_rendering(mut self) -> Self {
+        self.debug_rendering = true;
+        self.invalidate_display();
+        self
+    }
+
+    /// Caches the formatted `Display` output after it is first produced.
+    ///
+    /// Worth it for errors that are displayed repeatedly, e.g. logged on every retry: the
+    /// contexts and inner error are formatted once instead of on every `to_string()`.
+    /// The cost is a copy of the message kept alive with the error. Any change to the
+    /// contexts, metadata or rendering flags drops the cached string.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// let mut err = AsyncError::new(io::Error::other("timeout"))
+    ///     .append_context("fetching".to_string())
+    ///     .with_cached_display();
+    /// assert_eq!(err.cached_display(), None);
+    ///
+    /// assert_eq!(err.to_string(), "fetching: timeout");
+    /// let cached = err.cached_display().unwrap().as_ptr();
+    /// assert_eq!(err.to_string(), "fetching: timeout");
+    /// assert_eq!(err.cached_display().unwrap().as_ptr(), cached);
+    ///
+    /// err.set_context("refetching");
+    /// assert_eq!(err.cached_display(), None);
+    /// assert_eq!(err.to_string(), "refetching: timeout");
+    /// assert_eq!(err.cached_display(), Some("refetching: timeout"));
+    /// ```
+    pub fn with_cached_display(mut self) -> Self {
+        self.extras_mut().display_cache = Some(OnceLock::new());
+        self
+    }
+
+    /// Returns the cached `Display` output, if caching is enabled and it was produced.
+    pub fn cached_display(&self) -> Option<&str> {
+        self.extras
+            .as_ref()
+            .and_then(|extras| extras.display_cache.as_ref())
+            .and_then(|cache| cache.get())
+            .map(String::as_str)
+    }
+
+    /// Selects the multi-line layout for `report()`.
+    ///
+    /// Off by default, as for `std::error::Report`.
+    pub fn with_pretty_report(mut self, pretty: bool) -> Self {
+        self.pretty_report = pretty;
+        self
+    }
+
+    /// Attaches a typed category, e.g. a user-defined enum, without triggering hooks.
+    ///
+    /// Only one category is kept; attaching another replaces it, even if its type differs.
+    /// Read it back with `category::<C>()`.
+    pub fn with_category<C: Copy + Send + Sync + 'static>(mut self, category: C) -> Self {
+        self.extras_mut().category = Some(Arc::new(category));
+        self
+    }
+
+    /// Attaches another cause alongside the inner error, without triggering hooks.
+    ///
+    /// For aggregate failures with several equally important causes. `Error::source`
+    /// still returns the inner error only; use `sources()` to see all of them.
+    pub fn with_additional_source(mut self, source: Box<dyn Error + Send + Sync>) -> Self {
+        self.extras_mut().sources.push(Arc::from(source));
+        self
+    }
+
+    /// Sets the severity of the error without triggering hooks.
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Attaches a metadata entry without triggering hooks.
+    ///
+    /// An existing entry with the same key is replaced.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extras_mut().metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Combines two independent failures into one error, without triggering hooks.
+    ///
+    /// `self` stays the primary error: its inner error remains the `source()` and its
+    /// context stack is kept. `other`'s full `Display` output is joined in front of the
+    /// outermost context using `separator` (or becomes the outermost context if there is
+    /// none), so both messages appear in the merged `Display`. Metadata is combined
+    /// (entries of `self` win on key conflicts) and the higher severity of the two is kept.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{AsyncError, Severity};
+    /// # use std::io;
+    /// let primary = AsyncError::new(io::Error::other("disk full"))
+    ///     .append_context("writing report".to_string())
+    ///     .with_metadata("path", "/tmp/report");
+    /// let cleanup = AsyncError::new(io::Error::other("permission denied"))
+    ///     .append_context("removing temp file".to_string())
+    ///     .with_severity(Severity::Critical);
+    ///
+    /// let merged = primary.merge(cleanup, "; ");
+    /// assert_eq!(
+    ///     merged.to_string(),
+    ///     "removing temp file: permission denied; writing report: disk full"
+    /// );
+    /// assert_eq!(merged.severity(), Severity::Critical);
+    /// assert_eq!(merged.meta("path"), Some("/tmp/report"));
+    /// ```
+    pub fn merge(mut self, other: AsyncError<E>, separator: &str) -> AsyncError<E> {
+        let other_message = other.to_string();
+        let context = match self.rendered_context() {
+            Some(outer) => format!("{}{}{}", other_message, separator, outer),
+            None => other_message,
+        };
+        self.set_context(context);
+        self.severity = self.severity.max(other.severity);
+        for (key, value) in other.metadata() {
+            if self.meta(key).is_none() {
+                self.extras_mut()
+                    .metadata
+                    .insert(key.clone(), value.clone());
+            }
+        }
+        self
+    }
+
+    /// Rebuilds the error with a transformed inner error and outermost context.
+    ///
+    /// `ctx_fn` receives the outermost context (if any), rendered as `Display` shows it,
+    /// and returns its replacement; returning `None` removes it. Returning the context
+    /// unchanged keeps the original entry, so a template or lazy context stays one. The
+    /// rest of the context stack and everything else attached to the error (operations,
+    /// help, code, category, additional sources, severity, metadata, rendering flags and the
+    /// hooks-invoked and observed states) are preserved. No hooks are fired.
+    pub fn map<E2, F, G>(self, err_fn: F, ctx_fn: G) -> AsyncError<E2>
+    where
+        E2: Error + 'static,
+        F: FnOnce(E) -> E2,
+        G: FnOnce(Option<String>) -> Option<String>,
+    {
+        let outer = self.rendered_context().map(Cow::into_owned);
+        let mut contexts = self.contexts;
+        let mut kinds = self.kinds;
+        let mut extras = self.extras;
+        if let Some(extras) = &mut extras {
+            extras.invalidate_display();
+        }
+        let entry = contexts.pop().zip(kinds.pop());
+        match ctx_fn(outer.clone()) {
+            ctx if ctx == outer => {
+                if let Some((ctx, kind)) = entry {
+                    contexts.push(ctx);
+                    kinds.push(kind);
+                }
+            }
+            Some(ctx) => {
+                contexts.push(Cow::Owned(ctx));
+                kinds.push(ContextKind::Plain);
+            }
+            None => {}
+        }
+        AsyncError {
+            error: err_fn(self.error),
+            contexts,
+            kinds,
+            dropped_contexts: self.dropped_contexts,
+            severity: self.severity,
+            debug_rendering: self.debug_rendering,
+            pretty_report: self.pretty_report,
+            extras,
+            hooks_invoked: self.hooks_invoked,
+            observed: self.observed,
+        }
+    }
+
+    /// Converts the inner error via `Into`, keeping everything else as `map` does.
+    ///
+    /// `?` does not convert between wrapped error types on its own; use
+    /// `.map_err(AsyncError::err_into)?` instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::{fmt, io};
+    /// #[derive(Debug)]
+    /// struct AppError(String);
+    ///
+    /// impl fmt::Display for AppError {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         write!(f, "app error: {}", self.0)
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for AppError {}
+    ///
+    /// impl From<io::Error> for AppError {
+    ///     fn from(err: io::Error) -> Self {
+    ///         AppError(err.to_string())
+    ///     }
+    /// }
+    ///
+    /// fn read() -> Result<(), AsyncError<io::Error>> {
+    ///     Err(AsyncError::new(io::Error::other("disk full")).append_context("reading".to_string()))
+    /// }
+    ///
+    /// fn run() -> Result<(), AsyncError<AppError>> {
+    ///     read().map_err(AsyncError::err_into)?;
+    ///     Ok(())
+    /// }
+    ///
+    /// assert_eq!(run().unwrap_err().to_string(), "reading: app error: disk full");
+    /// ```
+    pub fn err_into<E2>(self) -> AsyncError<E2>
+    where
+        E2: Error + 'static,
+        E: Into<E2>,
+    {
+        self.map(Into::into, std::convert::identity)
+    }
+
+    /// Moves the error behind an `Arc`, making it cheap to clone.
+    pub fn into_shared(self) -> SharedAsyncError<E> {
+        self.into()
+    }
+
+    /// Boxes the inner error, converting into a `DynAsyncError`.
+    ///
+    /// Contexts and everything else attached to the error are preserved, as with `map`.
+    ///
+    /// `?` does not perform this conversion; call `.map_err(AsyncError::into_dyn)` before it.
+    pub fn into_dyn(self) -> crate::DynAsyncError
+    where
+        E: Send + Sync,
+    {
+        self.map(BoxedError::new, std::convert::identity)
+    }
+
     /// Returns a reference to the inner error.
     pub fn inner_error(&self) -> &E {
         &self.error
     }
 
-    /// Returns the context string, if any.
+    /// Returns the outermost context string, if any.
+    ///
+    /// A context added via `with_lazy_stored_context` is formatted here on first read.
     pub fn context(&self) -> Option<&str> {
-        self.context.as_deref()
+        match self.kinds.last()? {
+            ContextKind::Lazy(lazy) => Some(lazy.get()),
+            _ => self.contexts.last().map(|ctx| ctx.as_ref()),
+        }
+    }
+
+    /// Returns the outermost context with any metadata template rendered.
+    pub fn rendered_context(&self) -> Option<Cow<'_, str>> {
+        self.rendered_contexts().next_back()
+    }
+
+    /// Returns the suggested remediation, if any.
+    pub fn help(&self) -> Option<&str> {
+        self.extras.as_ref()?.help.as_deref()
+    }
+
+    /// Returns the error code attached via `with_code`, if any.
+    pub fn code(&self) -> Option<&str> {
+        self.extras.as_ref()?.code.as_deref()
+    }
+
+    /// Renders the error with its full cause chain and help text, one item per line.
+    ///
+    /// The first line is the `Display` output, followed by a `caused by:` line for each
+    /// error in the inner error's `source()` chain, an `also caused by:` line for each
+    /// additional source and a final `help:` line if set.
+    pub fn display_chain(&self) -> String {
+        let mut out = self.to_string();
+        let mut source = self.error.source();
+        while let Some(err) = source {
+            out.push_str(&format!("\n  caused by: {}", err));
+            source = err.source();
+        }
+        for src in self.sources().skip(1) {
+            out.push_str(&format!("\n  also caused by: {}", src));
+        }
+        if let Some(help) = self.help() {
+            out.push_str(&format!("\nhelp: {}", help));
+        }
+        out
+    }
+
+    /// Formats the error and its causes like `std::error::Report`, without the unstable API.
+    ///
+    /// The first line is the error's `Display`; the causes are the sources of the inner
+    /// error. By default everything is joined on one line with `": "`. With
+    /// `with_pretty_report(true)` the causes are listed under a `Caused by:` heading,
+    /// numbered when there is more than one. The captured application version, if any,
+    /// comes last.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::{error::Error, fmt};
+    /// #[derive(Debug)]
+    /// struct Layer(&'static str, Option<Box<Layer>>);
+    ///
+    /// impl fmt::Display for Layer {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         f.write_str(self.0)
+    ///     }
+    /// }
+    ///
+    /// impl Error for Layer {
+    ///     fn source(&self) -> Option<&(dyn Error + 'static)> {
+    ///         self.1.as_deref().map(|l| l as &(dyn Error + 'static))
+    ///     }
+    /// }
+    ///
+    /// let chain = Layer("query failed", Some(Box::new(Layer(
+    ///     "connection reset",
+    ///     Some(Box::new(Layer("broken pipe", None))),
+    /// ))));
+    /// let err = AsyncError::new(chain).append_context("loading user".to_string());
+    /// assert_eq!(
+    ///     err.report(),
+    ///     "loading user: query failed: connection reset: broken pipe"
+    /// );
+    ///
+    /// let err = err.with_pretty_report(true);
+    /// assert_eq!(
+    ///     err.report(),
+    ///     "loading user: query failed\n\nCaused by:\n      0: connection reset\n      1: broken pipe"
+    /// );
+    /// ```
+    pub fn report(&self) -> String {
+        let mut out = self.to_string();
+        let mut causes = Vec::new();
+        let mut source = self.error.source();
+        while let Some(err) = source {
+            causes.push(err.to_string());
+            source = err.source();
+        }
+        if !self.pretty_report {
+            for cause in &causes {
+                out.push_str(&format!(": {}", cause));
+            }
+        } else if let [cause] = causes.as_slice() {
+            out.push_str(&format!("\n\nCaused by:\n      {}", cause));
+        } else if !causes.is_empty() {
+            out.push_str("\n\nCaused by:");
+            for (i, cause) in causes.iter().enumerate() {
+                out.push_str(&format!("\n{:>7}: {}", i, cause));
+            }
+        }
+        match (self.version(), self.pretty_report) {
+            (Some(version), false) => out.push_str(&format!(" (version {})", version)),
+            (Some(version), true) => out.push_str(&format!("\n\nVersion: {}", version)),
+            (None, _) => {}
+        }
+        out
+    }
+
+    /// Returns the process exit code for this error.
+    ///
+    /// An error code set via `with_code` that is a number from 1 to 255 is used as is;
+    /// otherwise the code comes from the severity, see `Severity::exit_code`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{AsyncError, Severity};
+    /// let err = AsyncError::new(std::fmt::Error).with_severity(Severity::Critical);
+    /// assert_eq!(err.exit_code(), 2);
+    /// assert_eq!(err.with_code("75").exit_code(), 75);
+    /// ```
+    pub fn exit_code(&self) -> u8 {
+        self.code()
+            .and_then(|code| code.parse::<u8>().ok())
+            .filter(|&code| code != 0)
+            .unwrap_or_else(|| self.severity.exit_code())
+    }
+
+    /// Prints `display_chain()` to stderr and returns the error's exit code, see
+    /// `exit_code`.
+    ///
+    /// Intended for the end of `main`; see `ExitReport` for returning an `AsyncError`
+    /// from `main` directly.
+    pub fn report_exit_code(&self) -> ExitCode {
+        self.mark_observed();
+        eprintln!("Error: {}", self.display_chain());
+        ExitCode::from(self.exit_code())
+    }
+
+    /// Returns the application version captured when the error was created.
+    ///
+    /// The version also appears in `report()` and, with the `valuable` feature, as the
+    /// `version` field.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{set_app_version, AsyncError};
+    /// # use std::io;
+    /// set_app_version("1.4.2+g3f9c2a1");
+    /// let err = AsyncError::new(io::Error::other("disk full"));
+    /// assert_eq!(err.version(), Some("1.4.2+g3f9c2a1"));
+    /// assert_eq!(err.report(), "disk full (version 1.4.2+g3f9c2a1)");
+    /// ```
+    pub fn version(&self) -> Option<&'static str> {
+        self.extras.as_ref().and_then(|extras| extras.version)
+    }
+
+    /// Returns the ambient operations captured when the error was created, outermost first.
+    pub fn operations(&self) -> &[String] {
+        self.extras
+            .as_ref()
+            .map_or(&[], |extras| &extras.operations)
+    }
+
+    /// Returns the full context stack, innermost first.
+    ///
+    /// Templates added via `with_context_template` are returned unrendered.
+    /// Contexts added via `context_str_static` are borrowed, all others owned. Contexts
+    /// added via `with_lazy_stored_context` are returned as empty strings.
+    pub fn contexts(&self) -> &[Cow<'static, str>] {
+        &self.contexts
+    }
+
+    /// Returns the severity of the error.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns all metadata entries, ordered by key.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        self.extras
+            .as_ref()
+            .map_or(&NO_METADATA, |extras| &extras.metadata)
+    }
+
+    /// Returns the error as key-value pairs for structured loggers.
+    ///
+    /// The pairs are `error` (the inner error's message), `operations` (the captured
+    /// operations joined outermost first), `context` (the contexts joined outermost
+    /// first), `code`, `severity` (its label), then one `metadata` pair per entry, ordered
+    /// by key and rendered as `key=value`. Operations, context and code are omitted if
+    /// there are none. Metadata keys are not `'static`, so they go into the value.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{AsyncError, Severity};
+    /// # use std::io;
+    /// let err = AsyncError::new(io::Error::other("timeout"))
+    ///     .append_context("reading row".to_string())
+    ///     .append_context("loading user".to_string())
+    ///     .with_severity(Severity::Critical)
+    ///     .with_code("E1042")
+    ///     .with_metadata("user_id", "42")
+    ///     .with_metadata("attempt", "3");
+    ///
+    /// assert_eq!(
+    ///     err.as_kv(),
+    ///     [
+    ///         ("error", "timeout".to_string()),
+    ///         ("context", "loading user: reading row".to_string()),
+    ///         ("code", "E1042".to_string()),
+    ///         ("severity", "CRITICAL".to_string()),
+    ///         ("metadata", "attempt=3".to_string()),
+    ///         ("metadata", "user_id=42".to_string()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn as_kv(&self) -> Vec<(&'static str, String)> {
+        let mut message = String::new();
+        let _ = self.write_inner(&mut message);
+        let mut pairs = vec![("error", message)];
+        if !self.operations().is_empty() {
+            pairs.push(("operations", self.operations().join(": ")));
+        }
+        let contexts: Vec<_> = self
+            .rendered_contexts()
+            .rev()
+            .filter(|ctx| !ctx.trim().is_empty())
+            .collect();
+        if !contexts.is_empty() {
+            pairs.push(("context", contexts.join(": ")));
+        }
+        if let Some(code) = self.code() {
+            pairs.push(("code", code.to_string()));
+        }
+        pairs.push(("severity", self.severity.label().to_string()));
+        pairs.extend(
+            self.metadata()
+                .iter()
+                .map(|(key, value)| ("metadata", format!("{}={}", key, value))),
+        );
+        pairs
+    }
+
+    /// Returns the metadata value for `key`, if any.
+    pub fn meta(&self, key: &str) -> Option<&str> {
+        self.metadata().get(key).map(String::as_str)
+    }
+
+    /// Returns the category attached via `with_category`, if it is of type `C`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum Category {
+    ///     Network,
+    ///     Storage,
+    /// }
+    ///
+    /// let err = AsyncError::new(io::Error::other("timed out")).with_category(Category::Network);
+    /// assert_eq!(err.category::<Category>(), Some(Category::Network));
+    /// assert_eq!(err.category::<u32>(), None);
+    /// ```
+    pub fn category<C: Copy + 'static>(&self) -> Option<C> {
+        self.extras
+            .as_ref()?
+            .category
+            .as_ref()?
+            .downcast_ref()
+            .copied()
+    }
+
+    /// Returns the inner error followed by every source added via `with_additional_source`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// let err = AsyncError::new(io::Error::other("replica a down"))
+    ///     .with_additional_source(Box::new(io::Error::other("replica b down")))
+    ///     .with_additional_source("replica c down".into());
+    ///
+    /// let causes: Vec<String> = err.sources().map(|src| src.to_string()).collect();
+    /// assert_eq!(causes, ["replica a down", "replica b down", "replica c down"]);
+    /// ```
+    pub fn sources(&self) -> impl Iterator<Item = &(dyn Error + 'static)> {
+        let additional = self
+            .extras
+            .as_ref()
+            .map_or(&[][..], |extras| &extras.sources);
+        std::iter::once(&self.error as &(dyn Error + 'static)).chain(
+            additional
+                .iter()
+                .map(|src| &**src as &(dyn Error + 'static)),
+        )
+    }
+
+    /// Returns true if the inner error or any error in its `source()` chain is exactly `T`.
+    pub fn caused_by<T: Error + 'static>(&self) -> bool {
+        let mut current: Option<&(dyn Error + 'static)> = Some(&self.error);
+        while let Some(err) = current {
+            if err.is::<T>() {
+                return true;
+            }
+            current = err.source();
+        }
+        false
+    }
+
+    /// Strips everything but the inner error, e.g. before comparing errors in tests.
+    ///
+    /// Removes all contexts, metadata, operations, help text, code, additional sources and
+    /// the category. Severity and the hooks-invoked and observed states are kept. No hooks are
+    /// fired.
+    pub fn without_context(mut self) -> Self {
+        self.clear_context();
+        self.extras = None;
+        self
+    }
+
+    /// Returns true if the inner errors are equal, ignoring contexts and everything else.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::fmt;
+    /// let a = AsyncError::new(fmt::Error).append_context("rendering header".to_string());
+    /// let b = AsyncError::new(fmt::Error).append_context("while rendering the header".to_string());
+    /// assert!(a.inner_eq(&b));
+    /// assert_eq!(a.without_context().to_string(), b.without_context().to_string());
+    /// ```
+    pub fn inner_eq(&self, other: &AsyncError<E>) -> bool
+    where
+        E: PartialEq,
+    {
+        self.error == other.error
+    }
+
+    /// Returns true if both errors render the same causal chain.
+    ///
+    /// Compares the `Display` output of this error (contexts included) and of every error
+    /// in its `source()` chain pairwise, ignoring instance identity, severity and metadata.
+    /// Meant for test assertions where full structural equality would be brittle.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// let build = || {
+    ///     let cause = AsyncError::new(io::Error::other("connection refused"))
+    ///         .append_context("connecting".to_string());
+    ///     AsyncError::new(io::Error::other(cause)).append_context("loading profile".to_string())
+    /// };
+    /// assert!(build().chain_eq(&build()));
+    ///
+    /// let other = AsyncError::new(io::Error::other("timed out"))
+    ///     .append_context("loading profile".to_string());
+    /// assert!(!build().chain_eq(&other));
+    /// ```
+    pub fn chain_eq(&self, other: &AsyncError<E>) -> bool {
+        fn chain<E: Error + 'static>(err: &AsyncError<E>) -> Vec<String> {
+            let mut out = vec![err.to_string()];
+            let mut current: Option<&(dyn Error + 'static)> = Some(&err.error);
+            while let Some(err) = current {
+                out.push(err.to_string());
+                current = err.source();
+            }
+            out
+        }
+        chain(self) == chain(other)
+    }
+
+    /// Orders errors for reports: most severe first, then by code, then by context.
+    ///
+    /// Severity is compared in descending order. Ties are broken by the error code, with
+    /// errors without a code first, then by comparing the rendered context stacks
+    /// outermost first. The inner error is not compared, so this works for any `E`; the
+    /// `Ord` impl (for `E: Ord`) uses it before falling back to the inner error.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{AsyncError, Severity};
+    /// # use std::io;
+    /// let mut errors = vec![
+    ///     AsyncError::new(io::Error::other("a")).with_severity(Severity::Warning),
+    ///     AsyncError::new(io::Error::other("b")).with_severity(Severity::Critical),
+    ///     AsyncError::new(io::Error::other("c")),
+    /// ];
+    /// errors.sort_by(AsyncError::cmp_by_severity);
+    ///
+    /// let severities: Vec<_> = errors.iter().map(AsyncError::severity).collect();
+    /// assert_eq!(severities, [Severity::Critical, Severity::Error, Severity::Warning]);
+    /// ```
+    pub fn cmp_by_severity(&self, other: &Self) -> CmpOrdering {
+        other
+            .severity
+            .cmp(&self.severity)
+            .then_with(|| self.code().cmp(&other.code()))
+            .then_with(|| {
+                self.rendered_contexts()
+                    .rev()
+                    .cmp(other.rendered_contexts().rev())
+            })
+    }
+
+    /// Converts the error into any response type using a closure.
+    ///
+    /// This keeps the crate framework-agnostic: web handlers can map errors into their
+    /// framework's response type here, or implement `AsyncErrorConvert` once and call
+    /// `to_response`.
+    pub fn convert<R>(self, f: impl FnOnce(&AsyncError<E>) -> R) -> R {
+        f(&self)
     }
 
     /// Returns true if hooks have not been invoked yet, and marks them as invoked.
@@ -48,14 +1165,246 @@ impl<E: Error + 'static> AsyncError<E> {
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
             .is_ok()
     }
+
+    /// Marks the error as handled by application code.
+    ///
+    /// Unlike the hooks-invoked state, which only records that side effects such as
+    /// logging ran, this records that the error was deliberately dealt with. Terminal
+    /// combinators such as `.recover_with()` and `report_exit_code()` set it.
+    pub fn mark_observed(&self) {
+        self.observed.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the error was marked as handled via `mark_observed`.
+    pub fn is_observed(&self) -> bool {
+        self.observed.load(Ordering::SeqCst)
+    }
+
+    /// Returns the context stack, innermost first, with templates rendered.
+    pub(crate) fn rendered_contexts(&self) -> impl DoubleEndedIterator<Item = Cow<'_, str>> {
+        self.contexts
+            .iter()
+            .zip(&self.kinds)
+            .map(|(ctx, kind)| match kind {
+                ContextKind::Plain => Cow::Borrowed(ctx.as_ref()),
+                ContextKind::Template => Cow::Owned(render_template(ctx, self.metadata())),
+                ContextKind::Lazy(lazy) => Cow::Borrowed(lazy.get()),
+            })
+    }
+
+    /// Splits the error into the inner error and its non-empty rendered contexts,
+    /// innermost first, followed by the captured operations.
+    #[cfg_attr(not(feature = "eyre"), allow(dead_code))]
+    pub(crate) fn into_parts(self) -> (E, Vec<String>) {
+        let mut layers: Vec<String> = self
+            .rendered_contexts()
+            .filter(|ctx| !ctx.trim().is_empty())
+            .map(Cow::into_owned)
+            .collect();
+        layers.extend(self.operations().iter().rev().cloned());
+        (self.error, layers)
+    }
+
+    /// Returns the optional parts of the error, allocating them on first use.
+    ///
+    /// Drops the cached `Display` output, as the caller may change what it renders.
+    fn extras_mut(&mut self) -> &mut Extras {
+        let extras = self.extras.get_or_insert_with(Box::default);
+        extras.invalidate_display();
+        extras
+    }
+
+    /// Drops the cached `Display` output, if any.
+    fn invalidate_display(&mut self) {
+        if let Some(extras) = &mut self.extras {
+            extras.invalidate_display();
+        }
+    }
+
+    /// Copies the error for hooks that run after the call producing it has returned.
+    ///
+    /// Everything attached to the error is carried over; the category and additional
+    /// sources are shared with the original. Contexts are copied as their rendered text,
+    /// so the copy displays the same but holds no templates or lazy contexts.
+    #[cfg(feature = "hooks")]
+    pub(crate) fn detached_copy(&self) -> Self
+    where
+        E: Clone,
+    {
+        let contexts: Vec<Cow<'static, str>> = self
+            .rendered_contexts()
+            .map(|ctx| Cow::Owned(ctx.into_owned()))
+            .collect();
+        let extras = self.extras.as_ref().map(|extras| {
+            Box::new(Extras {
+                metadata: extras.metadata.clone(),
+                operations: extras.operations.clone(),
+                help: extras.help.clone(),
+                code: extras.code.clone(),
+                version: extras.version,
+                category: extras.category.clone(),
+                sources: extras.sources.clone(),
+                display_cache: extras.display_cache.clone(),
+            })
+        });
+        Self {
+            error: self.error.clone(),
+            kinds: contexts.iter().map(|_| ContextKind::Plain).collect(),
+            contexts,
+            dropped_contexts: self.dropped_contexts,
+            severity: self.severity,
+            debug_rendering: self.debug_rendering,
+            pretty_report: self.pretty_report,
+            extras,
+            hooks_invoked: AtomicBool::new(self.hooks_invoked.load(Ordering::SeqCst)),
+            observed: AtomicBool::new(self.observed.load(Ordering::SeqCst)),
+        }
+    }
+
+    /// Wraps `error` without context, but counts it and fires hooks as `with_context` would.
+    pub(crate) fn new_reported(error: E) -> Self {
+        let wrapped = Self::new(error);
+        count_error::<E>();
+        #[cfg(feature = "hooks")]
+        {
+            crate::hooks::invoke_hooks(&wrapped);
+        }
+        wrapped
+    }
+
+    /// Pushes a context the way `with_context` does: counting the error if this is its
+    /// first context and firing hooks.
+    fn push_reported_context(&mut self, context: Cow<'static, str>) {
+        if self.contexts.is_empty() && self.dropped_contexts == 0 {
+            count_error::<E>();
+        }
+        self.push_context(context);
+        #[cfg(feature = "hooks")]
+        {
+            crate::hooks::invoke_hooks(self);
+        }
+    }
+
+    /// Pushes a context, collapsing the oldest entries if the depth limit is exceeded.
+    fn push_context(&mut self, context: Cow<'static, str>) {
+        self.push_entry(context, ContextKind::Plain);
+    }
+
+    /// Pushes a context entry, collapsing the oldest entries if the depth limit is exceeded.
+    fn push_entry(&mut self, context: Cow<'static, str>, kind: ContextKind) {
+        self.contexts.push(context);
+        self.kinds.push(kind);
+        self.invalidate_display();
+        let max = max_context_depth();
+        if self.contexts.len() <= max {
+            return;
+        }
+        // The marker, once present, always sits at the bottom of the stack
+        let start = usize::from(self.dropped_contexts > 0);
+        // Keep the newest entries, leaving one slot below them for the marker
+        let excess = self.contexts.len() - start - (max - 1);
+        self.contexts.drain(start..start + excess);
+        self.kinds.drain(start..start + excess);
+        self.dropped_contexts += excess;
+        let marker = format!("…({} earlier contexts)", self.dropped_contexts);
+        if start == 0 {
+            self.contexts.insert(0, marker.into());
+            self.kinds.insert(0, ContextKind::Plain);
+        } else {
+            self.contexts[0] = marker.into();
+        }
+    }
+}
+
+impl<E: Error + 'static> AsyncError<E> {
+    /// Writes the `Display` output, from the cache if enabled.
+    fn write_plain(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        let Some(cache) = self.extras.as_ref().and_then(|e| e.display_cache.as_ref()) else {
+            return self.write_uncached(f);
+        };
+        if let Some(cached) = cache.get() {
+            return f.write_str(cached);
+        }
+        let mut buf = String::new();
+        self.write_uncached(&mut buf)?;
+        f.write_str(cache.get_or_init(|| buf))
+    }
+
+    /// Writes contexts, outermost first, followed by the inner error.
+    fn write_uncached(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        for op in self.operations() {
+            write!(f, "{}: ", op)?;
+        }
+        for ctx in self.rendered_contexts().rev() {
+            if !ctx.trim().is_empty() {
+                write!(f, "{}: ", ctx)?;
+            }
+        }
+        self.write_inner(f)
+    }
+
+    /// Writes the inner error, honoring `with_debug_rendering`.
+    pub(crate) fn write_inner(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        match self.debug_rendering {
+            true => write!(f, "{:?}", self.error),
+            false => write!(f, "{}", self.error),
+        }
+    }
+}
+
+/// Returns the extras `AsyncError::new` captures from its environment, if there are any.
+fn captured_extras() -> Option<Box<Extras>> {
+    #[cfg(feature = "operations")]
+    let operations = crate::tokio_ext::current_operations();
+    #[cfg(not(feature = "operations"))]
+    let operations = Vec::new();
+    let version = app_version();
+    (!operations.is_empty() || version.is_some()).then(|| {
+        Box::new(Extras {
+            operations,
+            version,
+            ..Extras::default()
+        })
+    })
+}
+
+/// Replaces `{key}` tokens in `template` with values from `metadata`.
+///
+/// Tokens whose key has no metadata entry are kept literally.
+fn render_template(template: &str, metadata: &BTreeMap<String, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) => {
+                let key = &after[..close];
+                match metadata.get(key) {
+                    Some(value) => out.push_str(value),
+                    None => out.push_str(&rest[open..open + close + 2]),
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
 }
 
 impl<E: Error + 'static> Display for AsyncError<E> {
+    /// Honors width, alignment and precision flags, e.g. `format!("{:>40}", err)`.
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match &self.context {
-            Some(ctx) if !ctx.trim().is_empty() => write!(f, "{}: {}", ctx, self.error),
-            _ => write!(f, "{}", self.error),
+        if f.width().is_none() && f.precision().is_none() {
+            return self.write_plain(f);
         }
+        let mut buf = String::new();
+        self.write_plain(&mut buf)?;
+        f.pad(&buf)
     }
 }
 
@@ -64,3 +1413,414 @@ impl<E: Error + 'static> Error for AsyncError<E> {
         Some(&self.error)
     }
 }
+
+/// Compares severity, code, rendered contexts and the inner error, consistent with `Ord`.
+impl<E: Error + PartialEq + 'static> PartialEq for AsyncError<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.severity == other.severity
+            && self.code() == other.code()
+            && self.rendered_contexts().eq(other.rendered_contexts())
+            && self.error == other.error
+    }
+}
+
+impl<E: Error + Eq + 'static> Eq for AsyncError<E> {}
+
+impl<E: Error + Ord + 'static> PartialOrd for AsyncError<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Most severe first, then by code and context (see `cmp_by_severity`), then by inner
+/// error.
+///
+/// Sorting a `Vec<AsyncError<E>>` therefore puts critical errors first.
+impl<E: Error + Ord + 'static> Ord for AsyncError<E> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.cmp_by_severity(other)
+            .then_with(|| self.error.cmp(&other.error))
+    }
+}
+
+/// Cheaply clonable `AsyncError`, shared behind an `Arc`.
+///
+/// `AsyncError` is not `Clone`, which rules out combinators such as
+/// `futures::FutureExt::shared` that hand the same output to several consumers. This
+/// wrapper derefs to the shared `AsyncError` and displays and chains sources like it.
+#[derive(Debug)]
+pub struct SharedAsyncError<E: Error + 'static>(Arc<AsyncError<E>>);
+
+impl<E: Error + 'static> SharedAsyncError<E> {
+    /// Returns true if both handles share the same error.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<E: Error + 'static> Clone for SharedAsyncError<E> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<E: Error + 'static> From<AsyncError<E>> for SharedAsyncError<E> {
+    fn from(error: AsyncError<E>) -> Self {
+        Self(Arc::new(error))
+    }
+}
+
+impl<E: Error + 'static> Deref for SharedAsyncError<E> {
+    type Target = AsyncError<E>;
+
+    fn deref(&self) -> &AsyncError<E> {
+        &self.0
+    }
+}
+
+impl<E: Error + 'static> Display for SharedAsyncError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&*self.0, f)
+    }
+}
+
+impl<E: Error + 'static> Error for SharedAsyncError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Result of `main` that reports an `AsyncError` and exits with a code derived from it.
+///
+/// Returning `Result<(), AsyncError<E>>` from `main` prints the error's `Debug` output and
+/// always exits with `1`. Returning `ExitReport<E>` instead prints `display_chain()` and
+/// exits with `AsyncError::exit_code()`, i.e. a numeric error code or else the severity's.
+///
+/// # Example
+/// ```no_run
+/// # use async_err::{error::ExitReport, AsyncError, Severity};
+/// # use std::io;
+/// fn run() -> Result<(), AsyncError<io::Error>> {
+///     Err(AsyncError::new(io::Error::other("corrupt index")).with_severity(Severity::Critical))
+/// }
+///
+/// fn main() -> ExitReport<io::Error> {
+///     run().into() // prints "Error: corrupt index" and exits with code 2
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ExitReport<E: Error + 'static>(pub Result<(), AsyncError<E>>);
+
+impl<E: Error + 'static> From<Result<(), AsyncError<E>>> for ExitReport<E> {
+    fn from(result: Result<(), AsyncError<E>>) -> Self {
+        Self(result)
+    }
+}
+
+impl<E: Error + 'static> Termination for ExitReport<E> {
+    fn report(self) -> ExitCode {
+        match self.0 {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => err.report_exit_code(),
+        }
+    }
+}
+
+/// Error produced from a caught panic, carrying the panic message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicError {
+    message: String,
+}
+
+impl PanicError {
+    /// Builds a `PanicError` from a panic payload, as returned by `catch_unwind`.
+    ///
+    /// `&str` and `String` payloads (i.e. from `panic!` with a message) are kept; any
+    /// other payload is recorded as `"Box<dyn Any>"`.
+    pub fn from_payload(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => "Box<dyn Any>".to_string(),
+            },
+        };
+        Self { message }
+    }
+
+    /// Returns the panic message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for PanicError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "panicked: {}", self.message)
+    }
+}
+
+impl Error for PanicError {}
+
+/// Several errors reported together, e.g. by `select_ok_async` when every future failed.
+///
+/// Displays as a count followed by each error, separated by `; `. `source()` is the first
+/// error.
+#[derive(Debug)]
+pub struct MultiError<E: Error + 'static> {
+    errors: Vec<AsyncError<E>>,
+}
+
+impl<E: Error + 'static> MultiError<E> {
+    /// Creates a `MultiError` from a list of errors.
+    pub fn new(errors: Vec<AsyncError<E>>) -> Self {
+        Self { errors }
+    }
+
+    /// Returns the errors, in the order they were collected.
+    pub fn errors(&self) -> &[AsyncError<E>] {
+        &self.errors
+    }
+
+    /// Consumes the wrapper, returning the errors.
+    pub fn into_errors(self) -> Vec<AsyncError<E>> {
+        self.errors
+    }
+}
+
+impl<E: Error + 'static> Display for MultiError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} errors", self.errors.len())?;
+        for (i, err) in self.errors.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{}", sep, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Error + 'static> Error for MultiError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.errors.first().map(|err| err as &(dyn Error + 'static))
+    }
+}
+
+/// Wraps every error in a batch of results with indexed context.
+///
+/// `ctx` receives the position of the result in `results` and the error, and is only
+/// called for errors. Successful values pass through unchanged.
+pub fn wrap_all<T, E, C>(results: Vec<Result<T, E>>, ctx: C) -> Vec<Result<T, AsyncError<E>>>
+where
+    E: Error + 'static,
+    C: Fn(usize, &E) -> String,
+{
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| {
+            result.map_err(|err| {
+                let context = ctx(i, &err);
+                AsyncError::new(err).with_context(context)
+            })
+        })
+        .collect()
+}
+
+/// Conversion of an `AsyncError` into a user-defined response type `R`.
+///
+/// Implement this for each framework response you need, e.g. an axum or actix
+/// response. The orphan rule allows it as long as `R` is defined in your crate.
+///
+/// # Example
+/// ```
+/// # use async_err::{AsyncError, error::AsyncErrorConvert};
+/// # use std::io;
+/// struct Response {
+///     status: u16,
+///     code: Option<String>,
+///     body: String,
+/// }
+///
+/// impl AsyncErrorConvert<Response> for AsyncError<io::Error> {
+///     fn to_response(&self) -> Response {
+///         Response {
+///             status: 500,
+///             code: self.code().map(str::to_owned),
+///             body: self.to_string(),
+///         }
+///     }
+/// }
+///
+/// let err = AsyncError::new(io::Error::other("boom"))
+///     .append_context("loading".into())
+///     .with_code("E500");
+/// let response = err.to_response();
+/// assert_eq!(response.body, "loading: boom");
+/// assert_eq!(response.code.as_deref(), Some("E500"));
+/// ```
+pub trait AsyncErrorConvert<R> {
+    /// Builds the response describing this error.
+    fn to_response(&self) -> R;
+}
+
+/// Counts errors per key, for summaries like "120 timeouts, 5 auth failures".
+///
+/// `key_fn` derives the grouping key of each error, e.g. its severity or a category
+/// read from metadata.
+pub fn summarize<E, K, F>(errors: &[AsyncError<E>], key_fn: F) -> HashMap<K, usize>
+where
+    E: Error + 'static,
+    K: Eq + Hash,
+    F: Fn(&AsyncError<E>) -> K,
+{
+    let mut counts = HashMap::new();
+    for error in errors {
+        *counts.entry(key_fn(error)).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Type-erased, thread-safe error that can be wrapped by `AsyncError`.
+///
+/// `Box<dyn Error + Send + Sync>` does not implement `Error` itself, so it cannot be
+/// used as the inner error directly; this newtype bridges that gap. Convert with
+/// `BoxedError::from(boxed)` and unwrap with `into_inner`.
+#[derive(Debug)]
+pub struct BoxedError(Box<dyn Error + Send + Sync>);
+
+impl BoxedError {
+    /// Boxes any error (or error message) into a `BoxedError`.
+    pub fn new(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self(error.into())
+    }
+
+    /// Returns a reference to the boxed error.
+    pub fn get_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.0
+    }
+
+    /// Consumes the wrapper, returning the boxed error.
+    pub fn into_inner(self) -> Box<dyn Error + Send + Sync> {
+        self.0
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for BoxedError {
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        Self(error)
+    }
+}
+
+/// Boxes the whole wrapped error, so `?` works in functions returning
+/// `Result<T, BoxedError>`.
+///
+/// Unlike `into_dyn`, the context stays part of the boxed error rather than the wrapper;
+/// downcast to `AsyncError<E>` to get it back.
+///
+/// # Example
+/// ```
+/// # use async_err::{AsyncError, BoxedError};
+/// # use std::io;
+/// fn load() -> Result<(), BoxedError> {
+///     Err(AsyncError::new(io::Error::other("corrupt")).append_context("loading".to_string()))?
+/// }
+///
+/// let err = load().unwrap_err();
+/// assert_eq!(err.to_string(), "loading: corrupt");
+/// let inner = err.get_ref().downcast_ref::<AsyncError<io::Error>>().unwrap();
+/// assert_eq!(inner.context(), Some("loading"));
+/// ```
+impl<E: Error + Send + Sync + 'static> From<AsyncError<E>> for BoxedError {
+    fn from(error: AsyncError<E>) -> Self {
+        Self(Box::new(error))
+    }
+}
+
+impl Display for BoxedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Error for BoxedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Boxed error behind a user-defined error trait object, such as `dyn DomainError`.
+///
+/// Hooks are registered per concrete error type. To share one hook between many error
+/// types implementing a common `trait DomainError: Error`, wrap them all as
+/// `AsyncError<DomainErrorBox<dyn DomainError>>` and register the hook for that single type.
+///
+/// # Example
+/// ```
+/// # use async_err::error::{domain_error, DomainErrorBox};
+/// # use async_err::AsyncError;
+/// # use std::{error::Error, fmt};
+/// trait DomainError: Error + Send + Sync {}
+///
+/// #[derive(Debug)]
+/// struct NotFound;
+/// impl fmt::Display for NotFound {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         f.write_str("not found")
+///     }
+/// }
+/// impl Error for NotFound {}
+/// impl DomainError for NotFound {}
+///
+/// let err: AsyncError<DomainErrorBox<dyn DomainError>> = domain_error(Box::new(NotFound));
+/// // `register_hook::<DomainErrorBox<dyn DomainError>>(..)` now covers every domain error.
+/// assert_eq!(err.to_string(), "not found");
+/// ```
+pub struct DomainErrorBox<D: ?Sized>(Box<D>);
+
+impl<D: ?Sized> DomainErrorBox<D> {
+    /// Wraps an already boxed error.
+    pub fn new(error: Box<D>) -> Self {
+        Self(error)
+    }
+
+    /// Returns a reference to the boxed error.
+    pub fn get_ref(&self) -> &D {
+        &self.0
+    }
+
+    /// Consumes the wrapper, returning the boxed error.
+    pub fn into_inner(self) -> Box<D> {
+        self.0
+    }
+}
+
+impl<D: ?Sized> From<Box<D>> for DomainErrorBox<D> {
+    fn from(error: Box<D>) -> Self {
+        Self(error)
+    }
+}
+
+impl<D: fmt::Debug + ?Sized> fmt::Debug for DomainErrorBox<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl<D: Display + ?Sized> Display for DomainErrorBox<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl<D: Error + ?Sized> Error for DomainErrorBox<D> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Wraps a boxed domain error into an `AsyncError<DomainErrorBox<D>>`.
+///
+/// `D` is usually a trait object such as `dyn DomainError`; see `DomainErrorBox`.
+pub fn domain_error<D: Error + ?Sized + 'static>(error: Box<D>) -> AsyncError<DomainErrorBox<D>> {
+    AsyncError::new(DomainErrorBox::new(error))
+}
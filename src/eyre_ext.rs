@@ -0,0 +1,38 @@
+#![cfg(feature = "eyre")]
+use crate::error::AsyncError;
+use core::error::Error;
+
+impl<E: Error + Send + Sync + 'static> AsyncError<E> {
+    /// Converts the error into an `eyre::Report`.
+    ///
+    /// The inner error becomes the report's root cause and each context wraps it as one
+    /// layer, so eyre's `Debug` output lists the contexts outermost first, followed by the
+    /// inner error. Severity, metadata and help are not carried over.
+    ///
+    /// `?` in a function returning `eyre::Result` does not call this: it goes through
+    /// eyre's own blanket `From<E: Error>` impl, which keeps the whole `AsyncError` as a
+    /// single layer whose message includes every context. Use
+    /// `.map_err(AsyncError::into_eyre)?` to get one layer per context.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// # use std::io;
+    /// let err = AsyncError::new(io::Error::other("disk full"))
+    ///     .append_context("writing cache".to_string())
+    ///     .append_context("saving profile".to_string());
+    ///
+    /// let report = err.into_eyre();
+    /// assert_eq!(report.to_string(), "saving profile");
+    /// let chain: Vec<String> = report.chain().map(|err| err.to_string()).collect();
+    /// assert_eq!(chain, ["saving profile", "writing cache", "disk full"]);
+    /// assert!(format!("{:?}", report).contains("Caused by:\n   0: writing cache"));
+    /// assert!(report.root_cause().downcast_ref::<io::Error>().is_some());
+    /// ```
+    pub fn into_eyre(self) -> eyre::Report {
+        let (error, layers) = self.into_parts();
+        layers
+            .into_iter()
+            .fold(eyre::Report::new(error), |report, ctx| report.wrap_err(ctx))
+    }
+}
@@ -1,8 +1,15 @@
-use std::error::Error;
+use crate::error::{AsyncError, MultiError, PanicError, SharedAsyncError};
+use core::error::Error;
+use futures_core::Stream;
+use std::convert::Infallible;
+use std::fmt::Debug;
 use std::future::Future;
 use std::marker::PhantomData;
+use std::ops::ControlFlow;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
 
 /// Extension trait providing a `.with_context()` method for futures resolving to `Result<T, E>`.
 ///
@@ -11,9 +18,13 @@ use std::task::{Context, Poll};
 ///
 /// # Example
 /// ```
+/// # use async_err::prelude::*;
+/// # async fn some_async_fn() -> Result<(), std::io::Error> { Ok(()) }
+/// # async fn run() {
 /// some_async_fn()
 ///     .with_context(|err| format!("Failed due to: {}", err))
 ///     .await;
+/// # }
 /// ```
 pub trait AsyncResultExt<T, E>: Future<Output = Result<T, E>> + Sized {
     /// Adds context to an error produced by this future lazily.
@@ -21,11 +32,37 @@ pub trait AsyncResultExt<T, E>: Future<Output = Result<T, E>> + Sized {
     /// The closure `ctx` is called only if the future resolves to an error, producing
     /// a string context to be attached to the error.
     ///
+    /// The closure may have side effects, such as bumping a metric: it runs exactly once
+    /// when the future fails, and never when it succeeds (it is dropped unused on `Ok`).
+    ///
+    /// On a future that already resolves to `AsyncError<E>` this nests the wrapper into
+    /// `AsyncError<AsyncError<E>>`; use `.with_outer_context()` there instead.
+    ///
     /// # Parameters
     /// - `ctx`: closure to create context string from error reference
     ///
     /// # Returns
     /// A future that resolves to `Result<T, AsyncError<E>>`, where errors are wrapped to include context.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultExt;
+    /// # use std::{io, sync::atomic::{AtomicUsize, Ordering}};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let failures = AtomicUsize::new(0);
+    /// let count = |err: &io::Error| {
+    ///     failures.fetch_add(1, Ordering::SeqCst);
+    ///     format!("request failed: {}", err)
+    /// };
+    ///
+    /// let _ = async { Ok::<_, io::Error>(()) }.with_context(count).await;
+    /// assert_eq!(failures.load(Ordering::SeqCst), 0);
+    ///
+    /// let _ = async { Err::<(), _>(io::Error::other("reset")) }.with_context(count).await;
+    /// assert_eq!(failures.load(Ordering::SeqCst), 1);
+    /// # }
+    /// ```
     fn with_context<C>(self, ctx: C) -> WithContext<Self, E, C>
     where
         C: FnOnce(&E) -> String,
@@ -33,6 +70,266 @@ pub trait AsyncResultExt<T, E>: Future<Output = Result<T, E>> + Sized {
         WithContext {
             future: self,
             context: Some(ctx),
+            skip_duplicate: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds context like `.with_context()`, returning an opaque `impl Future`.
+    ///
+    /// Useful for public APIs that return the wrapped future: callers can write
+    /// `impl Future<Output = Result<T, AsyncError<E>>>` instead of naming `WithContext`
+    /// and its generics. The future is `Send` whenever `Self`, `E` and `C` are.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::{AsyncError, AsyncResultExt};
+    /// # use std::{future::Future, io};
+    /// fn fetch(id: u32) -> impl Future<Output = Result<String, AsyncError<io::Error>>> + Send {
+    ///     async move { Err(io::Error::other("timeout")) }
+    ///         .with_context_named(move |_| format!("fetching {}", id))
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let err = tokio::spawn(fetch(7)).await.unwrap().unwrap_err();
+    /// assert_eq!(err.to_string(), "fetching 7: timeout");
+    /// # }
+    /// ```
+    fn with_context_named<C>(self, ctx: C) -> impl Future<Output = Result<T, AsyncError<E>>>
+    where
+        E: Error + 'static,
+        C: FnOnce(&E) -> String,
+    {
+        self.with_context(ctx)
+    }
+
+    /// Adds context like `.with_context()`, running the future inside a tracing span.
+    ///
+    /// The future is instrumented with an `info`-level `async_err.operation` span whose
+    /// `operation` field is `operation`, so the span lives exactly as long as the
+    /// operation. On error, the context is attached and the error is recorded onto the
+    /// span's `error.message`, `error.context` and `error.severity` fields. Span names
+    /// must be static at each callsite, which is why `operation` is a field rather than
+    /// the span name.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultExt;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let result = async { Err::<(), _>(std::io::Error::other("refused")) }
+    ///     .with_context_span("fetch_user", |_| "fetching user".to_string())
+    ///     .await;
+    /// assert_eq!(result.unwrap_err().to_string(), "fetching user: refused");
+    /// # }
+    /// ```
+    #[cfg(feature = "tracing")]
+    fn with_context_span<C>(
+        self,
+        operation: &'static str,
+        ctx: C,
+    ) -> crate::tracing_ext::WithContextSpan<Self, E, C>
+    where
+        C: FnOnce(&E) -> String,
+    {
+        crate::tracing_ext::WithContextSpan::new(self.with_context(ctx), operation)
+    }
+
+    /// Adds context like `.with_context()`, but leaves firing hooks to the caller.
+    ///
+    /// The error is returned inside a `DeferredHooks` token; no hooks run until the
+    /// caller handles the error and calls `.fire()` on it.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, DeferredHooks<E>>`.
+    #[cfg(feature = "hooks")]
+    fn with_context_deferred<C>(self, ctx: C) -> WithContextDeferred<Self, E, C>
+    where
+        C: FnOnce(&E) -> String,
+    {
+        WithContextDeferred {
+            future: self,
+            context: Some(ctx),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds context lazily, skipping it when it merely repeats the error message.
+    ///
+    /// Behaves like `.with_context()`, except that if the generated context equals the
+    /// inner error's `Display` output, no context is attached. This avoids `"foo: foo"`
+    /// output when callers mechanically forward the error message as context.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, AsyncError<E>>`.
+    fn with_context_dedup<C>(self, ctx: C) -> WithContext<Self, E, C>
+    where
+        C: FnOnce(&E) -> String,
+    {
+        WithContext {
+            future: self,
+            context: Some(ctx),
+            skip_duplicate: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds context only to errors that occurred after `threshold` had elapsed.
+    ///
+    /// The clock starts on the first poll. Failures within `threshold` are wrapped
+    /// without context; slower ones get the context from `ctx` as with `.with_context()`.
+    /// Either way the error is counted and hooks fire, as with `.with_context_dedup()`.
+    /// Useful for flagging usually-fast operations that were slow before failing.
+    ///
+    /// # Parameters
+    /// - `threshold`: minimum time between first poll and failure for `ctx` to apply
+    /// - `ctx`: closure to create context string from error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, AsyncError<E>>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultExt;
+    /// # use std::{io, time::Duration};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let threshold = Duration::from_millis(20);
+    /// let slow = |_: &io::Error| "slow lookup".to_string();
+    ///
+    /// let fast = async { Err::<(), _>(io::Error::other("not found")) }
+    ///     .with_context_if_slower_than(threshold, slow)
+    ///     .await
+    ///     .unwrap_err();
+    /// assert_eq!(fast.to_string(), "not found");
+    ///
+    /// let delayed = async {
+    ///     tokio::time::sleep(Duration::from_millis(50)).await;
+    ///     Err::<(), _>(io::Error::other("not found"))
+    /// }
+    /// .with_context_if_slower_than(threshold, slow)
+    /// .await
+    /// .unwrap_err();
+    /// assert_eq!(delayed.to_string(), "slow lookup: not found");
+    /// # }
+    /// ```
+    fn with_context_if_slower_than<C>(
+        self,
+        threshold: Duration,
+        ctx: C,
+    ) -> WithContextIfSlowerThan<Self, E, C>
+    where
+        C: FnOnce(&E) -> String,
+    {
+        WithContextIfSlowerThan {
+            future: self,
+            context: Some(ctx),
+            threshold,
+            started: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Adds context like `.with_context()`, resolving to a clonable `SharedAsyncError`.
+    ///
+    /// The output is `Clone` whenever `T` is, so the future can be passed to
+    /// `futures::FutureExt::shared` and awaited by several consumers, which all observe
+    /// the same error.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, SharedAsyncError<E>>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultExt;
+    /// # use futures::FutureExt;
+    /// # use std::io;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let fetch = async { Err::<u32, _>(io::Error::other("unreachable")) }
+    ///     .shareable_context(|_| "fetching config".to_string())
+    ///     .shared();
+    ///
+    /// let (a, b) = (fetch.clone().await.unwrap_err(), fetch.await.unwrap_err());
+    /// assert!(a.ptr_eq(&b));
+    /// assert_eq!(a.to_string(), "fetching config: unreachable");
+    /// # }
+    /// ```
+    fn shareable_context<C>(self, ctx: C) -> ShareableContext<Self, E, C>
+    where
+        C: FnOnce(&E) -> String,
+    {
+        ShareableContext {
+            inner: self.with_context(ctx),
+        }
+    }
+
+    /// Adds context to an error produced by this future, computed asynchronously.
+    ///
+    /// The closure `ctx` is called only if the future resolves to an error, and the
+    /// future it returns is awaited to produce the context string. If the closure or its
+    /// future panics, the panic is caught and the error is returned with a
+    /// `"<context unavailable>"` placeholder instead.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure producing a future that resolves to the context string
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, AsyncError<E>>`, where errors are wrapped to include context.
+    fn with_async_context<C, CFut>(self, ctx: C) -> WithAsyncContext<Self, E, C, CFut>
+    where
+        C: FnOnce(&E) -> CFut,
+        CFut: Future<Output = String>,
+    {
+        WithAsyncContext {
+            state: WithAsyncContextState::Running(self, Some(ctx)),
+        }
+    }
+
+    /// Adds the error's `Debug` representation as context.
+    ///
+    /// Equivalent to `.with_context(|err| format!("{:?}", err))`, useful for diagnostics
+    /// where the `Display` output hides details.
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, AsyncError<E>>`, where errors are wrapped to include context.
+    fn with_debug_context(self) -> WithContext<Self, E, fn(&E) -> String>
+    where
+        E: Debug,
+    {
+        self.with_context(|err| format!("{:?}", err))
+    }
+
+    /// Recovers from an error produced by this future by substituting a default value.
+    ///
+    /// If the future resolves to an error, it is wrapped into an `AsyncError`, counted when
+    /// error counting is enabled, hooks are fired once (when the `hooks` feature is
+    /// enabled), and the value produced by `default` is returned instead. This is the
+    /// common "log and continue" pattern. The error is marked as observed before its hooks
+    /// fire, so hooks can use `is_observed()` to tell recovered errors apart.
+    ///
+    /// # Parameters
+    /// - `default`: closure producing the fallback value from the error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `T`, never an error.
+    fn recover_with<D>(self, default: D) -> RecoverWith<Self, E, D>
+    where
+        D: FnOnce(&E) -> T,
+    {
+        RecoverWith {
+            future: self,
+            default: Some(default),
             _marker: PhantomData,
         }
     }
@@ -40,6 +337,82 @@ pub trait AsyncResultExt<T, E>: Future<Output = Result<T, E>> + Sized {
 
 impl<T, E, Fut> AsyncResultExt<T, E> for Fut where Fut: Future<Output = Result<T, E>> + Sized {}
 
+/// Builds a lazy context closure from `format!`-style arguments.
+///
+/// `ctx_lazy!("id={}", compute_id())` expands to `move |_| format!("id={}", compute_id())`,
+/// so the arguments are only evaluated if the future resolves to an error.
+///
+/// # Example
+/// ```
+/// # use async_err::{ctx_lazy, prelude::*};
+/// # fn compute_id() -> u32 { 42 }
+/// # #[tokio::main]
+/// # async fn main() {
+/// let result = async { Err::<(), _>(std::io::Error::other("gone")) }
+///     .with_context(ctx_lazy!("id={}", compute_id()))
+///     .await;
+/// assert_eq!(result.unwrap_err().to_string(), "id=42: gone");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ctx_lazy {
+    ($($arg:tt)*) => {
+        move |_| ::std::format!($($arg)*)
+    };
+}
+
+/// Adds the name of the enclosing function as context, like `.with_context()`.
+///
+/// `with_fn_context!(fut)` saves spelling out the operation name: on error the context is
+/// the bare name of the function the macro is used in. Inside closures and nested async
+/// blocks, where no clean name is available, the context is the `file:line` of the macro
+/// call. A single async block directly in a function counts as that function's body,
+/// like the body of an `async fn`.
+///
+/// # Example
+/// ```
+/// # use async_err::with_fn_context;
+/// # use std::io;
+/// async fn load_config() -> Result<(), async_err::AsyncError<io::Error>> {
+///     with_fn_context!(async { Err(io::Error::other("not found")) }).await
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let err = load_config().await.unwrap_err();
+/// assert_eq!(err.to_string(), "load_config: not found");
+///
+/// let err = async { with_fn_context!(async { Err::<(), _>(io::Error::other("gone")) }).await }
+///     .await
+///     .unwrap_err();
+/// assert!(err.to_string().starts_with("src/future_ext.rs:"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! with_fn_context {
+    ($fut:expr) => {{
+        fn __async_err_marker() {}
+        let path = ::core::any::type_name_of_val(&__async_err_marker);
+        $crate::AsyncResultExt::with_context($fut, move |_| {
+            $crate::future_ext::fn_context(path, ::core::file!(), ::core::line!())
+        })
+    }};
+}
+
+/// Turns the type name of `with_fn_context!`'s marker item into a context string.
+#[doc(hidden)]
+pub fn fn_context(marker_path: &str, file: &str, line: u32) -> String {
+    let path = marker_path
+        .strip_suffix("::__async_err_marker")
+        .unwrap_or(marker_path);
+    // The body of an `async fn` is itself an async block
+    let path = path.strip_suffix("::{{closure}}").unwrap_or(path);
+    match path.rsplit("::").next() {
+        Some(name) if !path.contains("{{closure}}") && !name.is_empty() => name.to_string(),
+        _ => format!("{}:{}", file, line),
+    }
+}
+
 /// Future wrapper produced by `.with_context()` to add error context.
 ///
 /// Wraps the original future, and on error, attaches the context string lazily generated
@@ -47,6 +420,7 @@ impl<T, E, Fut> AsyncResultExt<T, E> for Fut where Fut: Future<Output = Result<T
 pub struct WithContext<Fut, E, C> {
     future: Fut,
     context: Option<C>,
+    skip_duplicate: bool,
     _marker: PhantomData<E>,
 }
 
@@ -60,22 +434,27 @@ where
 
     /// Polls the wrapped future, converting any error by adding context.
     ///
-    /// If the wrapped future resolves to `Ok`, passes the value through.
-    /// If `Err`, applies the context closure, wraps the error (without invoking hooks!).
+    /// If the wrapped future resolves to `Ok`, drops the context closure and passes the value through.
+    /// If `Err`, applies the context closure and wraps the error via `AsyncError::with_context`,
+    /// which fires hooks. Use `.with_context_deferred()` to choose when hooks fire instead.
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // Safety: projected pinned fields can be safely accessed
         let this = unsafe { self.get_unchecked_mut() };
         let fut = unsafe { Pin::new_unchecked(&mut this.future) };
 
         match fut.poll(cx) {
-            Poll::Ready(Ok(val)) => Poll::Ready(Ok(val)),
+            Poll::Ready(Ok(val)) => {
+                // Free the closure's captures now rather than when the future is dropped
+                this.context = None;
+                Poll::Ready(Ok(val))
+            }
             Poll::Ready(Err(err)) => {
-                let ctx = this.context.take().map(|f| f(&err));
-                let wrapped =
-                    crate::error::AsyncError::new(err).with_context(ctx.unwrap_or_default());
-
-                // Do NOT invoke hooks here — defer hook invocation to caller to avoid duplicates
-
+                let ctx = this.context.take().map(|f| f(&err)).unwrap_or_default();
+                let wrapped = if this.skip_duplicate && ctx == err.to_string() {
+                    crate::error::AsyncError::new_reported(err)
+                } else {
+                    crate::error::AsyncError::new(err).with_context(ctx)
+                };
                 Poll::Ready(Err(wrapped))
             }
             Poll::Pending => Poll::Pending,
@@ -83,88 +462,1444 @@ where
     }
 }
 
-/// Extension trait adding `.and_then_async()` for chaining futures returning results.
-///
-/// This allows chaining asynchronous computations that depend on the success of the previous one.
-pub trait AsyncResultChainExt<T, E>: Future<Output = Result<T, E>> + Sized {
-    /// Chains an asynchronous computation to execute if the previous future resolves to `Ok`.
-    ///
-    /// The closure `f` takes the successful value and returns a new future producing a `Result`.
-    ///
-    /// # Parameters
-    /// - `f`: the chaining closure producing the next future.
-    ///
-    /// # Returns
-    /// A future that resolves to the chained computation’s `Result`.
-    fn and_then_async<Fut, F, U>(self, f: F) -> AndThenAsync<Self, Fut, F>
-    where
-        F: FnOnce(T) -> Fut,
-        Fut: Future<Output = Result<U, E>>,
-    {
-        AndThenAsync {
-            state: AndThenAsyncState::First(self, Some(f)),
-        }
+/// Future wrapper produced by `.with_context_if_slower_than()`.
+pub struct WithContextIfSlowerThan<Fut, E, C> {
+    future: Fut,
+    context: Option<C>,
+    threshold: Duration,
+    started: Option<Instant>,
+    _marker: PhantomData<E>,
+}
+
+impl<Fut, T, E, C> Future for WithContextIfSlowerThan<Fut, E, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> String,
+{
+    type Output = Result<T, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: projected pinned fields can be safely accessed
+        let this = unsafe { self.get_unchecked_mut() };
+        let started = *this.started.get_or_insert_with(Instant::now);
+        let fut = unsafe { Pin::new_unchecked(&mut this.future) };
+        fut.poll(cx).map_err(|err| {
+            let ctx = this.context.take().expect("Polled after completion");
+            if started.elapsed() < this.threshold {
+                return AsyncError::new_reported(err);
+            }
+            let context = ctx(&err);
+            AsyncError::new(err).with_context(context)
+        })
     }
 }
 
-impl<T, E, F> AsyncResultChainExt<T, E> for F where F: Future<Output = Result<T, E>> + Sized {}
+/// Future wrapper produced by `.with_context_deferred()`.
+#[cfg(feature = "hooks")]
+pub struct WithContextDeferred<Fut, E, C> {
+    future: Fut,
+    context: Option<C>,
+    _marker: PhantomData<E>,
+}
 
-/// Internal enum representing the current state of the chained async future.
-pub enum AndThenAsyncState<Fut1, Fut2, F> {
-    First(Fut1, Option<F>),
-    Second(Fut2),
+#[cfg(feature = "hooks")]
+impl<Fut, T, E, C> Future for WithContextDeferred<Fut, E, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> String,
+{
+    type Output = Result<T, crate::hooks::DeferredHooks<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: projected pinned fields can be safely accessed
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.future) };
+        fut.poll(cx).map_err(|err| {
+            let ctx = this.context.take().expect("Polled after completion");
+            let context = ctx(&err);
+            // Counted now, like `with_context`, even if the caller never fires the hooks
+            crate::error::count_error::<E>();
+            crate::hooks::DeferredHooks::new(AsyncError::new(err).append_context(context))
+        })
+    }
+}
+
+/// Future wrapper produced by `.shareable_context()`, sharing the wrapped error.
+pub struct ShareableContext<Fut, E, C> {
+    inner: WithContext<Fut, E, C>,
+}
+
+impl<Fut, T, E, C> Future for ShareableContext<Fut, E, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> String,
+{
+    type Output = Result<T, SharedAsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the inner future is never moved out of the pinned wrapper
+        let inner = unsafe { self.map_unchecked_mut(|this| &mut this.inner) };
+        inner.poll(cx).map_err(SharedAsyncError::from)
+    }
+}
+
+/// Placeholder context used when an async context future panics.
+const CONTEXT_UNAVAILABLE: &str = "<context unavailable>";
+
+/// Internal enum representing the current state of `WithAsyncContext`.
+pub enum WithAsyncContextState<Fut, E, C, CFut> {
+    Running(Fut, Option<C>),
+    Context(CFut, Option<E>),
     Done,
 }
 
-/// Future that chains two async computations sequentially.
+/// Future wrapper produced by `.with_async_context()` to add asynchronously computed context.
 ///
-/// Internally manages polling of the first, then the second future produced by the chaining closure.
-pub struct AndThenAsync<Fut1, Fut2, F> {
-    state: AndThenAsyncState<Fut1, Fut2, F>,
+/// Polls the original future, then, on error, the context future. Panics from the
+/// context closure or future are caught so they never unwind into the caller's task.
+pub struct WithAsyncContext<Fut, E, C, CFut> {
+    state: WithAsyncContextState<Fut, E, C, CFut>,
 }
 
-impl<Fut1, Fut2, F, T, U, E> Future for AndThenAsync<Fut1, Fut2, F>
+impl<Fut, T, E, C, CFut> Future for WithAsyncContext<Fut, E, C, CFut>
 where
-    Fut1: Future<Output = Result<T, E>>,
-    Fut2: Future<Output = Result<U, E>>,
-    F: FnOnce(T) -> Fut2,
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> CFut,
+    CFut: Future<Output = String>,
 {
-    type Output = Result<U, E>;
+    type Output = Result<T, AsyncError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // Safety: Moving pinned fields in pattern matching is allowed here.
         let this = unsafe { self.get_unchecked_mut() };
         loop {
             match &mut this.state {
-                AndThenAsyncState::First(fut1, maybe_f) => {
-                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
-                    match fut1_pin.poll(cx) {
+                WithAsyncContextState::Running(fut, maybe_ctx) => {
+                    let fut_pin = unsafe { Pin::new_unchecked(fut) };
+                    match fut_pin.poll(cx) {
                         Poll::Pending => return Poll::Pending,
-                        Poll::Ready(result) => match result {
-                            Ok(value) => {
-                                let f = maybe_f.take().expect("FnOnce already taken");
-                                let fut2 = f(value);
-                                this.state = AndThenAsyncState::Second(fut2);
-                            }
-                            Err(e) => {
-                                this.state = AndThenAsyncState::Done;
-                                return Poll::Ready(Err(e));
+                        Poll::Ready(Ok(val)) => {
+                            this.state = WithAsyncContextState::Done;
+                            return Poll::Ready(Ok(val));
+                        }
+                        Poll::Ready(Err(err)) => {
+                            let ctx = maybe_ctx.take().expect("FnOnce already taken");
+                            match catch_unwind(AssertUnwindSafe(|| ctx(&err))) {
+                                Ok(ctx_fut) => {
+                                    this.state = WithAsyncContextState::Context(ctx_fut, Some(err));
+                                }
+                                Err(_) => {
+                                    this.state = WithAsyncContextState::Done;
+                                    let wrapped = AsyncError::new(err)
+                                        .with_context(CONTEXT_UNAVAILABLE.to_string());
+                                    return Poll::Ready(Err(wrapped));
+                                }
                             }
-                        },
-                    }
-                }
-                AndThenAsyncState::Second(fut2) => {
-                    let fut2_pin = unsafe { Pin::new_unchecked(fut2) };
-                    match fut2_pin.poll(cx) {
-                        Poll::Pending => return Poll::Pending,
-                        Poll::Ready(result) => {
-                            this.state = AndThenAsyncState::Done;
-                            return Poll::Ready(result);
                         }
                     }
                 }
-                AndThenAsyncState::Done => panic!("Polled after completion"),
+                WithAsyncContextState::Context(ctx_fut, err) => {
+                    let ctx_pin = unsafe { Pin::new_unchecked(ctx_fut) };
+                    let ctx = match catch_unwind(AssertUnwindSafe(|| ctx_pin.poll(cx))) {
+                        Ok(Poll::Pending) => return Poll::Pending,
+                        Ok(Poll::Ready(ctx)) => ctx,
+                        Err(_) => CONTEXT_UNAVAILABLE.to_string(),
+                    };
+                    let err = err.take().expect("Polled after completion");
+                    this.state = WithAsyncContextState::Done;
+                    return Poll::Ready(Err(AsyncError::new(err).with_context(ctx)));
+                }
+                WithAsyncContextState::Done => panic!("Polled after completion"),
             }
         }
     }
 }
+
+/// Future wrapper produced by `.recover_with()` to substitute a value on error.
+///
+/// Wraps the original future, and on error, fires hooks for the wrapped error before
+/// returning the fallback value produced by the stored closure.
+pub struct RecoverWith<Fut, E, D> {
+    future: Fut,
+    default: Option<D>,
+    _marker: PhantomData<E>,
+}
+
+impl<Fut, T, E, D> Future for RecoverWith<Fut, E, D>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+    D: FnOnce(&E) -> T,
+{
+    type Output = T;
+
+    /// Polls the wrapped future, recovering from any error with the fallback value.
+    ///
+    /// If the wrapped future resolves to `Ok`, drops the fallback closure and passes the value through.
+    /// If `Err`, wraps the error, fires hooks once and returns the fallback value.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: projected pinned fields can be safely accessed
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        match fut.poll(cx) {
+            Poll::Ready(Ok(val)) => {
+                this.default = None;
+                Poll::Ready(val)
+            }
+            Poll::Ready(Err(err)) => {
+                let wrapped = crate::error::AsyncError::new(err);
+                crate::error::count_error::<E>();
+                // Marked before hooks fire, so they can tell it from errors that propagate
+                wrapped.mark_observed();
+
+                #[cfg(feature = "hooks")]
+                {
+                    if wrapped.invoke_hooks_once() {
+                        crate::hooks::invoke_hooks(&wrapped);
+                    }
+                }
+
+                let default = this.default.take().expect("Polled after completion");
+                Poll::Ready(default(wrapped.inner_error()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Extension trait for futures that cannot fail, i.e. resolve to `Result<T, Infallible>`.
+///
+/// `.with_context()` cannot be specialized for `Infallible`, so it would still produce
+/// an `AsyncError<Infallible>` type. This unwraps the result instead.
+pub trait AsyncInfallibleExt<T>: Future<Output = Result<T, Infallible>> + Sized {
+    /// Resolves to the success value directly, without any wrapping machinery.
+    ///
+    /// # Returns
+    /// A future that resolves to `T`.
+    fn into_ok(self) -> IntoOk<Self> {
+        IntoOk { future: self }
+    }
+}
+
+impl<T, Fut> AsyncInfallibleExt<T> for Fut where Fut: Future<Output = Result<T, Infallible>> + Sized {}
+
+/// Future produced by `.into_ok()` for infallible futures.
+pub struct IntoOk<Fut> {
+    future: Fut,
+}
+
+impl<Fut, T> Future for IntoOk<Fut>
+where
+    Fut: Future<Output = Result<T, Infallible>>,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: projected pinned fields can be safely accessed
+        let fut = unsafe { self.map_unchecked_mut(|this| &mut this.future) };
+        fut.poll(cx).map(|result| match result {
+            Ok(val) => val,
+            Err(never) => match never {},
+        })
+    }
+}
+
+/// Extension trait turning panics inside any future into `AsyncError`s.
+pub trait AsyncPanicExt: Future + Sized {
+    /// Catches a panic raised while polling this future and returns it as an error.
+    ///
+    /// Each poll runs inside `catch_unwind`. A caught panic drops the future and resolves
+    /// to an `AsyncError<PanicError>` carrying the panic message and the context produced
+    /// by `ctx`, firing hooks like `.with_context()`. The future is asserted unwind-safe:
+    /// state it shares with other code may be left inconsistent by the panic.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from the caught panic
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<Self::Output, AsyncError<PanicError>>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncPanicExt;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let result = async {
+    ///     if true {
+    ///         panic!("index out of range");
+    ///     }
+    ///     42
+    /// }
+    /// .catch_panic_context(|_| "rendering page".to_string())
+    /// .await;
+    ///
+    /// let err = result.unwrap_err();
+    /// assert_eq!(err.inner_error().message(), "index out of range");
+    /// assert_eq!(err.to_string(), "rendering page: panicked: index out of range");
+    /// # }
+    /// ```
+    fn catch_panic_context<C>(self, ctx: C) -> CatchPanic<Self, C>
+    where
+        C: FnOnce(&PanicError) -> String,
+    {
+        CatchPanic {
+            future: Some(self),
+            context: Some(ctx),
+        }
+    }
+}
+
+impl<Fut> AsyncPanicExt for Fut where Fut: Future + Sized {}
+
+/// Future produced by `.catch_panic_context()`.
+pub struct CatchPanic<Fut, C> {
+    future: Option<Fut>,
+    context: Option<C>,
+}
+
+impl<Fut, C> Future for CatchPanic<Fut, C>
+where
+    Fut: Future,
+    C: FnOnce(&PanicError) -> String,
+{
+    type Output = Result<Fut::Output, AsyncError<PanicError>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the future is only dropped in place, never moved
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = this.future.as_mut().expect("Polled after completion");
+        let fut = unsafe { Pin::new_unchecked(fut) };
+        match catch_unwind(AssertUnwindSafe(|| fut.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(val)) => {
+                this.future = None;
+                this.context = None;
+                Poll::Ready(Ok(val))
+            }
+            Err(payload) => {
+                this.future = None;
+                let err = PanicError::from_payload(payload);
+                let ctx = this.context.take().expect("FnOnce already taken");
+                let context = ctx(&err);
+                Poll::Ready(Err(AsyncError::new(err).with_context(context)))
+            }
+        }
+    }
+}
+
+/// Extension trait adding `.and_then_async()` for chaining futures returning results.
+///
+/// This allows chaining asynchronous computations that depend on the success of the previous one.
+pub trait AsyncResultChainExt<T, E>: Future<Output = Result<T, E>> + Sized {
+    /// Chains an asynchronous computation to execute if the previous future resolves to `Ok`.
+    ///
+    /// The closure `f` takes the successful value and returns a new future producing a `Result`.
+    ///
+    /// # Parameters
+    /// - `f`: the chaining closure producing the next future.
+    ///
+    /// # Returns
+    /// A future that resolves to the chained computation’s `Result`.
+    fn and_then_async<Fut, F, U>(self, f: F) -> AndThenAsync<Self, Fut, F>
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<U, E>>,
+    {
+        AndThenAsync {
+            state: AndThenAsyncState::First(self, Some(f)),
+        }
+    }
+
+    /// Chains a second stage that runs only if `continue_pred` accepts the first value.
+    ///
+    /// Fits APIs that signal "stop, but not an error" with a sentinel `Ok` value, such as
+    /// `Ok(None)` for "no more items". If the predicate returns `false`, the chain stops
+    /// with `ControlFlow::Break` holding the first value and `f` is never called;
+    /// otherwise the second stage's value is returned in `ControlFlow::Continue`.
+    ///
+    /// # Parameters
+    /// - `continue_pred`: decides from the first value whether to run the second stage.
+    /// - `f`: the chaining closure producing the next future.
+    ///
+    /// # Returns
+    /// A future resolving to `Result<ControlFlow<T, U>, E>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultChainExt;
+    /// # use std::{io, ops::ControlFlow};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let next_page = |cursor: Option<u32>| async move {
+    ///     Ok::<_, io::Error>(format!("page {}", cursor.unwrap()))
+    /// };
+    ///
+    /// let done = async { Ok::<_, io::Error>(None) }
+    ///     .and_then_async_while(Option::is_some, next_page)
+    ///     .await;
+    /// assert!(matches!(done, Ok(ControlFlow::Break(None))));
+    ///
+    /// let more = async { Ok::<_, io::Error>(Some(2)) }
+    ///     .and_then_async_while(Option::is_some, next_page)
+    ///     .await;
+    /// assert!(matches!(more, Ok(ControlFlow::Continue(page)) if page == "page 2"));
+    /// # }
+    /// ```
+    fn and_then_async_while<Fut, P, F, U>(
+        self,
+        continue_pred: P,
+        f: F,
+    ) -> AndThenAsyncWhile<Self, Fut, P, F>
+    where
+        P: Fn(&T) -> bool,
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<U, E>>,
+    {
+        AndThenAsyncWhile {
+            state: AndThenAsyncState::First(self, Some(f)),
+            continue_pred,
+        }
+    }
+
+    /// Chains a stream-producing stage to execute if the previous future resolves to `Ok`.
+    ///
+    /// The closure `f` takes the successful value and returns a stream of results, which
+    /// is flattened into the returned stream. If the previous future fails, the stream
+    /// yields that single error and ends.
+    ///
+    /// # Parameters
+    /// - `f`: the chaining closure producing the second-stage stream.
+    ///
+    /// # Returns
+    /// A stream yielding the second stage's items, or the first stage's error.
+    fn and_then_stream_async<S, F, U>(self, f: F) -> AndThenStream<Self, S, F>
+    where
+        F: FnOnce(T) -> S,
+        S: Stream<Item = Result<U, E>>,
+    {
+        AndThenStream {
+            state: AndThenAsyncState::First(self, Some(f)),
+        }
+    }
+
+    /// Chains a retryable second stage after a first stage that runs only once.
+    ///
+    /// Fits fetch-then-process pipelines where the fetch is not idempotent but the
+    /// processing is. The first value is kept inside the combinator, and `factory` builds
+    /// a fresh second-stage future from a reference to it for each attempt, up to
+    /// `attempts` times in total (at least once). The future must not borrow the value, so
+    /// copy out whatever it needs.
+    ///
+    /// # Parameters
+    /// - `attempts`: maximum number of second-stage attempts.
+    /// - `factory`: closure producing the second-stage future for each attempt.
+    ///
+    /// # Returns
+    /// A future resolving to the second stage's value. A first-stage failure is wrapped
+    /// with `"first stage failed"` context; the final second-stage failure is wrapped with
+    /// context recording how many attempts were made.
+    fn and_then_async_retry_second<Fct, Fut, U>(
+        self,
+        attempts: u32,
+        factory: Fct,
+    ) -> AndThenRetrySecond<Self, Fut, Fct, T>
+    where
+        Fct: FnMut(&T) -> Fut,
+        Fut: Future<Output = Result<U, E>>,
+    {
+        AndThenRetrySecond {
+            state: RetrySecondState::First(self),
+            factory,
+            max_attempts: attempts.max(1),
+            attempts: 0,
+        }
+    }
+
+    /// Chains a second stage, running `cleanup` on the first stage's value if it fails.
+    ///
+    /// Fits connect-then-use pipelines: `f` receives a clone of the first value (e.g. a
+    /// connection handle) to run the second stage. If the second stage fails, the original
+    /// value is passed to `cleanup`, whose future is awaited before the error is returned.
+    /// On success, or if the first stage fails, `cleanup` is not called.
+    ///
+    /// # Parameters
+    /// - `f`: the chaining closure producing the second-stage future.
+    /// - `cleanup`: closure producing a future that releases the first stage's value.
+    ///
+    /// # Returns
+    /// A future that resolves to the second stage's `Result`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultChainExt;
+    /// # use std::{io, sync::{atomic::{AtomicBool, Ordering}, Arc}};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let closed = Arc::new(AtomicBool::new(false));
+    /// let flag = closed.clone();
+    /// let result: Result<(), io::Error> = async { Ok::<_, io::Error>("conn-1") }
+    ///     .and_then_async_with_cleanup(
+    ///         |_conn| async { Err(io::Error::other("query failed")) },
+    ///         move |_conn| async move { flag.store(true, Ordering::SeqCst) },
+    ///     )
+    ///     .await;
+    ///
+    /// assert!(result.is_err());
+    /// assert!(closed.load(Ordering::SeqCst));
+    /// # }
+    /// ```
+    fn and_then_async_with_cleanup<Fut, F, U, Cl, ClFut>(
+        self,
+        f: F,
+        cleanup: Cl,
+    ) -> AndThenWithCleanup<Self, Fut, ClFut, F, Cl, T, E>
+    where
+        T: Clone,
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<U, E>>,
+        Cl: FnOnce(T) -> ClFut,
+        ClFut: Future<Output = ()>,
+    {
+        AndThenWithCleanup {
+            state: CleanupState::First(self),
+            value: None,
+            f: Some(f),
+            cleanup: Some(cleanup),
+        }
+    }
+
+    /// Chains a second stage that borrows the first stage's value, returning both.
+    ///
+    /// Both stages run inside one boxed future that owns the first value, so the second
+    /// stage can read it by reference; the value is then returned alongside the second
+    /// stage's value. Because the borrow's lifetime cannot be named in a generic future
+    /// type, `f` returns a boxed `RefFuture`. Neither stage has to be `Send`, and as a
+    /// result the combinator is not `Send` either; use `.and_then_async_ref_send()` for a
+    /// chain that is passed to `tokio::spawn`.
+    ///
+    /// # Parameters
+    /// - `f`: closure borrowing the first value and producing the second-stage future.
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<(T, U), E>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultChainExt;
+    /// # use std::io;
+    /// struct Config {
+    ///     host: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let load = async { Ok::<_, io::Error>(Config { host: "db".into(), port: 5432 }) };
+    /// let (config, addr) = load
+    ///     .and_then_async_ref(|config| Box::pin(async move { Ok(format!("{}:{}", config.host, config.port)) }))
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// assert_eq!(addr, "db:5432");
+    /// assert_eq!(config.port, 5432);
+    /// # }
+    /// ```
+    fn and_then_async_ref<'a, F, U>(self, f: F) -> AndThenAsyncRef<'a, T, U, E>
+    where
+        Self: 'a,
+        F: for<'b> FnOnce(&'b T) -> RefFuture<'b, U, E> + 'a,
+    {
+        AndThenAsyncRef {
+            inner: Box::pin(async move {
+                let value = self.await?;
+                let second = f(&value).await?;
+                Ok((value, second))
+            }),
+        }
+    }
+
+    /// Like `.and_then_async_ref()`, but `Send`, so the chain can be spawned.
+    ///
+    /// `f` returns a boxed `RefFutureSend`, and the first stage, its value and `f` must be
+    /// `Send`; the value must also be `Sync`, as the second stage borrows it.
+    ///
+    /// # Parameters
+    /// - `f`: closure borrowing the first value and producing the second-stage future.
+    ///
+    /// # Returns
+    /// A `Send` future that resolves to `Result<(T, U), E>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncResultChainExt;
+    /// # use std::io;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let load = async { Ok::<_, io::Error>(String::from("db")) };
+    /// let chain = load.and_then_async_ref_send(|host| Box::pin(async move { Ok(host.len()) }));
+    /// let (host, len) = tokio::spawn(chain).await.unwrap().unwrap();
+    ///
+    /// assert_eq!((host.as_str(), len), ("db", 2));
+    /// # }
+    /// ```
+    fn and_then_async_ref_send<'a, F, U>(self, f: F) -> AndThenAsyncRefSend<'a, T, U, E>
+    where
+        Self: Send + 'a,
+        T: Send + Sync,
+        F: for<'b> FnOnce(&'b T) -> RefFutureSend<'b, U, E> + Send + 'a,
+    {
+        AndThenAsyncRefSend {
+            inner: Box::pin(async move {
+                let value = self.await?;
+                let second = f(&value).await?;
+                Ok((value, second))
+            }),
+        }
+    }
+}
+
+impl<T, E, F> AsyncResultChainExt<T, E> for F where F: Future<Output = Result<T, E>> + Sized {}
+
+/// Extension trait for futures already resolving to `Result<T, AsyncError<E>>`.
+///
+/// These combinators compose with existing `AsyncError` contexts instead of replacing them.
+pub trait AsyncErrorChainExt<T, E: Error + 'static>:
+    Future<Output = Result<T, AsyncError<E>>> + Sized
+{
+    /// Chains an asynchronous computation, merging contexts if it fails.
+    ///
+    /// The closure `ctx` describes the first stage's value and is called once the first
+    /// stage succeeds. If the second stage then fails, that context is pushed onto the
+    /// second stage's error as its outermost context, so the final `Display` reads
+    /// `"<first stage context>: <second stage contexts>: <error>"`.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create the first stage's context from its value.
+    /// - `f`: the chaining closure producing the next future.
+    ///
+    /// # Returns
+    /// A future that resolves to the chained computation’s `Result`.
+    fn and_then_async_ctx_merge<Fut, F, C, U>(
+        self,
+        ctx: C,
+        f: F,
+    ) -> AndThenCtxMerge<Self, Fut, F, C>
+    where
+        C: FnOnce(&T) -> String,
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<U, AsyncError<E>>>,
+    {
+        AndThenCtxMerge {
+            state: AndThenAsyncState::First(self, Some((f, ctx))),
+            context: None,
+        }
+    }
+
+    /// Chains an asynchronous computation, measuring the wall-clock time of each stage.
+    ///
+    /// On success resolves to the second stage's value together with `StageTimings`.
+    /// On failure the timings gathered so far are recorded in the error's metadata under
+    /// `first_stage_duration` and, if the second stage ran, `second_stage_duration`.
+    ///
+    /// # Parameters
+    /// - `f`: the chaining closure producing the next future.
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<(U, StageTimings), AsyncError<E>>`.
+    fn and_then_async_timed_report<Fut, F, U>(self, f: F) -> AndThenTimed<Self, Fut, F>
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<U, AsyncError<E>>>,
+    {
+        AndThenTimed {
+            state: AndThenAsyncState::First(self, Some(f)),
+            stage_started: None,
+            first: None,
+        }
+    }
+
+    /// Pushes an outer context onto an already wrapped error.
+    ///
+    /// Calling `.with_context()` here would produce `AsyncError<AsyncError<E>>`; this
+    /// instead stacks the new context on the existing wrapper. Hooks are not fired
+    /// again, since they already ran when the error was first wrapped.
+    ///
+    /// This is a separate method because `.with_context()` itself cannot flatten on
+    /// stable Rust. `AsyncError<E>` is an `Error`, so a second `with_context` for these
+    /// futures would be ambiguous with the one every `Result` future gets, and a
+    /// conversion trait treating `AsyncError<E>` specially would overlap with its blanket
+    /// impl for all errors. Choosing between the two needs specialization.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from the inner error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, AsyncError<E>>` with the context pushed.
+    fn with_outer_context<C>(self, ctx: C) -> WithOuterContext<Self, C>
+    where
+        C: FnOnce(&E) -> String,
+    {
+        WithOuterContext {
+            future: self,
+            context: Some(ctx),
+        }
+    }
+}
+
+impl<T, E, Fut> AsyncErrorChainExt<T, E> for Fut
+where
+    E: Error + 'static,
+    Fut: Future<Output = Result<T, AsyncError<E>>> + Sized,
+{
+}
+
+/// Wall-clock durations of the two stages of `.and_then_async_timed_report()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageTimings {
+    /// Time from the first poll until the first stage resolved.
+    pub first: Duration,
+    /// Time from the start of the second stage until it resolved.
+    pub second: Duration,
+}
+
+/// Future produced by `.and_then_async_timed_report()`.
+///
+/// Records an `Instant` at each state transition of the underlying two-stage chain.
+pub struct AndThenTimed<Fut1, Fut2, F> {
+    state: AndThenAsyncState<Fut1, Fut2, F>,
+    stage_started: Option<Instant>,
+    first: Option<Duration>,
+}
+
+impl<Fut1, Fut2, F, T, U, E> Future for AndThenTimed<Fut1, Fut2, F>
+where
+    Fut1: Future<Output = Result<T, AsyncError<E>>>,
+    Fut2: Future<Output = Result<U, AsyncError<E>>>,
+    F: FnOnce(T) -> Fut2,
+    E: Error + 'static,
+{
+    type Output = Result<(U, StageTimings), AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: Moving pinned fields in pattern matching is allowed here.
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                AndThenAsyncState::First(fut1, maybe_f) => {
+                    let started = *this.stage_started.get_or_insert_with(Instant::now);
+                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
+                    let result = match fut1_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(result) => result,
+                    };
+                    let first = started.elapsed();
+                    match result {
+                        Ok(value) => {
+                            let f = maybe_f.take().expect("FnOnce already taken");
+                            this.first = Some(first);
+                            this.stage_started = Some(Instant::now());
+                            this.state = AndThenAsyncState::Second(f(value));
+                        }
+                        Err(e) => {
+                            this.state = AndThenAsyncState::Done;
+                            let e = e.with_metadata("first_stage_duration", format!("{:?}", first));
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                }
+                AndThenAsyncState::Second(fut2) => {
+                    let fut2_pin = unsafe { Pin::new_unchecked(fut2) };
+                    let result = match fut2_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(result) => result,
+                    };
+                    this.state = AndThenAsyncState::Done;
+                    let timings = StageTimings {
+                        first: this.first.unwrap_or_default(),
+                        second: this.stage_started.map(|t| t.elapsed()).unwrap_or_default(),
+                    };
+                    return Poll::Ready(match result {
+                        Ok(value) => Ok((value, timings)),
+                        Err(e) => Err(e
+                            .with_metadata("first_stage_duration", format!("{:?}", timings.first))
+                            .with_metadata(
+                                "second_stage_duration",
+                                format!("{:?}", timings.second),
+                            )),
+                    });
+                }
+                AndThenAsyncState::Done => panic!("Polled after completion"),
+            }
+        }
+    }
+}
+
+/// Future produced by `.with_outer_context()` to stack context on a wrapped error.
+pub struct WithOuterContext<Fut, C> {
+    future: Fut,
+    context: Option<C>,
+}
+
+impl<Fut, T, E, C> Future for WithOuterContext<Fut, C>
+where
+    Fut: Future<Output = Result<T, AsyncError<E>>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> String,
+{
+    type Output = Result<T, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: projected pinned fields can be safely accessed
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        match fut.poll(cx) {
+            Poll::Ready(Ok(val)) => {
+                this.context = None;
+                Poll::Ready(Ok(val))
+            }
+            Poll::Ready(Err(err)) => {
+                let ctx = this.context.take().map(|f| f(err.inner_error()));
+                Poll::Ready(Err(err.append_context(ctx.unwrap_or_default())))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future produced by `.and_then_async_ctx_merge()`.
+///
+/// Behaves like `AndThenAsync`, additionally holding the first stage's context until
+/// the second stage completes.
+pub struct AndThenCtxMerge<Fut1, Fut2, F, C> {
+    state: AndThenAsyncState<Fut1, Fut2, (F, C)>,
+    context: Option<String>,
+}
+
+impl<Fut1, Fut2, F, C, T, U, E> Future for AndThenCtxMerge<Fut1, Fut2, F, C>
+where
+    Fut1: Future<Output = Result<T, AsyncError<E>>>,
+    Fut2: Future<Output = Result<U, AsyncError<E>>>,
+    F: FnOnce(T) -> Fut2,
+    C: FnOnce(&T) -> String,
+    E: Error + 'static,
+{
+    type Output = Result<U, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: Moving pinned fields in pattern matching is allowed here.
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                AndThenAsyncState::First(fut1, maybe_f) => {
+                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
+                    match fut1_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(value)) => {
+                            let (f, ctx) = maybe_f.take().expect("FnOnce already taken");
+                            this.context = Some(ctx(&value));
+                            this.state = AndThenAsyncState::Second(f(value));
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.state = AndThenAsyncState::Done;
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                }
+                AndThenAsyncState::Second(fut2) => {
+                    let fut2_pin = unsafe { Pin::new_unchecked(fut2) };
+                    match fut2_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(result) => {
+                            this.state = AndThenAsyncState::Done;
+                            let context = this.context.take().unwrap_or_default();
+                            return Poll::Ready(result.map_err(|e| e.append_context(context)));
+                        }
+                    }
+                }
+                AndThenAsyncState::Done => panic!("Polled after completion"),
+            }
+        }
+    }
+}
+
+/// Internal enum representing the current state of the chained async future.
+pub enum AndThenAsyncState<Fut1, Fut2, F> {
+    First(Fut1, Option<F>),
+    Second(Fut2),
+    Done,
+}
+
+/// Future that chains two async computations sequentially.
+///
+/// Internally manages polling of the first, then the second future produced by the chaining closure.
+pub struct AndThenAsync<Fut1, Fut2, F> {
+    state: AndThenAsyncState<Fut1, Fut2, F>,
+}
+
+impl<Fut1, Fut2, F> AndThenAsync<Fut1, Fut2, F> {
+    /// Registers a callback run if the chain is dropped before completing.
+    ///
+    /// Dropping the chain already cancels whichever stage is in flight; the callback
+    /// allows extra best-effort cleanup. It runs synchronously inside `Drop`, before the
+    /// in-flight stage itself is dropped.
+    pub fn on_cancel<Cl>(self, callback: Cl) -> OnCancel<Self, Cl>
+    where
+        Cl: FnOnce(),
+    {
+        OnCancel {
+            future: self,
+            callback: Some(callback),
+        }
+    }
+}
+
+/// Future produced by `.and_then_async_while()`.
+pub struct AndThenAsyncWhile<Fut1, Fut2, P, F> {
+    state: AndThenAsyncState<Fut1, Fut2, F>,
+    continue_pred: P,
+}
+
+impl<Fut1, Fut2, P, F, T, U, E> Future for AndThenAsyncWhile<Fut1, Fut2, P, F>
+where
+    Fut1: Future<Output = Result<T, E>>,
+    Fut2: Future<Output = Result<U, E>>,
+    P: Fn(&T) -> bool,
+    F: FnOnce(T) -> Fut2,
+{
+    type Output = Result<ControlFlow<T, U>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the in-flight stage is never moved out of the pinned future
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                AndThenAsyncState::First(fut1, maybe_f) => {
+                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
+                    match fut1_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(value)) if (this.continue_pred)(&value) => {
+                            let f = maybe_f.take().expect("FnOnce already taken");
+                            this.state = AndThenAsyncState::Second(f(value));
+                        }
+                        Poll::Ready(result) => {
+                            this.state = AndThenAsyncState::Done;
+                            return Poll::Ready(result.map(ControlFlow::Break));
+                        }
+                    }
+                }
+                AndThenAsyncState::Second(fut2) => {
+                    let fut2_pin = unsafe { Pin::new_unchecked(fut2) };
+                    let result = match fut2_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(result) => result,
+                    };
+                    this.state = AndThenAsyncState::Done;
+                    return Poll::Ready(result.map(ControlFlow::Continue));
+                }
+                AndThenAsyncState::Done => panic!("Polled after completion"),
+            }
+        }
+    }
+}
+
+/// Future wrapper produced by `.on_cancel()` that runs a callback if dropped unfinished.
+pub struct OnCancel<Fut, Cl: FnOnce()> {
+    future: Fut,
+    callback: Option<Cl>,
+}
+
+impl<Fut, Cl> Future for OnCancel<Fut, Cl>
+where
+    Fut: Future,
+    Cl: FnOnce(),
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: projected pinned fields can be safely accessed
+        let this = unsafe { self.get_unchecked_mut() };
+        let fut = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let output = fut.poll(cx);
+        if output.is_ready() {
+            // Completed, so dropping the wrapper later is not a cancellation
+            this.callback = None;
+        }
+        output
+    }
+}
+
+impl<Fut, Cl: FnOnce()> Drop for OnCancel<Fut, Cl> {
+    fn drop(&mut self) {
+        if let Some(callback) = self.callback.take() {
+            callback();
+        }
+    }
+}
+
+impl<Fut1, Fut2, F, T, U, E> Future for AndThenAsync<Fut1, Fut2, F>
+where
+    Fut1: Future<Output = Result<T, E>>,
+    Fut2: Future<Output = Result<U, E>>,
+    F: FnOnce(T) -> Fut2,
+{
+    type Output = Result<U, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: Moving pinned fields in pattern matching is allowed here.
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                AndThenAsyncState::First(fut1, maybe_f) => {
+                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
+                    match fut1_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(result) => match result {
+                            Ok(value) => {
+                                let f = maybe_f.take().expect("FnOnce already taken");
+                                let fut2 = f(value);
+                                this.state = AndThenAsyncState::Second(fut2);
+                            }
+                            Err(e) => {
+                                this.state = AndThenAsyncState::Done;
+                                return Poll::Ready(Err(e));
+                            }
+                        },
+                    }
+                }
+                AndThenAsyncState::Second(fut2) => {
+                    let fut2_pin = unsafe { Pin::new_unchecked(fut2) };
+                    match fut2_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(result) => {
+                            this.state = AndThenAsyncState::Done;
+                            return Poll::Ready(result);
+                        }
+                    }
+                }
+                AndThenAsyncState::Done => panic!("Polled after completion"),
+            }
+        }
+    }
+}
+
+/// Stream that runs a future, then flattens the stream produced from its value.
+///
+/// Internally reuses `AndThenAsyncState`, where the second state holds the stream.
+pub struct AndThenStream<Fut, S, F> {
+    state: AndThenAsyncState<Fut, S, F>,
+}
+
+impl<Fut, S, F, T, U, E> Stream for AndThenStream<Fut, S, F>
+where
+    Fut: Future<Output = Result<T, E>>,
+    S: Stream<Item = Result<U, E>>,
+    F: FnOnce(T) -> S,
+{
+    type Item = Result<U, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Safety: Moving pinned fields in pattern matching is allowed here.
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                AndThenAsyncState::First(fut, maybe_f) => {
+                    let fut_pin = unsafe { Pin::new_unchecked(fut) };
+                    match fut_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(value)) => {
+                            let f = maybe_f.take().expect("FnOnce already taken");
+                            this.state = AndThenAsyncState::Second(f(value));
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.state = AndThenAsyncState::Done;
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                }
+                AndThenAsyncState::Second(stream) => {
+                    let stream_pin = unsafe { Pin::new_unchecked(stream) };
+                    return match stream_pin.poll_next(cx) {
+                        Poll::Ready(None) => {
+                            this.state = AndThenAsyncState::Done;
+                            Poll::Ready(None)
+                        }
+                        other => other,
+                    };
+                }
+                AndThenAsyncState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Repeatedly runs a future-producing closure until a predicate accepts the result.
+///
+/// Useful for polling-until-done patterns, such as checking a job status until it
+/// completes. Resolves to the first value for which `done` returns `true`, or to the
+/// first error, wrapped with context recording the attempt it failed on. The future yields
+/// to the executor between attempts, so a factory whose futures resolve immediately does
+/// not monopolize the thread.
+///
+/// # Parameters
+/// - `factory`: closure producing a fresh future for each attempt.
+/// - `done`: predicate deciding whether a successful value ends the loop.
+///
+/// # Returns
+/// A future that resolves to `Result<T, AsyncError<E>>`.
+pub fn repeat_until_async<Fct, Fut, F, T, E>(factory: Fct, done: F) -> RepeatUntil<Fct, Fut, F>
+where
+    Fct: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    F: Fn(&T) -> bool,
+{
+    RepeatUntil {
+        factory,
+        done,
+        current: None,
+        attempts: 0,
+    }
+}
+
+/// Future produced by `repeat_until_async()`.
+pub struct RepeatUntil<Fct, Fut, F> {
+    factory: Fct,
+    done: F,
+    current: Option<Fut>,
+    attempts: u32,
+}
+
+impl<Fct, Fut, F, T, E> Future for RepeatUntil<Fct, Fut, F>
+where
+    Fct: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    F: Fn(&T) -> bool,
+    E: Error + 'static,
+{
+    type Output = Result<T, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the current future is only dropped in place, never moved
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.current.is_none() {
+            this.attempts += 1;
+            this.current = Some((this.factory)());
+        }
+        let fut = unsafe { Pin::new_unchecked(this.current.as_mut().unwrap()) };
+        let result = ready!(fut.poll(cx));
+        this.current = None;
+        match result {
+            Ok(value) if (this.done)(&value) => Poll::Ready(Ok(value)),
+            Ok(_) => {
+                // Yield before the next attempt so an immediately ready factory cannot
+                // starve the executor.
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(err) => {
+                let ctx = format!("failed on attempt {}", this.attempts);
+                Poll::Ready(Err(AsyncError::new(err).with_context(ctx)))
+            }
+        }
+    }
+}
+
+/// Polls all futures concurrently and resolves to the first success.
+///
+/// Useful for querying several replicas and taking whichever answers first. As soon as
+/// one future succeeds, the others are dropped. Only if every future fails does it resolve
+/// to a `MultiError` holding all errors in input order, each wrapped with context naming
+/// the index of the future that produced it. An empty `futures` resolves to an empty
+/// `MultiError`.
+///
+/// # Example
+/// ```
+/// # use async_err::select_ok_async;
+/// # use std::{io, sync::Arc, time::Duration};
+/// async fn query(replica: u32, delay_ms: u64, healthy: bool) -> Result<u32, io::Error> {
+///     tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+///     match healthy {
+///         true => Ok(replica),
+///         false => Err(io::Error::other("unavailable")),
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let first = select_ok_async(vec![query(0, 50, true), query(1, 0, false), query(2, 10, true)]);
+/// assert_eq!(first.await.unwrap(), 2);
+///
+/// // Losers are dropped as soon as one future wins
+/// let token = Arc::new(());
+/// let tracked = |delay_ms| {
+///     let token = token.clone();
+///     async move {
+///         let _token = token;
+///         query(0, delay_ms, true).await
+///     }
+/// };
+/// let mut race = Box::pin(select_ok_async(vec![tracked(0), tracked(1_000)]));
+/// assert!(race.as_mut().await.is_ok());
+/// assert_eq!(Arc::strong_count(&token), 1);
+///
+/// let err = select_ok_async(vec![query(0, 0, false), query(1, 0, false)])
+///     .await
+///     .unwrap_err();
+/// assert_eq!(err.to_string(), "2 errors: future 0: unavailable; future 1: unavailable");
+/// # }
+/// ```
+pub fn select_ok_async<F, T, E>(futures: Vec<F>) -> SelectOk<F, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+{
+    let errors = futures.iter().map(|_| None).collect();
+    SelectOk {
+        futures: futures.into_iter().map(Some).collect(),
+        errors,
+    }
+}
+
+/// Future produced by `select_ok_async()`.
+pub struct SelectOk<F, E> {
+    futures: Vec<Option<F>>,
+    errors: Vec<Option<E>>,
+}
+
+impl<F, T, E> Future for SelectOk<F, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+{
+    type Output = Result<T, MultiError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the vector is never resized while futures are pinned inside it, and
+        // finished futures are only dropped in place
+        let this = unsafe { self.get_unchecked_mut() };
+        for (i, slot) in this.futures.iter_mut().enumerate() {
+            let Some(fut) = slot.as_mut() else {
+                continue;
+            };
+            match unsafe { Pin::new_unchecked(fut) }.poll(cx) {
+                Poll::Pending => {}
+                Poll::Ready(Ok(value)) => {
+                    // Cancel the remaining futures
+                    this.futures.clear();
+                    this.errors.clear();
+                    return Poll::Ready(Ok(value));
+                }
+                Poll::Ready(Err(err)) => {
+                    *slot = None;
+                    this.errors[i] = Some(err);
+                }
+            }
+        }
+        if this.futures.iter().any(Option::is_some) {
+            return Poll::Pending;
+        }
+        let errors = this
+            .errors
+            .drain(..)
+            .enumerate()
+            .filter_map(|(i, err)| {
+                err.map(|err| AsyncError::new(err).with_context(format!("future {}", i)))
+            })
+            .collect();
+        this.futures.clear();
+        Poll::Ready(Err(MultiError::new(errors)))
+    }
+}
+
+/// Internal enum representing the current state of `AndThenRetrySecond`.
+pub enum RetrySecondState<Fut1, Fut2, T> {
+    First(Fut1),
+    Second(T, Option<Fut2>),
+    Done,
+}
+
+/// Future produced by `.and_then_async_retry_second()`.
+pub struct AndThenRetrySecond<Fut1, Fut2, Fct, T> {
+    state: RetrySecondState<Fut1, Fut2, T>,
+    factory: Fct,
+    max_attempts: u32,
+    attempts: u32,
+}
+
+impl<Fut1, Fut2, Fct, T, U, E> Future for AndThenRetrySecond<Fut1, Fut2, Fct, T>
+where
+    Fut1: Future<Output = Result<T, E>>,
+    Fut2: Future<Output = Result<U, E>>,
+    Fct: FnMut(&T) -> Fut2,
+    E: Error + 'static,
+{
+    type Output = Result<U, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: futures are only dropped in place, never moved
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                RetrySecondState::First(fut1) => {
+                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
+                    match fut1_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(value)) => {
+                            this.state = RetrySecondState::Second(value, None);
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.state = RetrySecondState::Done;
+                            let ctx = "first stage failed".to_string();
+                            return Poll::Ready(Err(AsyncError::new(e).with_context(ctx)));
+                        }
+                    }
+                }
+                RetrySecondState::Second(value, current) => {
+                    if current.is_none() {
+                        this.attempts += 1;
+                        *current = Some((this.factory)(value));
+                    }
+                    let fut2 = unsafe { Pin::new_unchecked(current.as_mut().unwrap()) };
+                    match fut2.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(val)) => {
+                            this.state = RetrySecondState::Done;
+                            return Poll::Ready(Ok(val));
+                        }
+                        Poll::Ready(Err(_)) if this.attempts < this.max_attempts => {
+                            *current = None;
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.state = RetrySecondState::Done;
+                            let ctx =
+                                format!("second stage failed after {} attempts", this.attempts);
+                            return Poll::Ready(Err(AsyncError::new(e).with_context(ctx)));
+                        }
+                    }
+                }
+                RetrySecondState::Done => panic!("Polled after completion"),
+            }
+        }
+    }
+}
+
+/// Internal enum representing the current state of `AndThenWithCleanup`.
+pub enum CleanupState<Fut1, Fut2, ClFut, E> {
+    First(Fut1),
+    Second(Fut2),
+    Cleanup(ClFut, Option<E>),
+    Done,
+}
+
+/// Future produced by `.and_then_async_with_cleanup()`.
+pub struct AndThenWithCleanup<Fut1, Fut2, ClFut, F, Cl, T, E> {
+    state: CleanupState<Fut1, Fut2, ClFut, E>,
+    /// The first stage's value, kept for `cleanup` while the second stage runs.
+    value: Option<T>,
+    f: Option<F>,
+    cleanup: Option<Cl>,
+}
+
+impl<Fut1, Fut2, ClFut, F, Cl, T, U, E> Future
+    for AndThenWithCleanup<Fut1, Fut2, ClFut, F, Cl, T, E>
+where
+    Fut1: Future<Output = Result<T, E>>,
+    Fut2: Future<Output = Result<U, E>>,
+    ClFut: Future<Output = ()>,
+    F: FnOnce(T) -> Fut2,
+    Cl: FnOnce(T) -> ClFut,
+    T: Clone,
+{
+    type Output = Result<U, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: futures are only dropped in place, never moved
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match &mut this.state {
+                CleanupState::First(fut1) => {
+                    let fut1_pin = unsafe { Pin::new_unchecked(fut1) };
+                    match fut1_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(value)) => {
+                            let f = this.f.take().expect("FnOnce already taken");
+                            this.state = CleanupState::Second(f(value.clone()));
+                            this.value = Some(value);
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.state = CleanupState::Done;
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                }
+                CleanupState::Second(fut2) => {
+                    let fut2_pin = unsafe { Pin::new_unchecked(fut2) };
+                    match fut2_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(val)) => {
+                            this.state = CleanupState::Done;
+                            this.value = None;
+                            return Poll::Ready(Ok(val));
+                        }
+                        Poll::Ready(Err(e)) => {
+                            let value = this.value.take().expect("first stage value missing");
+                            let cleanup = this.cleanup.take().expect("FnOnce already taken");
+                            this.state = CleanupState::Cleanup(cleanup(value), Some(e));
+                        }
+                    }
+                }
+                CleanupState::Cleanup(cl_fut, err) => {
+                    let cl_pin = unsafe { Pin::new_unchecked(cl_fut) };
+                    match cl_pin.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(()) => {
+                            let e = err.take().expect("Polled after completion");
+                            this.state = CleanupState::Done;
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+                }
+                CleanupState::Done => panic!("Polled after completion"),
+            }
+        }
+    }
+}
+
+/// Boxed second-stage future borrowing the first stage's value, used by `.and_then_async_ref()`.
+pub type RefFuture<'a, U, E> = Pin<Box<dyn Future<Output = Result<U, E>> + 'a>>;
+
+/// Future produced by `.and_then_async_ref()`.
+pub struct AndThenAsyncRef<'a, T, U, E> {
+    inner: RefFuture<'a, (T, U), E>,
+}
+
+impl<T, U, E> Future for AndThenAsyncRef<'_, T, U, E> {
+    type Output = Result<(T, U), E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+/// `Send` second-stage future borrowing the first stage's value, used by
+/// `.and_then_async_ref_send()`.
+pub type RefFutureSend<'a, U, E> = Pin<Box<dyn Future<Output = Result<U, E>> + Send + 'a>>;
+
+/// Future produced by `.and_then_async_ref_send()`.
+pub struct AndThenAsyncRefSend<'a, T, U, E> {
+    inner: RefFutureSend<'a, (T, U), E>,
+}
+
+impl<T, U, E> Future for AndThenAsyncRefSend<'_, T, U, E> {
+    type Output = Result<(T, U), E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
@@ -1,26 +1,154 @@
 #![cfg(feature = "hooks")]
-use crate::AsyncError;
+use crate::{AsyncError, BoxedError, Severity};
+use core::error::Error;
 use downcast_rs::{impl_downcast, DowncastSync};
 use once_cell::sync::Lazy;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::{
     any::TypeId,
-    collections::HashMap,
-    error::Error,
+    cell::RefCell,
+    collections::{
+        hash_map::{DefaultHasher, Entry},
+        HashMap,
+    },
+    future::Future,
+    hash::{Hash, Hasher},
+    pin::Pin,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc,
     },
+    task::{Context, Poll, Waker},
 };
 
-static TIMESTAMP_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Settings shaping default hook output and registry diagnostics.
+///
+/// Never changed in place: every setter installs a changed copy, so a reader holding an
+/// `Arc` of it always sees one consistent set of values.
+#[derive(Clone)]
+struct HookSettings {
+    timestamps: bool,
+    timestamp_threshold: Option<Severity>,
+    format: HookFormat,
+    sink: Option<fn(&str)>,
+    default_context: Option<String>,
+    max_context_len: usize,
+    warning_sink: Option<fn(&str)>,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            timestamps: false,
+            timestamp_threshold: None,
+            format: HookFormat::Pretty,
+            sink: None,
+            default_context: None,
+            max_context_len: usize::MAX,
+            warning_sink: None,
+        }
+    }
+}
+
+/// The current settings. The lock is only held to clone or replace the `Arc`, never while
+/// hooks or sinks run.
+static SETTINGS: Lazy<RwLock<Arc<HookSettings>>> = Lazy::new(Default::default);
+
+/// Configuration a hook invocation runs with, read once when it starts.
+struct PinnedConfig {
+    settings: Arc<HookSettings>,
+    max_context_depth: usize,
+    #[cfg(feature = "regex")]
+    redaction: Arc<Vec<regex::Regex>>,
+}
+
+thread_local! {
+    /// Configuration pinned by the outermost hook invocation running on this thread.
+    static PINNED_CONFIG: RefCell<Option<PinnedConfig>> = const { RefCell::new(None) };
+}
+
+/// Returns the settings in effect: those pinned by the hook invocation running on this
+/// thread, or else the current ones.
+fn settings() -> Arc<HookSettings> {
+    PINNED_CONFIG
+        .with(|pinned| {
+            pinned
+                .borrow()
+                .as_ref()
+                .map(|config| Arc::clone(&config.settings))
+        })
+        .unwrap_or_else(|| Arc::clone(&SETTINGS.read()))
+}
+
+/// Installs a copy of the current settings changed by `f`.
+fn update_settings(f: impl FnOnce(&mut HookSettings)) {
+    let mut current = SETTINGS.write();
+    let mut settings = HookSettings::clone(&current);
+    f(&mut settings);
+    *current = Arc::new(settings);
+}
+
+/// Returns the context depth pinned by the hook invocation running on this thread.
+pub(crate) fn pinned_max_context_depth() -> Option<usize> {
+    PINNED_CONFIG.with(|pinned| {
+        pinned
+            .borrow()
+            .as_ref()
+            .map(|config| config.max_context_depth)
+    })
+}
+
+/// Returns the redaction patterns pinned by the hook invocation running on this thread.
+#[cfg(feature = "regex")]
+pub(crate) fn pinned_redaction() -> Option<Arc<Vec<regex::Regex>>> {
+    PINNED_CONFIG.with(|pinned| {
+        pinned
+            .borrow()
+            .as_ref()
+            .map(|config| Arc::clone(&config.redaction))
+    })
+}
+
+/// Pins the configuration for the hooks run while it is held, unless an enclosing
+/// invocation on this thread already did.
+struct ConfigPin {
+    pinned: bool,
+}
+
+impl ConfigPin {
+    fn enter() -> Self {
+        if PINNED_CONFIG.with(|pinned| pinned.borrow().is_some()) {
+            return Self { pinned: false };
+        }
+        // Read under the lock `HookConfig::apply` installs its settings under
+        let config = {
+            let settings = SETTINGS.read();
+            PinnedConfig {
+                settings: Arc::clone(&settings),
+                max_context_depth: crate::error::max_context_depth(),
+                #[cfg(feature = "regex")]
+                redaction: crate::redact::redaction_patterns(),
+            }
+        };
+        PINNED_CONFIG.with(|pinned| *pinned.borrow_mut() = Some(config));
+        Self { pinned: true }
+    }
+}
+
+impl Drop for ConfigPin {
+    fn drop(&mut self) {
+        if self.pinned {
+            PINNED_CONFIG.with(|pinned| pinned.borrow_mut().take());
+        }
+    }
+}
 
 /// Enable timestamped hook output globally.
 ///
 /// When enabled, async error hooks will include a timestamp prefix in their output.
 /// This can help correlate error logs with event times.
 pub fn enable_hook_timestamps() {
-    TIMESTAMP_ENABLED.store(true, Ordering::SeqCst);
+    update_settings(|settings| settings.timestamps = true);
 }
 
 /// Disable timestamped hook output globally.
@@ -28,7 +156,403 @@ pub fn enable_hook_timestamps() {
 /// Disabling timestamps causes hook output to omit the time prefix,
 /// producing simpler logs.
 pub fn disable_hook_timestamps() {
-    TIMESTAMP_ENABLED.store(false, Ordering::SeqCst);
+    update_settings(|settings| settings.timestamps = false);
+}
+
+/// Returns whether hook output is currently timestamped.
+pub fn hook_timestamps_enabled() -> bool {
+    settings().timestamps
+}
+
+/// Only timestamp hook output for errors at or above `severity`.
+///
+/// Has no effect while timestamps are disabled. Errors below the threshold are logged
+/// without the time prefix, keeping e.g. warning logs terse.
+///
+/// # Example
+/// ```
+/// # use async_err::hooks::{
+/// #     enable_hook_timestamps, format_hook_message, set_timestamp_severity_threshold,
+/// # };
+/// # use async_err::{AsyncError, Severity};
+/// # use std::io;
+/// enable_hook_timestamps();
+/// set_timestamp_severity_threshold(Severity::Critical);
+///
+/// let warning = AsyncError::new(io::Error::other("slow disk")).with_severity(Severity::Warning);
+/// let critical = AsyncError::new(io::Error::other("disk gone")).with_severity(Severity::Critical);
+/// assert!(format_hook_message(&warning).starts_with("[WARN] AsyncError Hook Triggered"));
+/// assert!(!format_hook_message(&critical).starts_with("[CRITICAL] AsyncError Hook Triggered"));
+/// ```
+pub fn set_timestamp_severity_threshold(severity: Severity) {
+    update_settings(|settings| settings.timestamp_threshold = Some(severity));
+}
+
+/// Removes the threshold set by `set_timestamp_severity_threshold`, timestamping all
+/// errors again.
+pub fn clear_timestamp_severity_threshold() {
+    update_settings(|settings| settings.timestamp_threshold = None);
+}
+
+/// Returns whether hook output for an error of `severity` is timestamped under `settings`.
+fn timestamps_for(settings: &HookSettings, severity: Severity) -> bool {
+    settings.timestamps
+        && settings
+            .timestamp_threshold
+            .map_or(true, |threshold| severity >= threshold)
+}
+
+/// An error whose hooks have not fired yet, produced by `.with_context_deferred()`.
+///
+/// Derefs to the wrapped `AsyncError`. Call `fire()` once the error is actually handled
+/// to invoke the hooks for it; repeated calls do nothing.
+///
+/// # Example
+/// ```
+/// # use async_err::{register_hook, AsyncError, AsyncErrorHook, AsyncResultExt};
+/// # use std::{io, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+/// struct CountHook(AtomicUsize);
+///
+/// impl AsyncErrorHook<io::Error> for CountHook {
+///     fn on_error(&self, _error: &AsyncError<io::Error>) {
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let hook = Arc::new(CountHook(AtomicUsize::new(0)));
+/// register_hook::<io::Error>(hook.clone());
+///
+/// let deferred = async { Err::<(), _>(io::Error::other("timeout")) }
+///     .with_context_deferred(|_| "syncing".to_string())
+///     .await
+///     .unwrap_err();
+/// assert_eq!(hook.0.load(Ordering::SeqCst), 0);
+///
+/// deferred.fire();
+/// deferred.fire();
+/// assert_eq!(hook.0.load(Ordering::SeqCst), 1);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DeferredHooks<E: Error + 'static> {
+    error: AsyncError<E>,
+}
+
+impl<E: Error + 'static> DeferredHooks<E> {
+    pub(crate) fn new(error: AsyncError<E>) -> Self {
+        Self { error }
+    }
+
+    /// Invokes the hooks for the wrapped error, at most once.
+    pub fn fire(&self) {
+        if self.error.invoke_hooks_once() {
+            invoke_hooks(&self.error);
+        }
+    }
+
+    /// Returns the wrapped error without firing its hooks.
+    pub fn into_error(self) -> AsyncError<E> {
+        self.error
+    }
+}
+
+impl<E: Error + 'static> std::ops::Deref for DeferredHooks<E> {
+    type Target = AsyncError<E>;
+
+    fn deref(&self) -> &AsyncError<E> {
+        &self.error
+    }
+}
+
+static HOOKS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable all hook invocation globally, without unregistering any hooks.
+///
+/// While disabled, `invoke_hooks` returns after a single relaxed atomic load, so hook
+/// side effects can be switched off cheaply in benchmarks or latency-critical sections.
+/// Hooks are enabled by default.
+///
+/// # Example
+/// ```
+/// # use async_err::{hooks::set_hooks_enabled, register_hook, AsyncError, AsyncErrorHook};
+/// # use std::{io, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+/// struct CountHook(AtomicUsize);
+///
+/// impl AsyncErrorHook<io::Error> for CountHook {
+///     fn on_error(&self, _error: &AsyncError<io::Error>) {
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// let hook = Arc::new(CountHook(AtomicUsize::new(0)));
+/// register_hook::<io::Error>(hook.clone());
+///
+/// set_hooks_enabled(false);
+/// let _ = AsyncError::new(io::Error::other("quiet")).with_context("benchmark".to_string());
+/// assert_eq!(hook.0.load(Ordering::SeqCst), 0);
+///
+/// set_hooks_enabled(true);
+/// let _ = AsyncError::new(io::Error::other("loud")).with_context("production".to_string());
+/// assert_eq!(hook.0.load(Ordering::SeqCst), 1);
+/// ```
+pub fn set_hooks_enabled(enabled: bool) {
+    HOOKS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether hook invocation is currently enabled.
+pub fn hooks_enabled() -> bool {
+    HOOKS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// When hooks run relative to the call that triggers them, e.g. `AsyncError::with_context`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HookDispatchMode {
+    /// Hooks run on the calling thread and have all returned before the call does. This
+    /// is the default.
+    #[default]
+    Sync,
+    /// The call returns immediately; hooks receive a copy of the error on a background
+    /// thread. Await `drain_hooks` to wait for them.
+    AfterReturn,
+}
+
+/// Choose when hooks for errors of type `E` run.
+///
+/// In `AfterReturn` mode hooks see a copy of the error taken when they are triggered,
+/// which is why `E` must be `Clone`. The copy carries everything attached to the error;
+/// only template and lazy contexts are turned into their rendered text. Invocations are queued to a single background
+/// thread, so hooks still see errors in the order they were raised. The mode stays in
+/// effect until changed, whether or not hooks are registered.
+///
+/// # Example
+/// ```
+/// # use async_err::hooks::{drain_hooks, set_hook_dispatch_mode, HookDispatchMode};
+/// # use async_err::{register_hook, AsyncError, AsyncErrorHook};
+/// # use std::{fmt, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread, time::Duration};
+/// #[derive(Debug, Clone)]
+/// struct UploadError;
+/// # impl fmt::Display for UploadError {
+/// #     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str("upload failed") }
+/// # }
+/// # impl std::error::Error for UploadError {}
+///
+/// struct SlowHook(AtomicBool);
+///
+/// impl AsyncErrorHook<UploadError> for SlowHook {
+///     fn on_error(&self, _error: &AsyncError<UploadError>) {
+///         thread::sleep(Duration::from_millis(50));
+///         self.0.store(true, Ordering::SeqCst);
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let hook = Arc::new(SlowHook(AtomicBool::new(false)));
+/// register_hook::<UploadError>(hook.clone());
+/// set_hook_dispatch_mode::<UploadError>(HookDispatchMode::AfterReturn);
+///
+/// let _err = AsyncError::new(UploadError).with_context("syncing".to_string());
+/// drain_hooks().await;
+/// assert!(hook.0.load(Ordering::SeqCst));
+/// # }
+/// ```
+pub fn set_hook_dispatch_mode<E>(mode: HookDispatchMode)
+where
+    E: Error + Clone + Send + 'static,
+{
+    with_registry_mut::<E, _>(|registered| {
+        registered.dispatch = match mode {
+            HookDispatchMode::Sync => None,
+            HookDispatchMode::AfterReturn => Some(dispatch_after_return::<E>),
+        };
+    });
+}
+
+/// Returns when hooks for errors of type `E` run, as set by `set_hook_dispatch_mode`.
+pub fn hook_dispatch_mode<E: Error + 'static>() -> HookDispatchMode {
+    match dispatch_plan::<E>() {
+        Some((_, Some(_))) => HookDispatchMode::AfterReturn,
+        _ => HookDispatchMode::Sync,
+    }
+}
+
+/// Hands an invocation to the background thread, in `HookDispatchMode::AfterReturn`.
+type BackgroundDispatch<E> = fn(HookList<E>, &AsyncError<E>, InFlightGuard);
+
+/// A hook invocation queued for the background thread.
+type HookJob = Box<dyn FnOnce() + Send>;
+
+/// Queue of the background thread running `AfterReturn` hooks, started on first use.
+static HOOK_QUEUE: Lazy<mpsc::Sender<HookJob>> = Lazy::new(|| {
+    let (queue, jobs) = mpsc::channel::<HookJob>();
+    std::thread::Builder::new()
+        .name("async-err-hooks".to_string())
+        .spawn(move || {
+            for job in jobs {
+                // A panicking hook must not take the other queued invocations with it
+                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+            }
+        })
+        .expect("failed to spawn the hook dispatch thread");
+    queue
+});
+
+/// Queues `hooks` to run on a copy of `error`, staying in flight until they have run.
+fn dispatch_after_return<E>(hooks: HookList<E>, error: &AsyncError<E>, in_flight: InFlightGuard)
+where
+    E: Error + Clone + Send + 'static,
+{
+    let error = error.detached_copy();
+    let job: HookJob = Box::new(move || {
+        let _in_flight = in_flight;
+        // Errors of the same type wrapped by these hooks are skipped, as on other threads
+        let _guard = ReentrancyGuard::enter(TypeId::of::<E>());
+        run_hooks(&hooks, &error);
+    });
+    HOOK_QUEUE
+        .send(job)
+        .expect("hook dispatch thread has exited");
+}
+
+/// Builder configuring global hook behaviour in one place.
+///
+/// Only settings that were explicitly set are changed by `apply`; everything else keeps
+/// its current value.
+///
+/// # Example
+/// ```
+/// # use async_err::hooks::{HookConfig, HookFormat};
+/// HookConfig::new()
+///     .timestamps(true)
+///     .format(HookFormat::Compact)
+///     .sink(Some(|message| log_somewhere(message)))
+///     .default_context("<unknown operation>")
+///     .max_context_len(200)
+///     .max_context_depth(16)
+///     .apply();
+/// # fn log_somewhere(_: &str) {}
+/// # HookConfig::new().format(HookFormat::Pretty).sink(None).apply();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HookConfig {
+    timestamps: Option<bool>,
+    timestamp_threshold: Option<Option<Severity>>,
+    format: Option<HookFormat>,
+    sink: Option<Option<fn(&str)>>,
+    default_context: Option<String>,
+    max_context_len: Option<usize>,
+    max_context_depth: Option<usize>,
+    warning_sink: Option<Option<fn(&str)>>,
+    #[cfg(feature = "regex")]
+    redaction: Option<Vec<regex::Regex>>,
+}
+
+impl HookConfig {
+    /// Creates an empty configuration that changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables timestamped hook output.
+    pub fn timestamps(mut self, enabled: bool) -> Self {
+        self.timestamps = Some(enabled);
+        self
+    }
+
+    /// Sets or clears the timestamp severity threshold, see
+    /// `set_timestamp_severity_threshold`.
+    pub fn timestamp_threshold(mut self, severity: Option<Severity>) -> Self {
+        self.timestamp_threshold = Some(severity);
+        self
+    }
+
+    /// Sets the layout of default hook output, see `set_hook_format`.
+    pub fn format(mut self, format: HookFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Sets or clears the sink receiving default hook output, see `set_hook_sink`.
+    pub fn sink(mut self, sink: Option<fn(&str)>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Sets the text shown for errors without context, see `set_hook_default_context`.
+    pub fn default_context(mut self, context: impl Into<String>) -> Self {
+        self.default_context = Some(context.into());
+        self
+    }
+
+    /// Sets the length contexts are cut to in hook output, see `set_hook_max_context_len`.
+    pub fn max_context_len(mut self, len: usize) -> Self {
+        self.max_context_len = Some(len);
+        self
+    }
+
+    /// Sets or clears the registry warning sink, see `set_hook_warning_sink`.
+    pub fn warning_sink(mut self, sink: Option<fn(&str)>) -> Self {
+        self.warning_sink = Some(sink);
+        self
+    }
+
+    /// Sets the maximum context depth, see `set_max_context_depth`.
+    pub fn max_context_depth(mut self, depth: usize) -> Self {
+        self.max_context_depth = Some(depth);
+        self
+    }
+
+    /// Sets the redaction patterns, see `set_redaction`.
+    #[cfg(feature = "regex")]
+    pub fn redaction(mut self, patterns: Vec<regex::Regex>) -> Self {
+        self.redaction = Some(patterns);
+        self
+    }
+
+    /// Installs every configured setting.
+    ///
+    /// The settings are installed as one snapshot. Each hook invocation reads the
+    /// configuration once when it starts and keeps it until it returns, so hooks see either
+    /// none or all of the settings, and no lock is held while they run. Settings changed
+    /// through the individual setter functions are not synchronized with `apply`. Calling
+    /// `apply` from inside a hook is allowed; that hook keeps the configuration it started
+    /// with.
+    pub fn apply(self) {
+        let mut current = SETTINGS.write();
+        let mut settings = HookSettings::clone(&current);
+        if let Some(enabled) = self.timestamps {
+            settings.timestamps = enabled;
+        }
+        if let Some(threshold) = self.timestamp_threshold {
+            settings.timestamp_threshold = threshold;
+        }
+        if let Some(format) = self.format {
+            settings.format = format;
+        }
+        if let Some(sink) = self.sink {
+            settings.sink = sink;
+        }
+        if let Some(context) = self.default_context {
+            settings.default_context = Some(context);
+        }
+        if let Some(len) = self.max_context_len {
+            settings.max_context_len = len;
+        }
+        if let Some(sink) = self.warning_sink {
+            settings.warning_sink = sink;
+        }
+        // Hook invocations read these under the same lock, so they see them with the rest
+        if let Some(depth) = self.max_context_depth {
+            crate::error::set_max_context_depth(depth);
+        }
+        #[cfg(feature = "regex")]
+        if let Some(patterns) = self.redaction {
+            crate::redact::set_redaction(patterns);
+        }
+        *current = Arc::new(settings);
+    }
 }
 
 /// Trait representing hooks that run on async errors, supporting downcasting.
@@ -59,48 +583,298 @@ impl_downcast!(sync AsyncErrorHook<E> where E: Error + 'static);
 pub trait AsyncErrorHookDefault<E: Error + 'static>: AsyncErrorHook<E> {
     /// Default `on_error` implementation prints a timestamped message showing
     /// the error context and inner error details.
+    ///
+    /// See `format_hook_message` for the layout. The message goes to the sink set via
+    /// `set_hook_sink`, or to stderr.
     fn on_error(&self, error: &AsyncError<E>) {
-        let header = if TIMESTAMP_ENABLED.load(Ordering::SeqCst) {
-            #[cfg(feature = "chrono")]
-            {
-                use chrono::Local;
-                let now = Local::now();
-                format!(
-                    "{} | AsyncError Hook Triggered",
-                    now.format("%Y-%m-%d %H:%M:%S")
-                )
+        let message = format_hook_message(error);
+        let sink = settings().sink;
+        match sink {
+            Some(sink) => sink(&message),
+            None => eprintln!("{}", message),
+        }
+    }
+}
+
+impl<E: Error + 'static, T> AsyncErrorHookDefault<E> for T where T: AsyncErrorHook<E> {}
+
+/// Layout of the messages produced by `format_hook_message`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HookFormat {
+    /// A header line, then the context and the inner error on lines of their own. This is
+    /// the default.
+    #[default]
+    Pretty,
+    /// A single line: `[ERROR] <context>: <inner error>`, with any timestamp after the
+    /// severity and any operations before the context.
+    Compact,
+}
+
+/// Choose the layout of default hook output.
+///
+/// # Example
+/// ```
+/// # use async_err::hooks::{format_hook_message, set_hook_format, HookFormat};
+/// # use async_err::AsyncError;
+/// # use std::io;
+/// set_hook_format(HookFormat::Compact);
+/// let err = AsyncError::new(io::Error::other("refused")).append_context("connecting".to_string());
+/// assert_eq!(format_hook_message(&err), "[ERROR] connecting: refused");
+/// # set_hook_format(HookFormat::Pretty);
+/// ```
+pub fn set_hook_format(format: HookFormat) {
+    update_settings(|settings| settings.format = format);
+}
+
+/// Route the output of the default hook to `sink` instead of stderr.
+///
+/// # Example
+/// ```
+/// # use async_err::hooks::{clear_hook_sink, set_hook_sink};
+/// set_hook_sink(|message| log_somewhere(message));
+/// # fn log_somewhere(_: &str) {}
+/// # clear_hook_sink();
+/// ```
+pub fn set_hook_sink(sink: fn(&str)) {
+    update_settings(|settings| settings.sink = Some(sink));
+}
+
+/// Restore the default of printing default hook output to stderr.
+pub fn clear_hook_sink() {
+    update_settings(|settings| settings.sink = None);
+}
+
+/// Set the text default hook output shows for errors that have no context.
+///
+/// Defaults to `<none>`.
+pub fn set_hook_default_context(context: impl Into<String>) {
+    let context = context.into();
+    update_settings(|settings| settings.default_context = Some(context));
+}
+
+/// Cut contexts longer than `len` characters in default hook output, marking the cut
+/// with `…`.
+///
+/// Only the hook output is shortened; the error keeps its full context. Unlimited by
+/// default.
+pub fn set_hook_max_context_len(len: usize) {
+    update_settings(|settings| settings.max_context_len = len);
+}
+
+/// Formats the message printed by the default hook.
+///
+/// The output is prefixed with the error's severity, e.g. `[WARN]` or `[CRITICAL]`, and
+/// a timestamp if enabled for that severity. Operations captured with the `operations`
+/// feature are listed before the context. The layout, the text used for errors
+/// without context and the maximum context length follow `set_hook_format`,
+/// `set_hook_default_context` and `set_hook_max_context_len`. With the `regex` feature,
+/// patterns set via `set_redaction` are masked. The inner error follows
+/// `with_debug_rendering`, as in `Display`.
+pub fn format_hook_message<E: Error + 'static>(error: &AsyncError<E>) -> String {
+    let settings = settings();
+    let timestamp = timestamps_for(&settings, error.severity()).then(|| {
+        #[cfg(feature = "chrono")]
+        {
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+        }
+        #[cfg(not(feature = "chrono"))]
+        {
+            let now = std::time::SystemTime::now();
+            match now.duration_since(std::time::UNIX_EPOCH) {
+                Ok(dur) => format!("[{}]", dur.as_secs()),
+                Err(_) => "[time unknown]".to_string(),
             }
-            #[cfg(not(feature = "chrono"))]
-            {
-                let now = std::time::SystemTime::now();
-                match now.duration_since(std::time::UNIX_EPOCH) {
-                    Ok(dur) => format!("[{}] | AsyncError Hook Triggered", dur.as_secs()),
-                    Err(_) => "[time unknown] | AsyncError Hook Triggered".to_string(),
-                }
+        }
+    });
+    let context = match error.rendered_context() {
+        Some(ctx) => truncate_context(&ctx, settings.max_context_len),
+        None => settings
+            .default_context
+            .clone()
+            .unwrap_or_else(|| "<none>".to_string()),
+    };
+    let operations = error.operations().join(": ");
+    let mut inner = String::new();
+    let _ = error.write_inner(&mut inner);
+    #[cfg(feature = "regex")]
+    let (operations, context, inner) = (
+        crate::redact::redact(&operations),
+        crate::redact::redact(&context),
+        crate::redact::redact(&inner),
+    );
+    let label = error.severity().label();
+    match (settings.format, timestamp) {
+        (HookFormat::Pretty, timestamp) => {
+            let header = match timestamp {
+                Some(timestamp) => format!("{} | AsyncError Hook Triggered", timestamp),
+                None => "AsyncError Hook Triggered".to_string(),
+            };
+            let operations = match operations.is_empty() {
+                true => String::new(),
+                false => format!("\n  Operations: {}", operations),
+            };
+            format!(
+                "[{}] {}{}\n  Context: {}\n  Inner error: {}\n------------------------------",
+                label, header, operations, context, inner
+            )
+        }
+        (HookFormat::Compact, timestamp) => {
+            let mut line = format!("[{}] ", label);
+            if let Some(timestamp) = timestamp {
+                line.push_str(&timestamp);
+                line.push_str(" | ");
             }
-        } else {
-            "AsyncError Hook Triggered".to_string()
-        };
-        let context = error.context().unwrap_or("<none>");
-        let msg = format!(
-            "{}\n  Context: {}\n  Inner error: {}\n------------------------------",
-            header,
-            context,
-            error.inner_error()
-        );
-        eprintln!("{}", msg);
+            if !operations.is_empty() {
+                line.push_str(&operations);
+                line.push_str(": ");
+            }
+            line.push_str(&format!("{}: {}", context, inner));
+            line
+        }
     }
 }
 
-impl<E: Error + 'static, T> AsyncErrorHookDefault<E> for T where T: AsyncErrorHook<E> {}
+/// Cuts `context` to at most `len` characters, the last of which becomes `…`.
+fn truncate_context(context: &str, len: usize) -> String {
+    if context.chars().count() <= len {
+        return context.to_string();
+    }
+    let mut cut: String = context.chars().take(len.saturating_sub(1)).collect();
+    if len > 0 {
+        cut.push('…');
+    }
+    cut
+}
+
+/// Shared, immutable list of hooks for one error type.
+type HookList<E> = Arc<[Arc<dyn AsyncErrorHook<E>>]>;
 
 /// Internal registry storing hooks for a specific error type `E`.
+///
+/// `snapshot` caches the hook list so invocations clone a single `Arc` instead of the
+/// whole vector. It is rebuilt under the write lock whenever registrations change.
 struct HookRegistry<E: Error + 'static> {
     hooks: Vec<Arc<dyn AsyncErrorHook<E>>>,
+    snapshot: HookList<E>,
+    /// Set in `HookDispatchMode::AfterReturn`, handing invocations to the background thread.
+    dispatch: Option<BackgroundDispatch<E>>,
 }
 
-static GLOBAL_HOOKS: Lazy<RwLock<HashMap<TypeId, Box<dyn std::any::Any + Send + Sync>>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
+impl<E: Error + 'static> HookRegistry<E> {
+    fn new() -> Self {
+        Self {
+            hooks: Vec::new(),
+            snapshot: Arc::from(Vec::new()),
+            dispatch: None,
+        }
+    }
+
+    /// Adds a hook unless the same instance is already registered.
+    ///
+    /// Returns `true` if the hook was added. The snapshot is not rebuilt.
+    fn insert(&mut self, hook: Arc<dyn AsyncErrorHook<E>>) -> bool {
+        if self
+            .hooks
+            .iter()
+            .any(|existing| Arc::ptr_eq(existing, &hook))
+        {
+            return false;
+        }
+        self.hooks.push(hook);
+        true
+    }
+
+    /// Rebuilds the cached snapshot from the current hook list.
+    fn refresh_snapshot(&mut self) {
+        self.snapshot = Arc::from(self.hooks.as_slice());
+    }
+}
+
+/// Type-erased map from error `TypeId` to its `HookRegistry<E>`.
+type HookMap = HashMap<TypeId, Box<dyn std::any::Any + Send + Sync>>;
+
+/// Number of independently locked shards the hook registry is split into.
+const HOOK_SHARDS: usize = 16;
+
+/// Hook registries, sharded by error `TypeId` so that registering or looking up hooks for
+/// different error types rarely contends on the same lock.
+static GLOBAL_HOOKS: Lazy<[RwLock<HookMap>; HOOK_SHARDS]> =
+    Lazy::new(|| std::array::from_fn(|_| RwLock::new(HashMap::new())));
+
+/// Returns the registry shard holding the hooks for `type_id`.
+fn hook_shard(type_id: TypeId) -> &'static RwLock<HookMap> {
+    let mut hasher = DefaultHasher::new();
+    type_id.hash(&mut hasher);
+    &GLOBAL_HOOKS[hasher.finish() as usize % HOOK_SHARDS]
+}
+
+/// Type names seen at registration, with their `TypeId` and whether a collision was reported.
+type TypeNameMap = HashMap<&'static str, (TypeId, bool)>;
+
+/// Type names seen at registration, sharded by name like `GLOBAL_HOOKS` is by `TypeId`,
+/// so registrations for different error types rarely contend on the same lock.
+static HOOK_TYPE_NAMES: Lazy<[RwLock<TypeNameMap>; HOOK_SHARDS]> =
+    Lazy::new(|| std::array::from_fn(|_| RwLock::new(HashMap::new())));
+
+/// Returns the shard holding the type name record for `type_name`.
+fn type_name_shard(type_name: &str) -> &'static RwLock<TypeNameMap> {
+    let mut hasher = DefaultHasher::new();
+    type_name.hash(&mut hasher);
+    &HOOK_TYPE_NAMES[hasher.finish() as usize % HOOK_SHARDS]
+}
+
+/// Records the type name registered for `type_id`, warning once per name on collisions.
+///
+/// Two distinct `TypeId`s sharing a type name usually means the same error type is pulled
+/// in from two versions of a crate, so hooks registered for one never fire for the other.
+///
+/// Returns `true` if a warning was emitted.
+pub(crate) fn note_hook_type_name(type_id: TypeId, type_name: &'static str) -> bool {
+    let shard = type_name_shard(type_name);
+    // Repeat registrations of a known type only need the shared lock
+    if shard.read().get(type_name).map(|(known_id, _)| *known_id) == Some(type_id) {
+        return false;
+    }
+    let mut names = shard.write();
+    let (known_id, warned) = names.entry(type_name).or_insert((type_id, false));
+    if *known_id == type_id || *warned {
+        return false;
+    }
+    *warned = true;
+    drop(names);
+    emit_warning(&format!(
+        "async-err: hooks registered for two distinct types named `{}`; \
+         this usually means duplicate versions of a dependency",
+        type_name
+    ));
+    true
+}
+
+/// Route registry diagnostics, such as type name collisions, to `sink` instead of stderr.
+///
+/// # Example
+/// ```
+/// # use async_err::hooks::{clear_hook_warning_sink, set_hook_warning_sink};
+/// set_hook_warning_sink(|message| log_somewhere(message));
+/// # fn log_somewhere(_: &str) {}
+/// # clear_hook_warning_sink();
+/// ```
+pub fn set_hook_warning_sink(sink: fn(&str)) {
+    update_settings(|settings| settings.warning_sink = Some(sink));
+}
+
+/// Restore the default of printing registry diagnostics to stderr.
+pub fn clear_hook_warning_sink() {
+    update_settings(|settings| settings.warning_sink = None);
+}
+
+fn emit_warning(message: &str) {
+    let sink = settings().warning_sink;
+    match sink {
+        Some(sink) => sink(message),
+        None => eprintln!("{}", message),
+    }
+}
 
 /// Register a new hook for a specific error type `E`.
 ///
@@ -114,22 +888,156 @@ static GLOBAL_HOOKS: Lazy<RwLock<HashMap<TypeId, Box<dyn std::any::Any + Send +
 /// # Notes
 ///
 /// This function requires explicit generic type annotation for `E` to clarify the error type.
+/// If another type with the same name was registered before, a one-time warning is sent to
+/// the sink set with `set_hook_warning_sink`, or printed to stderr.
 pub fn register_hook<E: Error + 'static>(hook: Arc<dyn AsyncErrorHook<E>>) {
-    let mut registry = GLOBAL_HOOKS.write();
+    register_hooks_batch(&[hook]);
+}
+
+/// Register several hooks for error type `E`, locking its registry shard only once.
+///
+/// Equivalent to calling `register_hook` for each hook in order, including the
+/// dedup-by-instance rule, but the registry is locked and its cached hook list rebuilt
+/// only once. Useful at startup when many hooks are registered together.
+///
+/// # Example
+/// ```
+/// # use async_err::{hooks::{get_hooks, register_hooks_batch}, AsyncError, AsyncErrorHook};
+/// # use std::{io, sync::Arc};
+/// struct NamedHook(usize);
+///
+/// impl AsyncErrorHook<io::Error> for NamedHook {
+///     fn on_error(&self, _error: &AsyncError<io::Error>) {}
+/// }
+///
+/// let hooks: Vec<Arc<dyn AsyncErrorHook<io::Error>>> =
+///     (0..5).map(|i| Arc::new(NamedHook(i)) as _).collect();
+/// register_hooks_batch(&hooks);
+///
+/// let registered = get_hooks::<io::Error>();
+/// assert_eq!(registered.len(), 5);
+/// for (i, hook) in registered.iter().enumerate() {
+///     assert_eq!(hook.as_any().downcast_ref::<NamedHook>().unwrap().0, i);
+/// }
+/// ```
+pub fn register_hooks_batch<E: Error + 'static>(hooks: &[Arc<dyn AsyncErrorHook<E>>]) {
+    let type_id = TypeId::of::<E>();
+    note_hook_type_name(type_id, std::any::type_name::<E>());
+    with_registry_mut::<E, _>(|registered| {
+        let mut changed = false;
+        for hook in hooks {
+            changed |= registered.insert(Arc::clone(hook));
+        }
+        if changed {
+            registered.refresh_snapshot();
+        }
+    });
+}
+
+/// Runs `f` on the registry for `E` under its shard's write lock, creating it if needed.
+fn with_registry_mut<E: Error + 'static, R>(f: impl FnOnce(&mut HookRegistry<E>) -> R) -> R {
     let type_id = TypeId::of::<E>();
+    let mut registry = hook_shard(type_id).write();
     let entry = registry
         .entry(type_id)
-        .or_insert_with(|| Box::new(HookRegistry::<E> { hooks: Vec::new() }));
-    let hooks = entry
+        .or_insert_with(|| Box::new(HookRegistry::<E>::new()));
+    f(entry
         .downcast_mut::<HookRegistry<E>>()
-        .expect("Type mismatch in global hooks registry");
-    if !hooks
-        .hooks
-        .iter()
-        .any(|existing| Arc::ptr_eq(existing, &hook))
-    {
-        hooks.hooks.push(hook);
-    }
+        .expect("Type mismatch in global hooks registry"))
+}
+
+/// A hook for type-erased errors, i.e. `DynAsyncError`.
+///
+/// This is implemented for every `AsyncErrorHook<BoxedError>`, so it only serves as a
+/// shorter name for hooks passed to `register_dyn_hook`.
+pub trait DynErrorHook: AsyncErrorHook<BoxedError> {}
+
+impl<T: AsyncErrorHook<BoxedError>> DynErrorHook for T {}
+
+/// Register a hook that fires for every `DynAsyncError`, whatever error was boxed.
+///
+/// Hooks are keyed by the inner error type, so a hook registered this way sees all
+/// errors wrapped as `AsyncError<BoxedError>` (e.g. via `into_dyn()`), but not errors
+/// still wrapped as their concrete type.
+///
+/// # Example
+/// ```
+/// # use async_err::{register_dyn_hook, AsyncError, AsyncErrorHook, BoxedError};
+/// # use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+/// struct CountHook(AtomicUsize);
+///
+/// impl AsyncErrorHook<BoxedError> for CountHook {
+///     fn on_error(&self, _error: &AsyncError<BoxedError>) {
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// let hook = Arc::new(CountHook(AtomicUsize::new(0)));
+/// register_dyn_hook(hook.clone());
+///
+/// let io = AsyncError::new(std::io::Error::other("disk full")).into_dyn();
+/// let fmt = AsyncError::new(std::fmt::Error).into_dyn();
+/// let _ = io.with_context("saving".to_string());
+/// let _ = fmt.with_context("rendering".to_string());
+/// assert_eq!(hook.0.load(Ordering::SeqCst), 2);
+/// ```
+pub fn register_dyn_hook(hook: Arc<dyn AsyncErrorHook<BoxedError>>) {
+    register_hook::<BoxedError>(hook);
+}
+
+/// Register one hook for several error types at once.
+///
+/// `register_hooks!(hook => [ErrA, ErrB])` expands to one `register_hook::<ErrX>(hook.clone())`
+/// call per listed type, so `hook` must be an `Arc` of a type implementing
+/// `AsyncErrorHook<ErrX>` for each of them.
+///
+/// # Example
+/// ```
+/// # use async_err::{register_hooks, AsyncError, AsyncErrorHook};
+/// # use std::{fmt, io, sync::Arc};
+/// struct LogHook;
+///
+/// impl AsyncErrorHook<io::Error> for LogHook {
+///     fn on_error(&self, error: &AsyncError<io::Error>) {
+///         eprintln!("{}", error);
+///     }
+/// }
+///
+/// impl AsyncErrorHook<fmt::Error> for LogHook {
+///     fn on_error(&self, error: &AsyncError<fmt::Error>) {
+///         eprintln!("{}", error);
+///     }
+/// }
+///
+/// let hook = Arc::new(LogHook);
+/// register_hooks!(hook => [io::Error, fmt::Error]);
+/// ```
+#[macro_export]
+macro_rules! register_hooks {
+    ($hook:expr => [$($err:ty),+ $(,)?]) => {{
+        let hook = &$hook;
+        $(
+            $crate::hooks::register_hook::<$err>(hook.clone());
+        )+
+    }};
+}
+
+/// Check whether a specific hook instance is registered for error type `E`.
+///
+/// Instances are compared with `Arc::ptr_eq`, matching the dedup rule of `register_hook`.
+/// This lets libraries make idempotent setup decisions before registering.
+pub fn is_hook_registered<E: Error + 'static>(hook: &Arc<dyn AsyncErrorHook<E>>) -> bool {
+    let type_id = TypeId::of::<E>();
+    let registry = hook_shard(type_id).read();
+    registry
+        .get(&type_id)
+        .and_then(|entry| entry.downcast_ref::<HookRegistry<E>>())
+        .is_some_and(|hooks| {
+            hooks
+                .hooks
+                .iter()
+                .any(|existing| Arc::ptr_eq(existing, hook))
+        })
 }
 
 /// Retrieve all registered hooks for the specified error type `E`.
@@ -140,20 +1048,88 @@ pub fn register_hook<E: Error + 'static>(hook: Arc<dyn AsyncErrorHook<E>>) {
 ///
 /// A vector of `Arc`-wrapped hooks. If no hooks are registered for `E`, returns an empty vector.
 pub fn get_hooks<E: Error + 'static>() -> Vec<Arc<dyn AsyncErrorHook<E>>> {
-    let registry = GLOBAL_HOOKS.read();
+    hooks_snapshot::<E>()
+        .map(|hooks| hooks.to_vec())
+        .unwrap_or_default()
+}
+
+/// Returns the cached hook list for `E`, cloning only the outer `Arc`.
+fn hooks_snapshot<E: Error + 'static>() -> Option<HookList<E>> {
+    let type_id = TypeId::of::<E>();
+    let registry = hook_shard(type_id).read();
     registry
-        .get(&TypeId::of::<E>())
+        .get(&type_id)
         .and_then(|entry| entry.downcast_ref::<HookRegistry<E>>())
-        .map(|hooks| hooks.hooks.clone())
-        .unwrap_or_default()
+        .map(|hooks| Arc::clone(&hooks.snapshot))
+}
+
+/// Returns the cached hook list for `E` and, in `AfterReturn` mode, its background dispatch.
+fn dispatch_plan<E: Error + 'static>() -> Option<(HookList<E>, Option<BackgroundDispatch<E>>)> {
+    let type_id = TypeId::of::<E>();
+    let registry = hook_shard(type_id).read();
+    registry
+        .get(&type_id)
+        .and_then(|entry| entry.downcast_ref::<HookRegistry<E>>())
+        .map(|hooks| (Arc::clone(&hooks.snapshot), hooks.dispatch))
+}
+
+/// Retrieve all registered hooks for error type `E` whose concrete type is `H`.
+///
+/// Each registered hook is downcast to `H`; hooks of other concrete types are skipped.
+/// Useful for finding and reconfiguring hooks of a particular kind.
+pub fn get_hooks_of_type<E, H>() -> Vec<Arc<H>>
+where
+    E: Error + 'static,
+    H: AsyncErrorHook<E>,
+{
+    get_hooks::<E>()
+        .into_iter()
+        .filter_map(|hook| hook.downcast_arc::<H>().ok())
+        .collect()
+}
+
+thread_local! {
+    /// Error types whose hooks are currently running on this thread.
+    static ACTIVE_HOOK_TYPES: RefCell<Vec<TypeId>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Thread-local re-entrancy guard for hook invocation of a single error type.
+///
+/// Removes its type from the active set on drop, so a panicking hook does not
+/// leave the type permanently blocked on this thread.
+struct ReentrancyGuard {
+    type_id: TypeId,
+}
+
+impl ReentrancyGuard {
+    /// Marks `type_id` as active on this thread, or returns `None` if it already is.
+    fn enter(type_id: TypeId) -> Option<Self> {
+        ACTIVE_HOOK_TYPES.with(|active| {
+            let mut active = active.borrow_mut();
+            if active.contains(&type_id) {
+                return None;
+            }
+            active.push(type_id);
+            Some(Self { type_id })
+        })
+    }
 }
 
-static HOOK_INVOKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        ACTIVE_HOOK_TYPES.with(|active| {
+            let mut active = active.borrow_mut();
+            if let Some(pos) = active.iter().rposition(|id| *id == self.type_id) {
+                active.remove(pos);
+            }
+        });
+    }
+}
 
-/// Invoke all registered hooks for this error, ensuring only one concurrent invocation.
+/// Invoke all registered hooks for this error, guarding against re-entrant invocation.
 ///
-/// Concurrent duplicate invocations are guarded by an atomic compare-and-swap counter,
-/// so only the first caller runs hooks, others return early.
+/// If a hook itself produces an `AsyncError<E>` of the same type `E`, the nested
+/// invocation is skipped instead of recursing. Hooks for other error types still run.
 ///
 /// # Parameters
 ///
@@ -161,17 +1137,293 @@ static HOOK_INVOKE_COUNTER: AtomicUsize = AtomicUsize::new(0);
 ///
 /// # Notes
 ///
-/// This method does not prevent sequential calls from multiple threads at different times.
+/// By default hooks run synchronously, in registration order, and have all returned when
+/// this does; see `set_hook_dispatch_mode` for running them after it returns instead.
+/// The guard is thread-local, so invocations on other threads are not affected.
+/// Nothing runs while hooks are disabled via `set_hooks_enabled(false)`. Hooks run with
+/// the configuration in effect when the invocation started, see `HookConfig::apply`.
+/// With the `hook-metrics` feature, each hook's run time is added to `hook_timings()`.
 pub fn invoke_hooks<E: Error + 'static>(error: &AsyncError<E>) {
-    // Attempt to set counter from 0 to 1 atomically; if already set, skip invocation
-    if HOOK_INVOKE_COUNTER
-        .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
-        .is_err()
-    {
+    if !hooks_enabled() {
+        return;
+    }
+    let Some(_guard) = ReentrancyGuard::enter(TypeId::of::<E>()) else {
+        return;
+    };
+    let Some((hooks, dispatch)) = dispatch_plan::<E>() else {
         return;
+    };
+    if hooks.is_empty() {
+        return;
+    }
+    let in_flight = InFlightGuard::enter();
+    match dispatch {
+        Some(dispatch) => dispatch(hooks, error, in_flight),
+        None => run_hooks(&hooks, error),
     }
-    for hook in get_hooks::<E>() {
+}
+
+/// Runs `hooks` for `error` on the current thread.
+fn run_hooks<E: Error + 'static>(hooks: &[Arc<dyn AsyncErrorHook<E>>], error: &AsyncError<E>) {
+    let _config = ConfigPin::enter();
+    for hook in hooks {
+        #[cfg(feature = "hook-metrics")]
+        let start = std::time::Instant::now();
         hook.on_error(error);
+        #[cfg(feature = "hook-metrics")]
+        record_hook_timing(hook.as_any().type_id(), start.elapsed());
+    }
+}
+
+/// Cumulative `on_error` time per concrete hook type.
+#[cfg(feature = "hook-metrics")]
+static HOOK_TIMINGS: Lazy<Mutex<HashMap<TypeId, std::time::Duration>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[cfg(feature = "hook-metrics")]
+fn record_hook_timing(hook_type: TypeId, elapsed: std::time::Duration) {
+    *HOOK_TIMINGS.lock().entry(hook_type).or_default() += elapsed;
+}
+
+/// Returns the total time spent in `on_error`, per concrete hook type.
+///
+/// Entries are keyed by the `TypeId` of the hook implementation (not the error type), so
+/// a hook registered for several error types is reported once. Compare against
+/// `TypeId::of::<MyHook>()` to find a specific hook.
+///
+/// # Example
+/// ```
+/// # use async_err::{hooks::hook_timings, register_hook, AsyncError, AsyncErrorHook};
+/// # use std::{any::TypeId, io, sync::Arc, time::Duration};
+/// struct SlowHook;
+///
+/// impl AsyncErrorHook<io::Error> for SlowHook {
+///     fn on_error(&self, _error: &AsyncError<io::Error>) {
+///         std::thread::sleep(Duration::from_millis(20));
+///     }
+/// }
+///
+/// register_hook::<io::Error>(Arc::new(SlowHook));
+/// let _ = AsyncError::new(io::Error::other("slow")).with_context("probing".to_string());
+///
+/// let (_, total) = hook_timings()
+///     .into_iter()
+///     .find(|(hook_type, _)| *hook_type == TypeId::of::<SlowHook>())
+///     .unwrap();
+/// assert!(total >= Duration::from_millis(20));
+/// ```
+#[cfg(feature = "hook-metrics")]
+pub fn hook_timings() -> Vec<(TypeId, std::time::Duration)> {
+    HOOK_TIMINGS
+        .lock()
+        .iter()
+        .map(|(&hook_type, &total)| (hook_type, total))
+        .collect()
+}
+
+/// Clears all timings recorded by `hook_timings`.
+#[cfg(feature = "hook-metrics")]
+pub fn reset_hook_timings() {
+    HOOK_TIMINGS.lock().clear();
+}
+
+/// Drain epoch new hook invocations join. `drain_hooks` moves it forward once no
+/// invocation of the previous epoch is running.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Hook invocations currently running, across all threads, counted in the slot of their
+/// epoch's parity.
+///
+/// An epoch only ends once the one before it has drained, so at most the current and the
+/// previous epoch have invocations running and two slots never mix epochs.
+static IN_FLIGHT: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+/// Returns the `IN_FLIGHT` slot counting invocations of `epoch`.
+fn in_flight_slot(epoch: u64) -> &'static AtomicUsize {
+    &IN_FLIGHT[(epoch % 2) as usize]
+}
+
+/// Tasks waiting in `drain_hooks` for the invocations of their epoch to finish, one waker
+/// per pending `Drain` future.
+static DRAIN_WAKERS: Lazy<Mutex<HashMap<u64, Waker>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Number of wakers in `DRAIN_WAKERS`, so finishing invocations only lock it while a
+/// `Drain` future is waiting.
+static DRAIN_WAITERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Source of `Drain` future ids.
+static NEXT_DRAIN_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Counts a hook invocation as in flight until dropped.
+struct InFlightGuard {
+    slot: &'static AtomicUsize,
+}
+
+impl InFlightGuard {
+    fn enter() -> Self {
+        loop {
+            let epoch = EPOCH.load(Ordering::SeqCst);
+            let slot = in_flight_slot(epoch);
+            slot.fetch_add(1, Ordering::SeqCst);
+            if EPOCH.load(Ordering::SeqCst) == epoch {
+                return Self { slot };
+            }
+            // The epoch moved on in between, so the slot may belong to a later epoch now
+            leave_in_flight(slot);
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        leave_in_flight(self.slot);
+    }
+}
+
+/// Removes an invocation from `slot`, waking waiting drains if it was the last one.
+fn leave_in_flight(slot: &AtomicUsize) {
+    if slot.fetch_sub(1, Ordering::SeqCst) == 1 && DRAIN_WAITERS.load(Ordering::SeqCst) > 0 {
+        for waker in DRAIN_WAKERS.lock().values() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+/// Starts a new epoch after `epoch` if no invocation of the epoch before it is running.
+fn advance_epoch(epoch: u64) {
+    if in_flight_slot(epoch + 1).load(Ordering::SeqCst) == 0 {
+        let _ = EPOCH.compare_exchange(epoch, epoch + 1, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+/// Wait until all hook invocations in flight when this is called have completed.
+///
+/// Intended for graceful shutdown, so the process does not exit while another thread
+/// is still running hooks (and losing their error logs). Resolves immediately if no
+/// hooks are running. Invocations starting after the call are not waited for, unless an
+/// earlier `drain_hooks` future is still waiting on its own invocations; either way hooks
+/// that keep firing cannot hold off the returned future indefinitely.
+///
+/// # Notes
+///
+/// Hooks invoked on the calling task itself run synchronously and have already finished
+/// by the time this is awaited, unless their error type uses
+/// `HookDispatchMode::AfterReturn`; this also waits for those.
+pub fn drain_hooks() -> impl Future<Output = ()> {
+    let epoch = EPOCH.load(Ordering::SeqCst);
+    advance_epoch(epoch);
+    Drain {
+        id: NEXT_DRAIN_ID.fetch_add(1, Ordering::Relaxed),
+        epoch,
+        registered: false,
+    }
+}
+
+/// Future behind `drain_hooks`, keeping at most one waker registered however often it is
+/// polled.
+struct Drain {
+    id: u64,
+    /// Latest epoch whose invocations are waited for.
+    epoch: u64,
+    /// Whether a waker is stored in `DRAIN_WAKERS`.
+    registered: bool,
+}
+
+impl Drain {
+    /// Returns true once no invocation from `self.epoch` or earlier is running, starting
+    /// the next epoch first if that has not happened yet.
+    fn drained(&self) -> bool {
+        if EPOCH.load(Ordering::SeqCst) == self.epoch {
+            advance_epoch(self.epoch);
+            if EPOCH.load(Ordering::SeqCst) == self.epoch {
+                return false;
+            }
+        }
+        // Once two epochs have passed, the slot is counting newer invocations
+        in_flight_slot(self.epoch).load(Ordering::SeqCst) == 0
+            || EPOCH.load(Ordering::SeqCst) >= self.epoch + 2
+    }
+}
+
+impl Future for Drain {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.drained() {
+            return Poll::Ready(());
+        }
+        match DRAIN_WAKERS.lock().entry(self.id) {
+            Entry::Occupied(mut entry) => {
+                if !entry.get().will_wake(cx.waker()) {
+                    entry.insert(cx.waker().clone());
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(cx.waker().clone());
+                DRAIN_WAITERS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        self.registered = true;
+        // Re-check in case the last invocation finished before the waker was stored
+        if self.drained() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Drain {
+    fn drop(&mut self) {
+        if self.registered && DRAIN_WAKERS.lock().remove(&self.id).is_some() {
+            DRAIN_WAITERS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Snapshot of all hook-related global state.
+#[cfg(feature = "test-util")]
+pub(crate) struct HookState {
+    hooks: Vec<HookMap>,
+    type_names: Vec<TypeNameMap>,
+    settings: Arc<HookSettings>,
+    enabled: bool,
+    #[cfg(feature = "hook-metrics")]
+    timings: HashMap<TypeId, std::time::Duration>,
+}
+
+/// Takes all hook-related global state, leaving it cleared.
+#[cfg(feature = "test-util")]
+pub(crate) fn take_hook_state() -> HookState {
+    HookState {
+        hooks: GLOBAL_HOOKS
+            .iter()
+            .map(|shard| std::mem::take(&mut *shard.write()))
+            .collect(),
+        type_names: HOOK_TYPE_NAMES
+            .iter()
+            .map(|shard| std::mem::take(&mut *shard.write()))
+            .collect(),
+        settings: std::mem::take(&mut *SETTINGS.write()),
+        enabled: HOOKS_ENABLED.swap(true, Ordering::SeqCst),
+        #[cfg(feature = "hook-metrics")]
+        timings: std::mem::take(&mut *HOOK_TIMINGS.lock()),
+    }
+}
+
+/// Replaces all hook-related global state with a previously taken snapshot.
+#[cfg(feature = "test-util")]
+pub(crate) fn restore_hook_state(state: HookState) {
+    for (shard, hooks) in GLOBAL_HOOKS.iter().zip(state.hooks) {
+        *shard.write() = hooks;
+    }
+    for (shard, names) in HOOK_TYPE_NAMES.iter().zip(state.type_names) {
+        *shard.write() = names;
+    }
+    *SETTINGS.write() = state.settings;
+    HOOKS_ENABLED.store(state.enabled, Ordering::SeqCst);
+    #[cfg(feature = "hook-metrics")]
+    {
+        *HOOK_TIMINGS.lock() = state.timings;
     }
-    HOOK_INVOKE_COUNTER.store(0, Ordering::Release);
 }
@@ -1,14 +1,73 @@
+#![cfg_attr(async_err_unstable, feature(async_iterator))]
+
+#[cfg(feature = "color")]
+pub mod color;
 pub mod error;
+#[cfg(feature = "eyre")]
+mod eyre_ext;
 pub mod future_ext;
 #[cfg(feature = "hooks")]
 pub mod hooks;
 pub mod prelude;
+#[cfg(feature = "regex")]
+pub mod redact;
+#[cfg(feature = "sink")]
+pub mod sink_ext;
+pub mod stream_ext;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "tokio")]
+pub mod tokio_ext;
+#[cfg(feature = "tracing")]
+mod tracing_ext;
+#[cfg(feature = "valuable")]
+mod valuable_ext;
+
+pub use crate::error::{
+    error_counts, set_app_version, set_count_errors, set_max_context_depth, summarize, wrap_all,
+    AsyncError, BoxedError, MultiError, PanicError, Severity, SharedAsyncError,
+};
+pub use crate::future_ext::{
+    repeat_until_async, select_ok_async, AsyncErrorChainExt, AsyncInfallibleExt, AsyncPanicExt,
+    AsyncResultChainExt, AsyncResultExt,
+};
+pub use crate::stream_ext::{AsyncIterResultExt, AsyncTryStreamExt};
+#[cfg(all(feature = "tracing", feature = "hooks"))]
+pub use crate::tracing_ext::TracingHook;
+#[cfg(feature = "tracing")]
+pub use crate::tracing_ext::WithContextSpan;
+
+/// `AsyncError` over a type-erased, thread-safe error.
+///
+/// An alias for `AsyncError<BoxedError>`. `AsyncError<E>` requires `E: Error`, which
+/// `Box<dyn Error + Send + Sync>` does not implement, so `BoxedError` wraps the box. Build
+/// one with `BoxedError::new` or `From<Box<dyn Error + Send + Sync>>`, and get the box back
+/// with `get_ref` or `into_inner`.
+///
+/// # Example
+/// ```
+/// # use async_err::{AsyncError, BoxedError, DynAsyncError};
+/// # use std::{error::Error, io};
+/// let boxed: Box<dyn Error + Send + Sync> = Box::new(io::Error::other("disk full"));
+/// let err: DynAsyncError = AsyncError::new(BoxedError::from(boxed));
+/// assert!(err.inner_error().get_ref().is::<io::Error>());
+///
+/// let boxed: Box<dyn Error + Send + Sync> = BoxedError::new("timed out").into_inner();
+/// assert_eq!(boxed.to_string(), "timed out");
+/// ```
+pub type DynAsyncError = AsyncError<BoxedError>;
+
+/// `Result` alias using `DynAsyncError` as the error type.
+pub type DynResult<T> = Result<T, DynAsyncError>;
 
-pub use crate::error::AsyncError;
-pub use crate::future_ext::{AsyncResultChainExt, AsyncResultExt};
+// `DynAsyncError` must stay usable as a thread-safe error trait object.
+const _: fn() = || {
+    fn assert_error_send_sync<T: core::error::Error + Send + Sync + 'static>() {}
+    assert_error_send_sync::<DynAsyncError>();
+};
 
 #[allow(unused_imports)]
 pub use crate::prelude::*;
 
 #[cfg(feature = "hooks")]
-pub use crate::hooks::{register_hook, AsyncErrorHook};
+pub use crate::hooks::{register_dyn_hook, register_hook, AsyncErrorHook};
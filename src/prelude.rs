@@ -1,5 +1,8 @@
 pub use crate::error::AsyncError;
-pub use crate::future_ext::{AsyncResultChainExt, AsyncResultExt};
+pub use crate::future_ext::{
+    AsyncErrorChainExt, AsyncInfallibleExt, AsyncPanicExt, AsyncResultChainExt, AsyncResultExt,
+};
+pub use crate::stream_ext::{AsyncIterResultExt, AsyncTryStreamExt};
 
 #[cfg(feature = "hooks")]
-pub use crate::hooks::{register_hook, AsyncErrorHook};
+pub use crate::hooks::{register_dyn_hook, register_hook, AsyncErrorHook};
@@ -0,0 +1,76 @@
+#![cfg(feature = "regex")]
+use crate::error::AsyncError;
+use core::error::Error;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use regex::Regex;
+use std::sync::Arc;
+
+/// Replacement text for redacted matches.
+const REDACTED: &str = "***";
+
+static REDACTION_PATTERNS: Lazy<RwLock<Arc<Vec<Regex>>>> = Lazy::new(Default::default);
+
+/// Set the patterns redacted from error output globally.
+///
+/// Matches in contexts and inner error messages are replaced with `***` by the default
+/// hook and by `AsyncError::redacted_display()`. Passing an empty vector disables
+/// redaction. Plain `Display` output is never redacted.
+pub fn set_redaction(patterns: Vec<Regex>) {
+    *REDACTION_PATTERNS.write() = Arc::new(patterns);
+}
+
+/// Takes the configured patterns, leaving redaction disabled.
+#[cfg(feature = "test-util")]
+pub(crate) fn take_redaction() -> Vec<Regex> {
+    Arc::unwrap_or_clone(std::mem::take(&mut *REDACTION_PATTERNS.write()))
+}
+
+/// Reinstalls patterns previously returned by `take_redaction`.
+#[cfg(feature = "test-util")]
+pub(crate) fn restore_redaction(patterns: Vec<Regex>) {
+    *REDACTION_PATTERNS.write() = Arc::new(patterns);
+}
+
+/// Returns the patterns in effect, preferring those pinned by a running hook invocation.
+pub(crate) fn redaction_patterns() -> Arc<Vec<Regex>> {
+    #[cfg(feature = "hooks")]
+    if let Some(patterns) = crate::hooks::pinned_redaction() {
+        return patterns;
+    }
+    Arc::clone(&REDACTION_PATTERNS.read())
+}
+
+/// Replaces every match of the configured patterns in `text` with `***`.
+pub(crate) fn redact(text: &str) -> String {
+    let patterns = redaction_patterns();
+    let mut redacted = text.to_string();
+    for pattern in patterns.iter() {
+        redacted = pattern.replace_all(&redacted, REDACTED).into_owned();
+    }
+    redacted
+}
+
+impl<E: Error + 'static> AsyncError<E> {
+    /// Renders the error like `Display`, with the configured patterns redacted.
+    ///
+    /// Redaction applies to every operation, every context and the inner error message,
+    /// which follows `with_debug_rendering` as in `Display`.
+    pub fn redacted_display(&self) -> String {
+        let mut out = String::new();
+        for op in self.operations() {
+            out.push_str(&redact(op));
+            out.push_str(": ");
+        }
+        for ctx in self.rendered_contexts().rev() {
+            if !ctx.trim().is_empty() {
+                out.push_str(&redact(&ctx));
+                out.push_str(": ");
+            }
+        }
+        let mut inner = String::new();
+        let _ = self.write_inner(&mut inner);
+        out.push_str(&redact(&inner));
+        out
+    }
+}
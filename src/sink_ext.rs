@@ -0,0 +1,85 @@
+#![cfg(feature = "sink")]
+use crate::error::AsyncError;
+use core::error::Error;
+use futures_sink::Sink;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Extension trait providing `.sink_with_context()` for sinks.
+///
+/// Adapts a `Sink<Item, Error = E>` into one whose errors are `AsyncError<E>`, so send,
+/// flush and close failures carry context like errors from futures do.
+pub trait AsyncSinkExt<Item, E>: Sink<Item, Error = E> + Sized {
+    /// Wraps every error produced by this sink with context.
+    ///
+    /// The closure `ctx` is called for each error, producing a string context to be
+    /// attached to it.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from error reference
+    ///
+    /// # Returns
+    /// A sink whose errors are `AsyncError<E>`.
+    fn sink_with_context<C>(self, ctx: C) -> SinkWithContext<Self, C>
+    where
+        C: FnMut(&E) -> String,
+    {
+        SinkWithContext { sink: self, ctx }
+    }
+}
+
+impl<Item, E, S> AsyncSinkExt<Item, E> for S where S: Sink<Item, Error = E> + Sized {}
+
+/// Sink adaptor produced by `.sink_with_context()` to add error context.
+pub struct SinkWithContext<S, C> {
+    sink: S,
+    ctx: C,
+}
+
+impl<S, C> SinkWithContext<S, C> {
+    /// Projects the pinned adaptor into the pinned sink and the context closure.
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut S>, &mut C) {
+        // Safety: the sink is never moved out of the pinned adaptor
+        let this = unsafe { self.get_unchecked_mut() };
+        (unsafe { Pin::new_unchecked(&mut this.sink) }, &mut this.ctx)
+    }
+}
+
+/// Wraps `err` with the context produced by `ctx`.
+fn wrap<E, C>(ctx: &mut C, err: E) -> AsyncError<E>
+where
+    E: Error + 'static,
+    C: FnMut(&E) -> String,
+{
+    let context = ctx(&err);
+    AsyncError::new(err).with_context(context)
+}
+
+impl<S, C, Item, E> Sink<Item> for SinkWithContext<S, C>
+where
+    S: Sink<Item, Error = E>,
+    E: Error + 'static,
+    C: FnMut(&E) -> String,
+{
+    type Error = AsyncError<E>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let (sink, ctx) = self.project();
+        sink.poll_ready(cx).map_err(|err| wrap(ctx, err))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let (sink, ctx) = self.project();
+        sink.start_send(item).map_err(|err| wrap(ctx, err))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let (sink, ctx) = self.project();
+        sink.poll_flush(cx).map_err(|err| wrap(ctx, err))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let (sink, ctx) = self.project();
+        sink.poll_close(cx).map_err(|err| wrap(ctx, err))
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::AsyncError;
+#[cfg(async_err_unstable)]
+use core::async_iter::AsyncIterator as AsyncIterBase;
+use core::error::Error;
+use futures_core::Stream;
+#[cfg(not(async_err_unstable))]
+use futures_core::Stream as AsyncIterBase;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Extension trait providing `.try_collect_with_context()` for fallible streams.
+pub trait AsyncTryStreamExt<T, E>: Stream<Item = Result<T, E>> + Sized {
+    /// Collects all items into `Coll`, wrapping the first error with context.
+    ///
+    /// Collection stops at the first error: the items gathered so far are discarded and
+    /// the error is returned as an `AsyncError<E>` carrying the context produced by `ctx`.
+    /// Hooks fire as with `.with_context()`.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from error reference
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<Coll, AsyncError<E>>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::stream_ext::AsyncTryStreamExt;
+    /// # use std::io;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let items = vec![Ok(1), Ok(2), Err(io::Error::other("connection reset")), Ok(4)];
+    /// let result: Result<Vec<i32>, _> = futures::stream::iter(items)
+    ///     .try_collect_with_context(|_| "collecting rows".to_string())
+    ///     .await;
+    ///
+    /// let err = result.unwrap_err();
+    /// assert_eq!(err.to_string(), "collecting rows: connection reset");
+    /// # }
+    /// ```
+    fn try_collect_with_context<C, Coll>(self, ctx: C) -> TryCollectWithContext<Self, C, Coll>
+    where
+        C: FnOnce(&E) -> String,
+        Coll: Default + Extend<T>,
+    {
+        TryCollectWithContext {
+            stream: self,
+            context: Some(ctx),
+            items: Coll::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, E, S> AsyncTryStreamExt<T, E> for S where S: Stream<Item = Result<T, E>> + Sized {}
+
+/// Future produced by `.try_collect_with_context()`.
+pub struct TryCollectWithContext<S, C, Coll> {
+    stream: S,
+    context: Option<C>,
+    items: Coll,
+    _marker: PhantomData<fn() -> Coll>,
+}
+
+impl<S, C, Coll, T, E> Future for TryCollectWithContext<S, C, Coll>
+where
+    S: Stream<Item = Result<T, E>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> String,
+    Coll: Default + Extend<T>,
+{
+    type Output = Result<Coll, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the stream is never moved out of the pinned future
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+        loop {
+            match stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(item))) => this.items.extend(Some(item)),
+                Poll::Ready(Some(Err(err))) => {
+                    this.items = Coll::default();
+                    let ctx = this.context.take().expect("Polled after completion");
+                    let context = ctx(&err);
+                    return Poll::Ready(Err(AsyncError::new(err).with_context(context)));
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(std::mem::take(&mut this.items))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Extension trait providing `.with_context_each()` for fallible async iterators.
+///
+/// Built on `futures_core::Stream` by default. Compiling with `--cfg async_err_unstable`
+/// on a nightly toolchain builds it on `core::async_iter::AsyncIterator` instead, with
+/// the same semantics.
+pub trait AsyncIterResultExt<T, E>: AsyncIterBase<Item = Result<T, E>> + Sized {
+    /// Wraps every error item with context, passing `Ok` items through unchanged.
+    ///
+    /// `ctx` is called once per error item; iteration continues after errors. Hooks
+    /// fire for each wrapped error as with `.with_context()`.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from each error reference
+    ///
+    /// # Returns
+    /// An async iterator yielding `Result<T, AsyncError<E>>`.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::stream_ext::AsyncIterResultExt;
+    /// # use futures::StreamExt;
+    /// # use std::io;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let rows = vec![Ok(1), Err(io::Error::other("bad row")), Ok(3), Err(io::Error::other("eof"))];
+    /// let mut line = 0;
+    /// let results: Vec<_> = futures::stream::iter(rows)
+    ///     .with_context_each(|_| {
+    ///         line += 1;
+    ///         format!("parse error #{}", line)
+    ///     })
+    ///     .map(|item| item.map_err(|err| err.to_string()))
+    ///     .collect()
+    ///     .await;
+    ///
+    /// assert_eq!(
+    ///     results,
+    ///     [
+    ///         Ok(1),
+    ///         Err("parse error #1: bad row".to_string()),
+    ///         Ok(3),
+    ///         Err("parse error #2: eof".to_string()),
+    ///     ]
+    /// );
+    /// # }
+    /// ```
+    fn with_context_each<C>(self, ctx: C) -> WithContextEach<Self, C>
+    where
+        C: FnMut(&E) -> String,
+    {
+        WithContextEach { inner: self, ctx }
+    }
+}
+
+impl<T, E, I> AsyncIterResultExt<T, E> for I where I: AsyncIterBase<Item = Result<T, E>> + Sized {}
+
+/// Async iterator produced by `.with_context_each()`.
+pub struct WithContextEach<I, C> {
+    inner: I,
+    ctx: C,
+}
+
+impl<I, C, T, E> AsyncIterBase for WithContextEach<I, C>
+where
+    I: AsyncIterBase<Item = Result<T, E>>,
+    E: Error + 'static,
+    C: FnMut(&E) -> String,
+{
+    type Item = Result<T, AsyncError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Safety: the inner iterator is never moved out of the pinned adapter
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        inner.poll_next(cx).map(|item| {
+            item.map(|result| {
+                result.map_err(|err| {
+                    let context = (this.ctx)(&err);
+                    AsyncError::new(err).with_context(context)
+                })
+            })
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
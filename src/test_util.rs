@@ -0,0 +1,89 @@
+#![cfg(feature = "test-util")]
+use crate::error::{restore_error_state, take_error_state, ErrorState};
+use crate::hooks::{note_hook_type_name, restore_hook_state, take_hook_state, HookState};
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, MutexGuard};
+use std::any::TypeId;
+
+static GUARD_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Isolates global hook state for the duration of a test.
+///
+/// Creating the guard resets every global setting in the crate to its default: registered
+/// hooks along with their dispatch modes, recorded hook type names, the warning sink, the
+/// `set_hook_*` output settings, the enabled flag and hook timings, as well as the maximum
+/// context depth, the application version, context deduplication, error counting and its
+/// counters, and the redaction patterns. Dropping it restores the state that was present
+/// before. Guards are serialized through a global lock, so tests holding one never observe each
+/// other's hooks even when the test harness runs them in parallel.
+///
+/// # Example
+/// ```
+/// # use async_err::test_util::HookTestGuard;
+/// let _guard = HookTestGuard::new();
+/// // Register hooks and trigger errors; they are discarded when `_guard` drops.
+/// ```
+pub struct HookTestGuard {
+    saved: Option<SavedState>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+/// Global state set aside while a guard is held.
+struct SavedState {
+    hooks: HookState,
+    errors: ErrorState,
+    #[cfg(feature = "regex")]
+    redaction: Vec<regex::Regex>,
+}
+
+impl HookTestGuard {
+    /// Acquires the guard, waiting for any other guard to be dropped first.
+    pub fn new() -> Self {
+        let lock = GUARD_LOCK.lock();
+        Self {
+            saved: Some(SavedState {
+                hooks: take_hook_state(),
+                errors: take_error_state(),
+                #[cfg(feature = "regex")]
+                redaction: crate::redact::take_redaction(),
+            }),
+            _lock: lock,
+        }
+    }
+}
+
+impl Default for HookTestGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for HookTestGuard {
+    fn drop(&mut self) {
+        if let Some(state) = self.saved.take() {
+            restore_hook_state(state.hooks);
+            restore_error_state(state.errors);
+            #[cfg(feature = "regex")]
+            crate::redact::restore_redaction(state.redaction);
+        }
+    }
+}
+
+/// Records `type_name` for `type_id` as hook registration does, returning `true` if this
+/// reported a type name collision.
+///
+/// Two distinct types rarely share a name within one build, so this lets tests pair an
+/// arbitrary `TypeId` with an existing name to exercise the collision warning.
+///
+/// # Example
+/// ```
+/// # use async_err::test_util::{record_hook_type_name, HookTestGuard};
+/// # use std::any::TypeId;
+/// let _guard = HookTestGuard::new();
+/// assert!(!record_hook_type_name(TypeId::of::<u8>(), "Shared"));
+/// assert!(record_hook_type_name(TypeId::of::<u16>(), "Shared"));
+/// assert!(!record_hook_type_name(TypeId::of::<u32>(), "Shared"));
+/// ```
+pub fn record_hook_type_name(type_id: TypeId, type_name: &'static str) -> bool {
+    note_hook_type_name(type_id, type_name)
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "tokio")]
+use crate::error::{AsyncError, BoxedError};
+use core::error::Error;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::JoinHandle;
+
+/// Extension trait providing `.join_with_context()` for tokio `JoinHandle`s of fallible tasks.
+///
+/// Awaiting a `JoinHandle<Result<T, E>>` yields a nested `Result<Result<T, E>, JoinError>`.
+/// This flattens both layers into a single `AsyncError<BoxedError>`, distinguishing
+/// panicked and cancelled tasks from tasks that returned an error.
+///
+/// # Example
+/// ```
+/// # use async_err::tokio_ext::AsyncJoinExt;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let handle = tokio::spawn(async { Err::<(), _>(std::io::Error::other("boom")) });
+/// let result = handle
+///     .join_with_context(|_| "Worker failed".into())
+///     .await;
+/// assert_eq!(result.unwrap_err().to_string(), "Worker failed: boom");
+/// # }
+/// ```
+pub trait AsyncJoinExt<T, E> {
+    /// Awaits the task, wrapping task errors and join failures with context.
+    ///
+    /// The closure `ctx` is called only on failure. Join failures additionally get an
+    /// inner `"task panicked"` or `"task was cancelled"` context.
+    ///
+    /// # Parameters
+    /// - `ctx`: closure to create context string from the boxed error
+    ///
+    /// # Returns
+    /// A future that resolves to `Result<T, AsyncError<BoxedError>>`.
+    fn join_with_context<C>(self, ctx: C) -> JoinWithContext<T, E, C>
+    where
+        C: FnOnce(&BoxedError) -> String;
+}
+
+impl<T, E> AsyncJoinExt<T, E> for JoinHandle<Result<T, E>> {
+    fn join_with_context<C>(self, ctx: C) -> JoinWithContext<T, E, C>
+    where
+        C: FnOnce(&BoxedError) -> String,
+    {
+        JoinWithContext {
+            handle: self,
+            context: Some(ctx),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Runs fallible, CPU-bound work on tokio's blocking pool, wrapping failures with context.
+///
+/// The closure runs via `tokio::task::spawn_blocking`. Its error, or a panic inside it,
+/// is wrapped exactly as `.join_with_context()` does.
+///
+/// # Parameters
+/// - `f`: the blocking closure to run.
+/// - `ctx`: closure to create context string from the boxed error.
+///
+/// # Returns
+/// A future that resolves to `Result<T, AsyncError<BoxedError>>`.
+pub fn blocking_with_context<T, E, F, C>(f: F, ctx: C) -> JoinWithContext<T, E, C>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+    C: FnOnce(&BoxedError) -> String,
+{
+    tokio::task::spawn_blocking(f).join_with_context(ctx)
+}
+
+/// Future produced by `.join_with_context()`.
+pub struct JoinWithContext<T, E, C> {
+    handle: JoinHandle<Result<T, E>>,
+    context: Option<C>,
+    _marker: PhantomData<E>,
+}
+
+impl<T, E, C> Future for JoinWithContext<T, E, C>
+where
+    E: Error + Send + Sync + 'static,
+    C: FnOnce(&BoxedError) -> String,
+{
+    type Output = Result<T, AsyncError<BoxedError>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the join handle is `Unpin` and the closure is never pinned
+        let this = unsafe { self.get_unchecked_mut() };
+
+        let (error, kind) = match Pin::new(&mut this.handle).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Ok(Ok(val))) => return Poll::Ready(Ok(val)),
+            Poll::Ready(Ok(Err(err))) => (BoxedError::new(err), None),
+            Poll::Ready(Err(join_err)) => {
+                let kind = if join_err.is_panic() {
+                    "task panicked"
+                } else {
+                    "task was cancelled"
+                };
+                (BoxedError::new(join_err), Some(kind))
+            }
+        };
+
+        let ctx = this.context.take().map(|f| f(&error));
+        let mut wrapped = AsyncError::new(error);
+        if let Some(kind) = kind {
+            wrapped = wrapped.append_context(kind.to_string());
+        }
+        Poll::Ready(Err(wrapped.with_context(ctx.unwrap_or_default())))
+    }
+}
+
+impl<E: Error + 'static> AsyncError<E> {
+    /// Records the identifier of the current tokio task in the `task_id` metadata entry.
+    ///
+    /// Outside of a tokio task the error is returned unchanged. Tokio only exposes task
+    /// names on unstable builds, so the task id is the identifier captured.
+    pub fn with_task_name(self) -> Self {
+        match tokio::task::try_id() {
+            Some(id) => self.with_metadata("task_id", id.to_string()),
+            None => self,
+        }
+    }
+}
+
+#[cfg(feature = "operations")]
+tokio::task_local! {
+    /// Operations entered on the current task, outermost first.
+    static OPERATIONS: Vec<String>;
+}
+
+/// Runs `fut` as a named operation, pushing `name` onto the task-local operation stack.
+///
+/// Every `AsyncError` created while `fut` runs captures the stack, so its rendered
+/// message is prefixed with the enclosing operations, outermost first. Operations nest.
+///
+/// # Example
+/// ```
+/// # use async_err::{tokio_ext::with_operation, AsyncError};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let err = with_operation("loading config", async {
+///     with_operation("reading file", async {
+///         AsyncError::new(std::io::Error::other("boom"))
+///     })
+///     .await
+/// })
+/// .await;
+/// assert_eq!(err.to_string(), "loading config: reading file: boom");
+/// # }
+/// ```
+#[cfg(feature = "operations")]
+pub async fn with_operation<F>(name: impl Into<String>, fut: F) -> F::Output
+where
+    F: Future,
+{
+    let mut stack = current_operations();
+    stack.push(name.into());
+    OPERATIONS.scope(stack, fut).await
+}
+
+/// Returns the task-local operation stack, or an empty one outside any operation.
+#[cfg(feature = "operations")]
+pub(crate) fn current_operations() -> Vec<String> {
+    OPERATIONS.try_with(Vec::clone).unwrap_or_default()
+}
@@ -0,0 +1,139 @@
+#![cfg(feature = "tracing")]
+use crate::error::{AsyncError, Severity};
+use crate::future_ext::WithContext;
+#[cfg(feature = "hooks")]
+use crate::hooks::AsyncErrorHook;
+use core::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tracing::instrument::{Instrument, Instrumented};
+use tracing::{Level, Span};
+
+impl<E: Error + 'static> AsyncError<E> {
+    /// Records the error onto an existing span instead of emitting a new event.
+    ///
+    /// Sets the `error.message`, `error.context`, `error.code` and `error.severity` fields;
+    /// context and code only when the error has them. As with any
+    /// `Span::record` call, the span must declare these fields up front (for example as
+    /// `tracing::field::Empty`), otherwise the values are silently dropped.
+    ///
+    /// # Example
+    /// ```
+    /// # use async_err::AsyncError;
+    /// let span = tracing::info_span!(
+    ///     "load",
+    ///     error.message = tracing::field::Empty,
+    ///     error.context = tracing::field::Empty,
+    ///     error.code = tracing::field::Empty,
+    ///     error.severity = tracing::field::Empty,
+    /// );
+    /// let err = AsyncError::new(std::io::Error::other("boom")).append_context("loading".into());
+    /// err.record_into(&span);
+    /// ```
+    pub fn record_into(&self, span: &Span) {
+        span.record("error.message", tracing::field::display(self.inner_error()));
+        if let Some(context) = self.rendered_context() {
+            span.record("error.context", context.as_ref());
+        }
+        if let Some(code) = self.code() {
+            span.record("error.code", code);
+        }
+        span.record("error.severity", self.severity().label());
+    }
+}
+
+impl Severity {
+    /// Returns the `tracing` level errors of this severity are emitted at.
+    ///
+    /// Warnings map to `WARN`; errors and critical errors both map to `ERROR`, the
+    /// highest level `tracing` has.
+    pub fn level(&self) -> Level {
+        match self {
+            Severity::Warning => Level::WARN,
+            Severity::Error | Severity::Critical => Level::ERROR,
+        }
+    }
+}
+
+/// Hook emitting each error as a `tracing` event at the level given by `Severity::level`.
+///
+/// The event carries the same `error.message`, `error.context`, `error.code` and
+/// `error.severity` fields as `AsyncError::record_into`.
+///
+/// # Example
+/// ```
+/// # use async_err::{register_hook, TracingHook};
+/// # use std::{io, sync::Arc};
+/// register_hook::<io::Error>(Arc::new(TracingHook));
+/// ```
+#[cfg(feature = "hooks")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TracingHook;
+
+#[cfg(feature = "hooks")]
+impl<E: Error + 'static> AsyncErrorHook<E> for TracingHook {
+    fn on_error(&self, error: &AsyncError<E>) {
+        let message = tracing::field::display(error.inner_error());
+        let context = error.rendered_context();
+        let context = context.as_deref().unwrap_or_default();
+        let code = error.code();
+        let severity = error.severity().label();
+        match error.severity() {
+            Severity::Warning => tracing::warn!(
+                error.message = message,
+                error.context = context,
+                error.code = code,
+                error.severity = severity,
+                "async error"
+            ),
+            Severity::Error | Severity::Critical => tracing::error!(
+                error.message = message,
+                error.context = context,
+                error.code = code,
+                error.severity = severity,
+                "async error"
+            ),
+        }
+    }
+}
+
+/// Future produced by `.with_context_span()`, running inside its own span.
+pub struct WithContextSpan<Fut, E, C> {
+    inner: Instrumented<WithContext<Fut, E, C>>,
+}
+
+impl<Fut, E, C> WithContextSpan<Fut, E, C> {
+    pub(crate) fn new(future: WithContext<Fut, E, C>, operation: &'static str) -> Self {
+        let span = tracing::info_span!(
+            "async_err.operation",
+            operation,
+            error.message = tracing::field::Empty,
+            error.context = tracing::field::Empty,
+            error.code = tracing::field::Empty,
+            error.severity = tracing::field::Empty,
+        );
+        Self {
+            inner: future.instrument(span),
+        }
+    }
+}
+
+impl<Fut, T, E, C> Future for WithContextSpan<Fut, E, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+    C: FnOnce(&E) -> String,
+{
+    type Output = Result<T, AsyncError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the inner future is never moved out of the pinned wrapper
+        let this = unsafe { self.get_unchecked_mut() };
+        let result = ready!(unsafe { Pin::new_unchecked(&mut this.inner) }.poll(cx));
+        if let Err(err) = &result {
+            err.record_into(this.inner.span());
+        }
+        Poll::Ready(result)
+    }
+}
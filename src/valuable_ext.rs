@@ -0,0 +1,82 @@
+#![cfg(feature = "valuable")]
+use crate::error::AsyncError;
+use core::error::Error;
+use valuable::{Fields, NamedField, NamedValues, StructDef, Structable, Valuable, Value, Visit};
+
+static FIELDS: &[NamedField<'static>] = &[
+    NamedField::new("message"),
+    NamedField::new("operations"),
+    NamedField::new("context"),
+    NamedField::new("contexts"),
+    NamedField::new("code"),
+    NamedField::new("severity"),
+    NamedField::new("metadata"),
+    NamedField::new("version"),
+];
+
+/// Exposes the error as a structure with `message`, `operations`, `context`, `contexts`,
+/// `code`, `severity`, `metadata` and `version` fields, so `valuable`-aware subscribers
+/// can traverse it without parsing the `Display` output.
+///
+/// `operations` lists the captured operations, outermost first, `context` is the outermost
+/// rendered context (unit if there is none), `contexts` the rendered stack, outermost first,
+/// `code` the error code (unit if none was attached), `severity` the severity label and
+/// `version` the captured application version (unit if none was set).
+///
+/// # Example
+/// ```
+/// # use async_err::AsyncError;
+/// # use valuable::{NamedValues, Valuable, Value, Visit};
+/// struct ContextVisitor(Option<String>);
+///
+/// impl Visit for ContextVisitor {
+///     fn visit_named_fields(&mut self, fields: &NamedValues<'_>) {
+///         if let Some(Value::String(ctx)) = fields.get_by_name("context") {
+///             self.0 = Some(ctx.to_string());
+///         }
+///     }
+///
+///     fn visit_value(&mut self, _value: Value<'_>) {}
+/// }
+///
+/// let err = AsyncError::new(std::io::Error::other("disk full")).append_context("saving".to_string());
+/// let mut visitor = ContextVisitor(None);
+/// err.visit(&mut visitor);
+/// assert_eq!(visitor.0.as_deref(), Some("saving"));
+/// ```
+impl<E: Error + 'static> Valuable for AsyncError<E> {
+    fn as_value(&self) -> Value<'_> {
+        Value::Structable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        let message = self.inner_error().to_string();
+        let operations = self.operations();
+        let contexts: Vec<String> = self
+            .rendered_contexts()
+            .rev()
+            .map(|ctx| ctx.into_owned())
+            .collect();
+        let context = match contexts.first() {
+            Some(ctx) => Value::String(ctx),
+            None => Value::Unit,
+        };
+        let values = [
+            Value::String(&message),
+            operations.as_value(),
+            context,
+            contexts.as_value(),
+            self.code().map_or(Value::Unit, Value::String),
+            Value::String(self.severity().label()),
+            self.metadata().as_value(),
+            self.version().map_or(Value::Unit, Value::String),
+        ];
+        visit.visit_named_fields(&NamedValues::new(FIELDS, &values));
+    }
+}
+
+impl<E: Error + 'static> Structable for AsyncError<E> {
+    fn definition(&self) -> StructDef<'_> {
+        StructDef::new_static("AsyncError", Fields::Named(FIELDS))
+    }
+}
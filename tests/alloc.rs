@@ -0,0 +1,117 @@
+//! Allocation counting tests. They live in their own binary because they install a
+//! counting global allocator.
+#[macro_use]
+mod common;
+
+use async_err::AsyncError;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::cell::Cell;
+
+/// System allocator counting allocations made on the current thread.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Returns the number of allocations `f` makes on this thread.
+fn allocations<R>(f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    let after = ALLOCATIONS.with(Cell::get);
+    drop(result);
+    after - before
+}
+
+test_error!(StaticContextError);
+
+#[test]
+fn static_context_does_not_allocate_with_spare_capacity() {
+    // Warm up lazily initialized statics and thread-locals
+    let _ = AsyncError::new(StaticContextError("warm-up")).context_str_static("warm-up");
+    let mut err = AsyncError::new(StaticContextError("io")).context_str_static("reading");
+    err.clear_context();
+
+    let mut err = Some(err);
+    let allocated = allocations(|| err.take().unwrap().context_str_static("reading"));
+    assert_eq!(allocated, 0);
+
+    let mut err = AsyncError::new(StaticContextError("io")).context_str_static("reading");
+    err.clear_context();
+    let err = err.context_str_static("reading");
+    assert!(matches!(err.contexts(), [Cow::Borrowed("reading")]));
+}
+
+#[test]
+fn owned_context_allocates_its_string() {
+    let mut err = AsyncError::new(StaticContextError("io")).context_str_static("reading");
+    err.clear_context();
+
+    let mut err = Some(err);
+    let allocated = allocations(|| err.take().unwrap().with_context("reading".to_string()));
+    assert!(allocated >= 1);
+}
+
+#[cfg(feature = "hooks")]
+mod hooks {
+    use super::allocations;
+    use async_err::{hooks::invoke_hooks, register_hook, AsyncError, AsyncErrorHook};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    test_error!(HotPathError);
+
+    /// Counts invocations without allocating.
+    #[derive(Default)]
+    struct QuietHook(AtomicUsize);
+
+    impl AsyncErrorHook<HotPathError> for QuietHook {
+        fn on_error(&self, _error: &AsyncError<HotPathError>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn invoking_hooks_does_not_allocate_with_stable_registrations() {
+        let hooks: Vec<Arc<QuietHook>> = (0..3).map(|_| Arc::default()).collect();
+        for hook in &hooks {
+            register_hook::<HotPathError>(hook.clone());
+        }
+        let err = AsyncError::new(HotPathError("hot"));
+        // Warm up lazily initialized statics and thread-locals
+        invoke_hooks(&err);
+
+        let allocated = allocations(|| {
+            for _ in 0..100 {
+                invoke_hooks(&err);
+            }
+        });
+
+        assert_eq!(allocated, 0);
+        assert!(hooks
+            .iter()
+            .all(|hook| hook.0.load(Ordering::SeqCst) == 101));
+    }
+}
@@ -0,0 +1,55 @@
+//! Sets the global application version, so it runs as its own test binary. The version
+//! cannot be unset, so everything is checked in one test, in order.
+#[macro_use]
+mod common;
+
+use async_err::{set_app_version, AsyncError};
+
+test_error!(CrashError);
+
+#[test]
+fn version_is_captured_by_errors_created_after_it_is_set() {
+    let before = AsyncError::new(CrashError("disk full"));
+    assert_eq!(before.version(), None);
+    assert_eq!(before.report(), "disk full");
+
+    set_app_version("1.4.2+g3f9c2a1");
+    let err = AsyncError::new(CrashError("disk full")).append_context("saving".to_string());
+    assert_eq!(err.version(), Some("1.4.2+g3f9c2a1"));
+    let kept = AsyncError::new(CrashError("x"));
+    assert_eq!(err.report(), "saving: disk full (version 1.4.2+g3f9c2a1)");
+    assert_eq!(
+        err.with_pretty_report(true).report(),
+        "saving: disk full\n\nVersion: 1.4.2+g3f9c2a1"
+    );
+    assert_eq!(before.version(), None, "existing errors are not updated");
+
+    set_app_version("1.4.3");
+    assert_eq!(AsyncError::new(CrashError("x")).version(), Some("1.4.3"));
+    assert_eq!(
+        kept.version(),
+        Some("1.4.2+g3f9c2a1"),
+        "replacing keeps old versions"
+    );
+
+    #[cfg(feature = "valuable")]
+    {
+        use valuable::{NamedValues, Valuable, Value, Visit};
+
+        struct VersionVisitor(Option<String>);
+
+        impl Visit for VersionVisitor {
+            fn visit_named_fields(&mut self, fields: &NamedValues<'_>) {
+                if let Some(Value::String(version)) = fields.get_by_name("version") {
+                    self.0 = Some(version.to_string());
+                }
+            }
+
+            fn visit_value(&mut self, _value: Value<'_>) {}
+        }
+
+        let mut visitor = VersionVisitor(None);
+        AsyncError::new(CrashError("x")).visit(&mut visitor);
+        assert_eq!(visitor.0.as_deref(), Some("1.4.3"));
+    }
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "color")]
+#[macro_use]
+mod common;
+
+use async_err::{color::ColorMode, AsyncError, Severity};
+use std::io::IsTerminal;
+
+test_error!(ColorError);
+
+fn sample(severity: Severity) -> AsyncError<ColorError> {
+    AsyncError::new(ColorError("disk full"))
+        .append_context("saving".to_string())
+        .with_severity(severity)
+}
+
+#[test]
+fn forced_color_highlights_severity_and_context() {
+    let out = sample(Severity::Critical).colored_display(ColorMode::Always);
+    assert!(out.contains("\x1b[1;31mCRITICAL\x1b[0m"));
+    assert!(out.contains("\x1b[1msaving\x1b[0m"));
+
+    let out = sample(Severity::Warning).colored_display(ColorMode::Always);
+    assert!(out.contains("\x1b[33mWARN\x1b[0m"));
+}
+
+#[test]
+fn disabled_color_has_no_escape_codes() {
+    let out = sample(Severity::Critical).colored_display(ColorMode::Never);
+    assert!(!out.contains('\x1b'));
+    assert_eq!(out, "[CRITICAL] saving: disk full");
+}
+
+#[test]
+fn auto_color_follows_whether_stderr_is_a_terminal() {
+    let out = sample(Severity::Warning).colored_display(ColorMode::Auto);
+    assert_eq!(out.contains('\x1b'), std::io::stderr().is_terminal());
+}
@@ -0,0 +1,191 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+#[cfg(feature = "hooks")]
+use async_err::{register_hook, AsyncError, AsyncErrorHook};
+#[cfg(feature = "hooks")]
+use parking_lot::Mutex;
+#[cfg(feature = "hooks")]
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Declares an error type displaying its message.
+///
+/// Hooks are keyed by error type, so tests that register hooks declare their own type
+/// and stay independent of tests running in parallel.
+#[allow(unused_macros)]
+macro_rules! test_error {
+    ($name:ident) => {
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+        struct $name(&'static str);
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        impl std::error::Error for $name {}
+    };
+}
+
+/// Hook counting its invocations and remembering the last error's `Display` output.
+#[cfg(feature = "hooks")]
+#[derive(Default)]
+pub struct CountingHook {
+    calls: AtomicUsize,
+    last: Mutex<Option<String>>,
+}
+
+#[cfg(feature = "hooks")]
+impl CountingHook {
+    /// Registers a new counting hook for `E`.
+    pub fn register<E: Error + 'static>() -> Arc<Self> {
+        let hook = Arc::new(Self::default());
+        register_hook::<E>(hook.clone());
+        hook
+    }
+
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    pub fn last(&self) -> Option<String> {
+        self.last.lock().clone()
+    }
+}
+
+#[cfg(feature = "hooks")]
+impl<E: Error + 'static> AsyncErrorHook<E> for CountingHook {
+    fn on_error(&self, error: &AsyncError<E>) {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        *self.last.lock() = Some(error.to_string());
+    }
+}
+
+/// Minimal `tracing` subscriber recording span and event fields for assertions.
+#[cfg(feature = "tracing")]
+pub mod recording {
+    use parking_lot::Mutex;
+    use std::{
+        collections::HashMap,
+        fmt,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Level, Metadata, Subscriber,
+    };
+
+    /// A recorded span or event.
+    #[derive(Debug, Clone)]
+    pub struct Recorded {
+        pub name: &'static str,
+        pub level: Level,
+        pub fields: HashMap<String, String>,
+        /// How often the span was entered; always zero for events.
+        pub entered: usize,
+    }
+
+    impl Recorded {
+        pub fn field(&self, name: &str) -> Option<&str> {
+            self.fields.get(name).map(String::as_str)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    pub struct RecordingSubscriber {
+        inner: Arc<Inner>,
+    }
+
+    #[derive(Default)]
+    struct Inner {
+        next_id: AtomicU64,
+        spans: Mutex<HashMap<u64, Recorded>>,
+        events: Mutex<Vec<Recorded>>,
+    }
+
+    impl RecordingSubscriber {
+        /// Returns the first span with the given name.
+        pub fn span(&self, name: &str) -> Option<Recorded> {
+            self.inner
+                .spans
+                .lock()
+                .values()
+                .find(|span| span.name == name)
+                .cloned()
+        }
+
+        pub fn events(&self) -> Vec<Recorded> {
+            self.inner.events.lock().clone()
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+            let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            let span = Recorded {
+                name: attrs.metadata().name(),
+                level: *attrs.metadata().level(),
+                fields,
+                entered: 0,
+            };
+            self.inner.spans.lock().insert(id, span);
+            span::Id::from_u64(id)
+        }
+
+        fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+            if let Some(span) = self.inner.spans.lock().get_mut(&id.into_u64()) {
+                values.record(&mut FieldVisitor(&mut span.fields));
+            }
+        }
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = HashMap::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.inner.events.lock().push(Recorded {
+                name: event.metadata().name(),
+                level: *event.metadata().level(),
+                fields,
+                entered: 0,
+            });
+        }
+
+        fn enter(&self, span: &span::Id) {
+            if let Some(span) = self.inner.spans.lock().get_mut(&span.into_u64()) {
+                span.entered += 1;
+            }
+        }
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+}
@@ -0,0 +1,50 @@
+//! Changes the global context depth, so it runs as its own test binary.
+use async_err::{set_max_context_depth, AsyncError};
+use parking_lot::Mutex;
+use std::io;
+
+static DEPTH_LOCK: Mutex<()> = Mutex::new(());
+
+fn pushed(contexts: &[&str]) -> AsyncError<io::Error> {
+    contexts
+        .iter()
+        .fold(AsyncError::new(io::Error::other("boom")), |err, ctx| {
+            err.append_context(ctx.to_string())
+        })
+}
+
+#[test]
+fn oldest_contexts_collapse_into_marker() {
+    let _lock = DEPTH_LOCK.lock();
+    set_max_context_depth(3);
+    let err = pushed(&["a", "b", "c", "d", "e"]);
+    set_max_context_depth(usize::MAX);
+
+    assert_eq!(err.contexts(), ["…(3 earlier contexts)", "d", "e"]);
+    assert_eq!(err.to_string(), "e: d: …(3 earlier contexts): boom");
+}
+
+#[test]
+fn newest_context_is_kept_at_the_smallest_limit() {
+    let _lock = DEPTH_LOCK.lock();
+    set_max_context_depth(1);
+    let err = pushed(&["a", "b", "c"]);
+    set_max_context_depth(0);
+    let zero = pushed(&["a", "b", "c", "d"]);
+    set_max_context_depth(usize::MAX);
+
+    // Limits below 2 are raised to 2: the marker plus the newest context
+    assert_eq!(err.contexts(), ["…(2 earlier contexts)", "c"]);
+    assert_eq!(zero.contexts(), ["…(3 earlier contexts)", "d"]);
+    assert_eq!(zero.context(), Some("d"));
+}
+
+#[test]
+fn stack_within_limit_is_untouched() {
+    let _lock = DEPTH_LOCK.lock();
+    set_max_context_depth(3);
+    let err = pushed(&["a", "b", "c"]);
+    set_max_context_depth(usize::MAX);
+
+    assert_eq!(err.contexts(), ["a", "b", "c"]);
+}
@@ -0,0 +1,49 @@
+//! Toggles global context deduplication, so it runs as its own test binary.
+#[macro_use]
+mod common;
+
+use async_err::{error::set_dedup_adjacent_contexts, AsyncError};
+use parking_lot::Mutex;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+test_error!(ForwardError);
+
+fn forwarded(contexts: &[&str]) -> AsyncError<ForwardError> {
+    contexts
+        .iter()
+        .fold(AsyncError::new(ForwardError("timeout")), |err, ctx| {
+            err.append_context(ctx.to_string())
+        })
+}
+
+#[test]
+fn adjacent_duplicates_are_skipped_when_enabled() {
+    let _lock = LOCK.lock();
+    set_dedup_adjacent_contexts(true);
+    let err = forwarded(&["request failed", "request failed"]);
+    set_dedup_adjacent_contexts(false);
+
+    assert_eq!(err.contexts(), ["request failed"]);
+    assert_eq!(err.to_string(), "request failed: timeout");
+}
+
+#[test]
+fn only_the_most_recent_context_is_compared() {
+    let _lock = LOCK.lock();
+    set_dedup_adjacent_contexts(true);
+    let err = forwarded(&["request failed", "retrying", "request failed"]);
+    set_dedup_adjacent_contexts(false);
+
+    assert_eq!(
+        err.contexts(),
+        ["request failed", "retrying", "request failed"]
+    );
+}
+
+#[test]
+fn duplicates_are_kept_by_default() {
+    let _lock = LOCK.lock();
+    let err = forwarded(&["request failed", "request failed"]);
+    assert_eq!(err.contexts(), ["request failed", "request failed"]);
+}
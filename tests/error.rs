@@ -0,0 +1,967 @@
+#[macro_use]
+mod common;
+
+use async_err::AsyncError;
+
+test_error!(PlainError);
+
+#[test]
+fn with_context_pushes_onto_the_stack() {
+    let err = AsyncError::new(PlainError("err"))
+        .with_context("a".to_string())
+        .with_context("b".to_string());
+
+    assert_eq!(err.to_string(), "b: a: err");
+    assert_eq!(err.context(), Some("b"));
+    assert_eq!(err.contexts(), ["a", "b"]);
+}
+
+test_error!(MapSource);
+test_error!(MapTarget);
+
+#[test]
+fn map_transforms_error_and_context_and_keeps_the_rest() {
+    use async_err::Severity;
+
+    let err = AsyncError::new(MapSource("timeout"))
+        .append_context("inner".to_string())
+        .append_context("fetching".to_string())
+        .with_severity(Severity::Critical)
+        .with_code("E504")
+        .with_metadata("url", "/users");
+
+    let mapped = err.map(
+        |src| {
+            MapTarget(if src.0 == "timeout" {
+                "upstream timeout"
+            } else {
+                "other"
+            })
+        },
+        |ctx| ctx.map(|ctx| format!("{} user", ctx)),
+    );
+
+    assert_eq!(mapped.inner_error(), &MapTarget("upstream timeout"));
+    assert_eq!(mapped.contexts(), ["inner", "fetching user"]);
+    assert_eq!(mapped.severity(), Severity::Critical);
+    assert_eq!(mapped.code(), Some("E504"));
+    assert_eq!(mapped.meta("url"), Some("/users"));
+}
+
+#[test]
+fn map_can_remove_the_outermost_context() {
+    let err = AsyncError::new(MapSource("boom")).append_context("outer".to_string());
+    let mapped = err.map(|src| src, |_| None);
+    assert!(mapped.contexts().is_empty());
+    assert_eq!(mapped.to_string(), "boom");
+}
+
+#[cfg(feature = "hooks")]
+#[test]
+fn map_fires_no_hooks() {
+    let hook = common::CountingHook::register::<MapTarget>();
+    let err = AsyncError::new(MapSource("boom")).append_context("outer".to_string());
+    let _ = err.map(|_| MapTarget("mapped"), |ctx| ctx);
+    assert_eq!(hook.calls(), 0);
+}
+
+test_error!(MutateError);
+
+fn replace_outer_context(err: &mut AsyncError<MutateError>) {
+    err.set_context("retrying request");
+}
+
+#[test]
+fn set_context_replaces_outermost_context_in_place() {
+    let mut err = AsyncError::new(MutateError("reset"))
+        .append_context("inner".to_string())
+        .append_context("sending request".to_string());
+
+    replace_outer_context(&mut err);
+
+    assert_eq!(err.contexts(), ["inner", "retrying request"]);
+    assert_eq!(err.to_string(), "retrying request: inner: reset");
+}
+
+#[test]
+fn set_context_pushes_when_there_is_none() {
+    let mut err = AsyncError::new(MutateError("reset"));
+    err.set_context("connecting");
+    assert_eq!(err.to_string(), "connecting: reset");
+}
+
+#[test]
+fn clear_context_removes_every_context() {
+    let mut err = AsyncError::new(MutateError("reset"))
+        .append_context("a".to_string())
+        .append_context("b".to_string());
+    let _ = err.to_string();
+
+    err.clear_context();
+
+    assert!(err.contexts().is_empty());
+    assert_eq!(err.to_string(), "reset");
+}
+
+#[cfg(feature = "hooks")]
+#[test]
+fn set_and_clear_context_fire_no_hooks() {
+    let hook = common::CountingHook::register::<MutateError>();
+    let mut err = AsyncError::new(MutateError("reset"));
+    err.set_context("a");
+    err.clear_context();
+    assert_eq!(hook.calls(), 0);
+}
+
+test_error!(BoxedParseError);
+
+#[test]
+fn dyn_async_error_stores_different_boxed_errors() {
+    use async_err::{BoxedError, DynAsyncError, DynResult};
+    use std::error::Error;
+
+    fn parse() -> DynResult<u32> {
+        Err(AsyncError::new(BoxedError::new(BoxedParseError(
+            "bad digit",
+        ))))
+    }
+
+    let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing config");
+    let errors: Vec<DynAsyncError> = vec![
+        AsyncError::new(BoxedError::new(io)).append_context("reading config".to_string()),
+        parse().unwrap_err(),
+    ];
+
+    assert_eq!(errors[0].to_string(), "reading config: missing config");
+    assert_eq!(errors[1].to_string(), "bad digit");
+    assert!(errors[1]
+        .inner_error()
+        .get_ref()
+        .downcast_ref::<BoxedParseError>()
+        .is_some());
+
+    let stored: Box<dyn Error + Send + Sync> = Box::new(errors.into_iter().next().unwrap());
+    assert_eq!(stored.to_string(), "reading config: missing config");
+}
+
+#[test]
+fn boxed_error_round_trips_the_box() {
+    use async_err::BoxedError;
+    use std::error::Error;
+
+    let boxed: Box<dyn Error + Send + Sync> = Box::new(BoxedParseError("bad digit"));
+    let wrapped = BoxedError::from(boxed);
+    assert!(wrapped.get_ref().is::<BoxedParseError>());
+
+    let boxed = wrapped.into_inner();
+    assert_eq!(boxed.downcast::<BoxedParseError>().unwrap().0, "bad digit");
+}
+
+test_error!(BatchError);
+
+#[test]
+fn wrap_all_wraps_only_errors_with_their_index() {
+    let calls = std::cell::Cell::new(0);
+    let results = vec![
+        Ok(1),
+        Err(BatchError("bad row")),
+        Ok(3),
+        Err(BatchError("missing column")),
+    ];
+
+    let wrapped = async_err::wrap_all(results, |i, err| {
+        calls.set(calls.get() + 1);
+        format!("row {} ({})", i, err.0)
+    });
+
+    assert_eq!(calls.get(), 2);
+    assert_eq!(wrapped[0].as_ref().ok(), Some(&1));
+    assert_eq!(wrapped[2].as_ref().ok(), Some(&3));
+    let errors: Vec<String> = wrapped
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "row 1 (bad row): bad row",
+            "row 3 (missing column): missing column"
+        ]
+    );
+}
+
+test_error!(PadError);
+
+#[test]
+fn display_honors_width_alignment_and_precision() {
+    let err = AsyncError::new(PadError("boom")).append_context("io".to_string());
+
+    assert_eq!(format!("{:>20}", err), "            io: boom");
+    assert_eq!(format!("{:<12}|", err), "io: boom    |");
+    assert_eq!(format!("{:^12}", err), "  io: boom  ");
+    assert_eq!(format!("{:.5}", err), "io: b");
+    assert_eq!(format!("{:>4}", err), "io: boom");
+}
+
+test_error!(RootCause);
+test_error!(Unrelated);
+
+/// Error with a single source, used to build chains.
+#[derive(Debug)]
+struct Layer<S> {
+    message: &'static str,
+    source: S,
+}
+
+impl<S> std::fmt::Display for Layer<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl<S: std::error::Error + 'static> std::error::Error for Layer<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[test]
+fn caused_by_finds_errors_anywhere_in_the_chain() {
+    let middle = Layer {
+        message: "query failed",
+        source: RootCause("socket closed"),
+    };
+    let top = Layer {
+        message: "request failed",
+        source: middle,
+    };
+    let err = AsyncError::new(top);
+
+    assert!(err.caused_by::<Layer<Layer<RootCause>>>());
+    assert!(err.caused_by::<Layer<RootCause>>());
+    assert!(err.caused_by::<RootCause>());
+    assert!(!err.caused_by::<Unrelated>());
+}
+
+test_error!(TemplateError);
+
+#[test]
+fn context_template_renders_metadata_at_display_time() {
+    let err = AsyncError::new(TemplateError("boom"))
+        .with_context_template("loading user {user_id} in {region}")
+        .with_metadata("user_id", "42");
+
+    assert_eq!(err.to_string(), "loading user 42 in {region}: boom");
+    assert_eq!(
+        err.rendered_context().as_deref(),
+        Some("loading user 42 in {region}")
+    );
+    assert_eq!(err.context(), Some("loading user {user_id} in {region}"));
+}
+
+#[test]
+fn conversions_keep_templates_and_lazy_contexts() {
+    let err = AsyncError::new(TemplateError("boom"))
+        .with_context_template("loading user {user_id}")
+        .with_metadata("user_id", "42");
+
+    let mapped = err.map(|e| e, |ctx| ctx);
+    assert_eq!(mapped.to_string(), "loading user 42: boom");
+    let mapped = mapped.with_metadata("user_id", "43");
+    assert_eq!(mapped.to_string(), "loading user 43: boom");
+    assert_eq!(mapped.into_dyn().to_string(), "loading user 43: boom");
+
+    let lazy = AsyncError::new(TemplateError("boom")).with_lazy_stored_context(|| "lazy".into());
+    let mapped = lazy.err_into::<TemplateError>();
+    assert_eq!(mapped.context(), Some("lazy"));
+    assert_eq!(mapped.to_string(), "lazy: boom");
+}
+
+#[test]
+fn map_receives_the_rendered_template() {
+    let err = AsyncError::new(TemplateError("boom"))
+        .with_context_template("loading user {user_id}")
+        .with_metadata("user_id", "42");
+
+    let mapped = err.map(|e| e, |ctx| ctx.map(|ctx| format!("{} (retried)", ctx)));
+    assert_eq!(mapped.to_string(), "loading user 42 (retried): boom");
+}
+
+#[test]
+fn equality_and_ordering_use_rendered_contexts() {
+    let template = AsyncError::new(TemplateError("boom"))
+        .with_context_template("user {user_id}")
+        .with_metadata("user_id", "42");
+    let plain = AsyncError::new(TemplateError("boom")).append_context("user 42".to_string());
+    assert_eq!(template, plain);
+    assert_eq!(template.cmp(&plain), std::cmp::Ordering::Equal);
+
+    let lazy_a = AsyncError::new(TemplateError("boom")).with_lazy_stored_context(|| "a".into());
+    let lazy_b = AsyncError::new(TemplateError("boom")).with_lazy_stored_context(|| "b".into());
+    assert_ne!(lazy_a, lazy_b);
+    assert!(lazy_a < lazy_b);
+}
+
+test_error!(SummaryError);
+
+#[test]
+fn summarize_counts_errors_per_key() {
+    let errors: Vec<_> = ["timeout", "auth", "timeout", "timeout", "auth", "disk"]
+        .into_iter()
+        .map(|kind| AsyncError::new(SummaryError("failed")).with_metadata("kind", kind))
+        .collect();
+
+    let counts = async_err::summarize(&errors, |err| err.meta("kind").unwrap().to_string());
+
+    assert_eq!(counts.len(), 3);
+    assert_eq!(counts["timeout"], 3);
+    assert_eq!(counts["auth"], 2);
+    assert_eq!(counts["disk"], 1);
+    assert!(async_err::summarize(&errors[..0], |err| err.severity()).is_empty());
+}
+
+test_error!(ResponseError);
+
+/// Dummy framework response.
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    code: Option<String>,
+    body: String,
+}
+
+impl async_err::error::AsyncErrorConvert<Response> for AsyncError<ResponseError> {
+    fn to_response(&self) -> Response {
+        let status = match self.severity() {
+            async_err::Severity::Critical => 503,
+            _ => 500,
+        };
+        Response {
+            status,
+            code: self.code().map(str::to_owned),
+            body: self.to_string(),
+        }
+    }
+}
+
+#[test]
+fn errors_convert_into_framework_responses() {
+    use async_err::error::AsyncErrorConvert;
+
+    let err = AsyncError::new(ResponseError("db down"))
+        .append_context("listing orders".to_string())
+        .with_severity(async_err::Severity::Critical)
+        .with_code("DB_UNAVAILABLE");
+
+    assert_eq!(
+        err.to_response(),
+        Response {
+            status: 503,
+            code: Some("DB_UNAVAILABLE".to_string()),
+            body: "listing orders: db down".to_string()
+        }
+    );
+
+    let status = err.convert(|err| {
+        (
+            err.context().map(str::to_owned),
+            err.code().map(str::to_owned),
+            err.severity(),
+        )
+    });
+    assert_eq!(
+        status,
+        (
+            Some("listing orders".to_string()),
+            Some("DB_UNAVAILABLE".to_string()),
+            async_err::Severity::Critical
+        )
+    );
+}
+
+/// Error type written against `core` only, as a `no_std` crate would.
+#[derive(Debug)]
+struct CoreOnlyError;
+
+impl core::fmt::Display for CoreOnlyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("core failure")
+    }
+}
+
+impl core::error::Error for CoreOnlyError {}
+
+#[test]
+fn wraps_errors_implementing_only_core_error() {
+    let err = AsyncError::new(CoreOnlyError).append_context("parsing frame".to_string());
+    assert_eq!(err.to_string(), "parsing frame: core failure");
+
+    let source = core::error::Error::source(&err).expect("inner error is the source");
+    assert!(source.is::<CoreOnlyError>());
+}
+
+test_error!(HelpError);
+
+#[test]
+fn help_shows_in_display_chain_but_not_display() {
+    let err = AsyncError::new(Layer {
+        message: "config invalid",
+        source: HelpError("missing key `port`"),
+    })
+    .append_context("starting server".to_string())
+    .with_help("add `port = 8080` to config.toml");
+
+    assert_eq!(err.help(), Some("add `port = 8080` to config.toml"));
+    assert_eq!(err.to_string(), "starting server: config invalid");
+    assert_eq!(
+        err.display_chain(),
+        "starting server: config invalid\n  caused by: missing key `port`\nhelp: add `port = 8080` to config.toml"
+    );
+}
+
+#[test]
+fn display_chain_omits_help_when_unset() {
+    let err = AsyncError::new(HelpError("boom"));
+    assert_eq!(err.help(), None);
+    assert_eq!(err.display_chain(), "boom");
+}
+
+/// Opaque error whose `Display` hides the useful detail.
+#[derive(Debug)]
+struct OpaqueError {
+    #[allow(dead_code)] // only shown by `Debug`
+    code: u32,
+}
+
+impl std::fmt::Display for OpaqueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("operation failed")
+    }
+}
+
+impl std::error::Error for OpaqueError {}
+
+#[test]
+fn debug_rendering_switches_the_inner_error_format() {
+    let err = AsyncError::new(OpaqueError { code: 17 }).append_context("syncing".to_string());
+    assert_eq!(err.to_string(), "syncing: operation failed");
+
+    let err = err.with_debug_rendering();
+    assert_eq!(err.to_string(), "syncing: OpaqueError { code: 17 }");
+}
+
+#[test]
+fn debug_rendering_invalidates_the_display_cache() {
+    let err = AsyncError::new(OpaqueError { code: 3 }).with_cached_display();
+    assert_eq!(err.to_string(), "operation failed");
+    assert_eq!(
+        err.with_debug_rendering().to_string(),
+        "OpaqueError { code: 3 }"
+    );
+}
+
+test_error!(LowLevelError);
+
+#[derive(Debug, PartialEq)]
+struct AppError(String);
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "app: {}", self.0)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<LowLevelError> for AppError {
+    fn from(err: LowLevelError) -> Self {
+        AppError(err.0.to_string())
+    }
+}
+
+#[test]
+fn err_into_converts_between_wrapped_errors_with_question_mark() {
+    fn read() -> Result<(), AsyncError<LowLevelError>> {
+        Err(AsyncError::new(LowLevelError("eof"))
+            .append_context("reading header".to_string())
+            .with_metadata("offset", "512")
+            .with_severity(async_err::Severity::Warning))
+    }
+
+    fn load() -> Result<(), AsyncError<AppError>> {
+        read().map_err(AsyncError::err_into)?;
+        Ok(())
+    }
+
+    let err = load().unwrap_err();
+    assert_eq!(err.inner_error(), &AppError("eof".to_string()));
+    assert_eq!(err.to_string(), "reading header: app: eof");
+    assert_eq!(err.meta("offset"), Some("512"));
+    assert_eq!(err.severity(), async_err::Severity::Warning);
+}
+
+test_error!(SortError);
+
+#[test]
+fn sorting_puts_the_most_severe_errors_first() {
+    use async_err::Severity;
+
+    let mut errors = [
+        AsyncError::new(SortError("b")).with_severity(Severity::Warning),
+        AsyncError::new(SortError("a")).with_severity(Severity::Critical),
+        AsyncError::new(SortError("c")).with_severity(Severity::Error),
+        AsyncError::new(SortError("a")).with_severity(Severity::Warning),
+        AsyncError::new(SortError("z"))
+            .with_severity(Severity::Critical)
+            .append_context("alpha".to_string()),
+        AsyncError::new(SortError("y"))
+            .with_severity(Severity::Critical)
+            .with_code("E2"),
+        AsyncError::new(SortError("x"))
+            .with_severity(Severity::Critical)
+            .with_code("E1")
+            .append_context("beta".to_string()),
+    ];
+    errors.sort();
+
+    let order: Vec<(Severity, Option<&str>, String)> = errors
+        .iter()
+        .map(|err| (err.severity(), err.code(), err.to_string()))
+        .collect();
+    assert_eq!(
+        order,
+        [
+            (Severity::Critical, None, "a".to_string()),
+            (Severity::Critical, None, "alpha: z".to_string()),
+            (Severity::Critical, Some("E1"), "beta: x".to_string()),
+            (Severity::Critical, Some("E2"), "y".to_string()),
+            (Severity::Error, None, "c".to_string()),
+            (Severity::Warning, None, "a".to_string()),
+            (Severity::Warning, None, "b".to_string()),
+        ]
+    );
+    assert_ne!(
+        AsyncError::new(SortError("y")).with_code("E1"),
+        AsyncError::new(SortError("y")).with_code("E2")
+    );
+}
+
+#[test]
+fn cmp_by_severity_works_without_ord_on_the_inner_error() {
+    use async_err::Severity;
+
+    let mut errors = [
+        AsyncError::new(OpaqueError { code: 1 }).with_severity(Severity::Warning),
+        AsyncError::new(OpaqueError { code: 2 }).with_severity(Severity::Critical),
+    ];
+    errors.sort_by(AsyncError::cmp_by_severity);
+    assert_eq!(errors[0].severity(), Severity::Critical);
+}
+
+test_error!(CategoryError);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Route {
+    Retry,
+    Alert,
+}
+
+#[test]
+fn category_reads_back_the_attached_enum() {
+    let err = AsyncError::new(CategoryError("throttled")).with_category(Route::Retry);
+    assert_eq!(err.category::<Route>(), Some(Route::Retry));
+
+    let err = err.with_category(Route::Alert);
+    assert_eq!(err.category::<Route>(), Some(Route::Alert));
+}
+
+#[test]
+fn category_of_another_type_is_none() {
+    let err = AsyncError::new(CategoryError("throttled")).with_category(7u16);
+    assert_eq!(err.category::<Route>(), None);
+    assert_eq!(err.category::<u16>(), Some(7));
+
+    let err = AsyncError::new(CategoryError("throttled"));
+    assert_eq!(err.category::<Route>(), None);
+}
+
+test_error!(LazyError);
+
+#[test]
+fn lazy_stored_context_runs_only_when_first_read() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let err = AsyncError::new(LazyError("timeout")).with_lazy_stored_context(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        "fetching user 42".to_string()
+    });
+    assert_eq!(
+        calls.load(Ordering::SeqCst),
+        0,
+        "context formatted at wrap time"
+    );
+
+    assert_eq!(err.context(), Some("fetching user 42"));
+    assert_eq!(err.to_string(), "fetching user 42: timeout");
+    assert_eq!(err.context(), Some("fetching user 42"));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn lazy_stored_context_is_rendered_on_display() {
+    let err = AsyncError::new(LazyError("timeout"))
+        .append_context("inner".to_string())
+        .with_lazy_stored_context(|| "outer".to_string());
+    assert_eq!(err.to_string(), "outer: inner: timeout");
+}
+
+test_error!(ExitError);
+
+#[test]
+fn exit_code_follows_severity() {
+    use async_err::Severity;
+
+    assert_eq!(Severity::Warning.exit_code(), 1);
+    assert_eq!(Severity::Error.exit_code(), 1);
+    assert_eq!(Severity::Critical.exit_code(), 2);
+}
+
+#[test]
+fn report_exit_code_derives_the_code_and_marks_observed() {
+    use async_err::Severity;
+    use std::process::ExitCode;
+
+    let err = AsyncError::new(ExitError("corrupt index")).with_severity(Severity::Critical);
+    assert_eq!(err.report_exit_code(), ExitCode::from(2));
+    assert!(err.is_observed());
+
+    let err = AsyncError::new(ExitError("missing file"));
+    assert_eq!(err.report_exit_code(), ExitCode::from(1));
+
+    let err = AsyncError::new(ExitError("config")).with_severity(Severity::Critical);
+    assert_eq!(err.with_code("78").report_exit_code(), ExitCode::from(78));
+}
+
+#[test]
+fn exit_code_falls_back_to_the_severity_for_non_numeric_codes() {
+    use async_err::Severity;
+
+    let err = AsyncError::new(ExitError("x")).with_severity(Severity::Critical);
+    assert_eq!(err.exit_code(), 2);
+    let err = err.with_code("E_CONFIG");
+    assert_eq!(err.exit_code(), 2);
+    assert_eq!(err.with_code("0").exit_code(), 2, "0 would report success");
+    let err = AsyncError::new(ExitError("x")).with_code("300");
+    assert_eq!(err.exit_code(), 1, "out of range for an exit code");
+}
+
+#[test]
+fn exit_report_terminates_with_the_severity_code() {
+    use async_err::{error::ExitReport, Severity};
+    use std::process::{ExitCode, Termination};
+
+    let ok: ExitReport<ExitError> = Ok(()).into();
+    assert_eq!(ok.report(), ExitCode::SUCCESS);
+
+    let failed: ExitReport<ExitError> =
+        Err(AsyncError::new(ExitError("corrupt index")).with_severity(Severity::Critical)).into();
+    assert_eq!(failed.report(), ExitCode::from(2));
+}
+
+test_error!(ObservedError);
+
+#[test]
+fn observed_is_independent_of_hooks_invoked() {
+    let err = AsyncError::new(ObservedError("timeout"));
+    assert!(!err.is_observed());
+
+    assert!(err.invoke_hooks_once());
+    assert!(
+        !err.is_observed(),
+        "firing hooks does not count as handling"
+    );
+
+    let err = AsyncError::new(ObservedError("timeout"));
+    err.mark_observed();
+    assert!(err.is_observed());
+    assert!(
+        err.invoke_hooks_once(),
+        "handling does not count as firing hooks"
+    );
+}
+
+#[test]
+fn observed_survives_map() {
+    let err = AsyncError::new(ObservedError("timeout"));
+    err.mark_observed();
+
+    let mapped = err.map(|err| PlainError(err.0), |ctx| ctx);
+    assert!(mapped.is_observed());
+}
+
+test_error!(ChainRoot);
+
+fn nested(
+    root: &'static str,
+    severity: async_err::Severity,
+) -> AsyncError<Layer<Layer<ChainRoot>>> {
+    AsyncError::new(Layer {
+        message: "request failed",
+        source: Layer {
+            message: "query failed",
+            source: ChainRoot(root),
+        },
+    })
+    .append_context("loading profile".to_string())
+    .with_severity(severity)
+}
+
+#[test]
+fn chain_eq_compares_independently_built_chains() {
+    use async_err::Severity;
+
+    let a = nested("socket closed", Severity::Error);
+    let b = nested("socket closed", Severity::Critical).with_metadata("attempt", "2");
+    assert!(a.chain_eq(&b));
+    assert!(b.chain_eq(&a));
+}
+
+#[test]
+fn chain_eq_detects_differing_causes_and_contexts() {
+    use async_err::Severity;
+
+    let a = nested("socket closed", Severity::Error);
+    assert!(!a.chain_eq(&nested("timed out", Severity::Error)));
+
+    let b = nested("socket closed", Severity::Error).append_context("retrying".to_string());
+    assert!(!a.chain_eq(&b));
+}
+
+test_error!(MergeError);
+
+#[test]
+fn merge_reports_both_messages_with_self_as_source() {
+    use async_err::Severity;
+    use std::error::Error;
+
+    let primary = AsyncError::new(MergeError("disk full"))
+        .append_context("writing report".to_string())
+        .with_metadata("path", "/tmp/report")
+        .with_metadata("attempt", "1");
+    let cleanup = AsyncError::new(MergeError("permission denied"))
+        .append_context("removing temp file".to_string())
+        .with_metadata("attempt", "2")
+        .with_metadata("temp", "/tmp/report.tmp")
+        .with_severity(Severity::Critical);
+
+    let merged = primary.merge(cleanup, " | ");
+
+    assert_eq!(
+        merged.context(),
+        Some("removing temp file: permission denied | writing report")
+    );
+    assert_eq!(
+        merged.to_string(),
+        "removing temp file: permission denied | writing report: disk full"
+    );
+    assert_eq!(merged.source().unwrap().to_string(), "disk full");
+    assert_eq!(merged.severity(), Severity::Critical);
+    assert_eq!(merged.meta("path"), Some("/tmp/report"));
+    assert_eq!(merged.meta("temp"), Some("/tmp/report.tmp"));
+    assert_eq!(merged.meta("attempt"), Some("1"));
+}
+
+#[test]
+fn merge_without_primary_context_uses_the_other_message() {
+    let merged = AsyncError::new(MergeError("disk full"))
+        .merge(AsyncError::new(MergeError("permission denied")), "; ");
+    assert_eq!(merged.to_string(), "permission denied: disk full");
+}
+
+#[derive(Debug, PartialEq)]
+struct CodeError(u16);
+
+impl std::fmt::Display for CodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "code {}", self.0)
+    }
+}
+
+impl std::error::Error for CodeError {}
+
+#[test]
+fn inner_eq_ignores_differing_contexts() {
+    let a = AsyncError::new(CodeError(404))
+        .append_context("loading user".to_string())
+        .with_metadata("id", "7");
+    let b = AsyncError::new(CodeError(404)).append_context("fetching profile".to_string());
+
+    assert!(a.inner_eq(&b));
+    assert!(!a.inner_eq(&AsyncError::new(CodeError(500))));
+}
+
+#[test]
+fn without_context_leaves_a_bare_error() {
+    use async_err::Severity;
+
+    let err = AsyncError::new(CodeError(404))
+        .append_context("loading user".to_string())
+        .with_metadata("id", "7")
+        .with_help("check the id")
+        .with_category(3u8)
+        .with_severity(Severity::Warning)
+        .without_context();
+
+    assert_eq!(err.to_string(), "code 404");
+    assert!(err.contexts().is_empty());
+    assert!(err.metadata().is_empty());
+    assert_eq!(err.help(), None);
+    assert_eq!(err.category::<u8>(), None);
+    assert_eq!(err.severity(), Severity::Warning);
+}
+
+test_error!(AggregateError);
+
+#[test]
+fn sources_lists_the_primary_then_additional_causes() {
+    use std::error::Error;
+
+    let err = AsyncError::new(AggregateError("replica a down"))
+        .with_additional_source(Box::new(AggregateError("replica b down")))
+        .with_additional_source(Box::new(std::io::Error::other("replica c down")));
+
+    let sources: Vec<String> = err.sources().map(ToString::to_string).collect();
+    assert_eq!(
+        sources,
+        ["replica a down", "replica b down", "replica c down"]
+    );
+    assert_eq!(err.source().unwrap().to_string(), "replica a down");
+    assert_eq!(err.to_string(), "replica a down");
+}
+
+#[test]
+fn sources_without_additional_causes_is_the_primary() {
+    let err = AsyncError::new(AggregateError("replica a down"));
+    assert_eq!(err.sources().count(), 1);
+}
+
+#[test]
+fn report_lists_a_two_deep_chain() {
+    use async_err::Severity;
+
+    let err = nested("socket closed", Severity::Error);
+    assert_eq!(
+        err.report(),
+        "loading profile: request failed: query failed: socket closed"
+    );
+
+    let err = err.with_pretty_report(true);
+    assert_eq!(
+        err.report(),
+        "loading profile: request failed\n\
+         \n\
+         Caused by:\n      \
+         0: query failed\n      \
+         1: socket closed"
+    );
+}
+
+#[test]
+fn pretty_report_does_not_number_a_single_cause() {
+    let err = AsyncError::new(Layer {
+        message: "query failed",
+        source: ChainRoot("socket closed"),
+    })
+    .with_pretty_report(true);
+    assert_eq!(
+        err.report(),
+        "query failed\n\nCaused by:\n      socket closed"
+    );
+
+    let err = AsyncError::new(ChainRoot("socket closed")).with_pretty_report(true);
+    assert_eq!(err.report(), "socket closed");
+}
+
+test_error!(KvError);
+
+#[test]
+fn as_kv_lists_message_context_code_severity_and_metadata() {
+    use async_err::Severity;
+
+    let err = AsyncError::new(KvError("timeout"))
+        .append_context("reading row".to_string())
+        .with_context_template("loading user {user_id}")
+        .with_severity(Severity::Warning)
+        .with_code("E_TIMEOUT")
+        .with_metadata("user_id", "42")
+        .with_metadata("attempt", "3");
+
+    let pairs: Vec<(&'static str, String)> = err.as_kv();
+    assert_eq!(
+        pairs,
+        [
+            ("error", "timeout".to_string()),
+            ("context", "loading user 42: reading row".to_string()),
+            ("code", "E_TIMEOUT".to_string()),
+            ("severity", "WARN".to_string()),
+            ("metadata", "attempt=3".to_string()),
+            ("metadata", "user_id=42".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn as_kv_omits_missing_context() {
+    let err = AsyncError::new(KvError("timeout")).with_debug_rendering();
+    assert_eq!(
+        err.as_kv(),
+        [
+            ("error", "KvError(\"timeout\")".to_string()),
+            ("severity", "ERROR".to_string()),
+        ]
+    );
+}
+
+test_error!(CachedError);
+
+#[test]
+fn cached_display_is_reused_and_refreshed_after_set_context() {
+    let mut err = AsyncError::new(CachedError("timeout"))
+        .append_context("fetching".to_string())
+        .with_cached_display();
+    assert_eq!(err.cached_display(), None, "filled on first display only");
+
+    assert_eq!(err.to_string(), "fetching: timeout");
+    let cached = err.cached_display().unwrap().as_ptr();
+    assert_eq!(format!("{}", err), "fetching: timeout");
+    assert_eq!(err.cached_display().unwrap().as_ptr(), cached);
+
+    err.set_context("refetching");
+    assert_eq!(err.cached_display(), None);
+    assert_eq!(err.to_string(), "refetching: timeout");
+    assert_eq!(err.cached_display(), Some("refetching: timeout"));
+}
+
+#[test]
+fn cached_display_follows_metadata_used_by_templates() {
+    let err = AsyncError::new(CachedError("timeout"))
+        .with_context_template("fetching user {id}")
+        .with_cached_display();
+    assert_eq!(err.to_string(), "fetching user {id}: timeout");
+
+    let err = err.with_metadata("id", "7");
+    assert_eq!(err.to_string(), "fetching user 7: timeout");
+}
+
+#[test]
+fn display_is_not_cached_by_default() {
+    let err = AsyncError::new(CachedError("timeout"));
+    assert_eq!(err.to_string(), "timeout");
+    assert_eq!(err.cached_display(), None);
+}
@@ -0,0 +1,124 @@
+//! Toggles global error counting, so it runs as its own test binary.
+#[macro_use]
+mod common;
+
+use async_err::error::reset_error_counts;
+use async_err::{error_counts, set_count_errors, AsyncError, AsyncResultExt};
+use parking_lot::Mutex;
+use std::any::TypeId;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+test_error!(ReadError);
+test_error!(ParseError);
+
+fn count_of<E: 'static>() -> u64 {
+    error_counts().get(&TypeId::of::<E>()).copied().unwrap_or(0)
+}
+
+#[test]
+fn counts_each_error_type_separately() {
+    let _lock = LOCK.lock();
+    reset_error_counts();
+    set_count_errors(true);
+
+    for _ in 0..3 {
+        let _ = AsyncError::new(ReadError("io")).with_context("reading".to_string());
+    }
+    for _ in 0..2 {
+        let _ = futures::executor::block_on(
+            async { Err::<(), _>(ParseError("syntax")) }.with_context(|_| "parsing".to_string()),
+        );
+    }
+    set_count_errors(false);
+
+    assert_eq!(count_of::<ReadError>(), 3);
+    assert_eq!(count_of::<ParseError>(), 2);
+}
+
+#[test]
+fn further_context_does_not_count_again() {
+    let _lock = LOCK.lock();
+    reset_error_counts();
+    set_count_errors(true);
+
+    let _ = AsyncError::new(ReadError("io"))
+        .with_context("reading".to_string())
+        .with_context("loading".to_string());
+    set_count_errors(false);
+
+    assert_eq!(count_of::<ReadError>(), 1);
+}
+
+#[test]
+fn nothing_is_counted_while_disabled() {
+    let _lock = LOCK.lock();
+    reset_error_counts();
+    set_count_errors(false);
+
+    let _ = AsyncError::new(ReadError("io")).with_context("reading".to_string());
+
+    assert_eq!(count_of::<ReadError>(), 0);
+    assert!(error_counts().is_empty());
+}
+
+#[cfg(feature = "hooks")]
+test_error!(DeferredError);
+
+#[cfg(feature = "hooks")]
+#[test]
+fn deferred_errors_are_counted_when_wrapped() {
+    let _lock = LOCK.lock();
+    reset_error_counts();
+    set_count_errors(true);
+
+    let deferred = futures::executor::block_on(
+        async { Err::<(), _>(DeferredError("timeout")) }
+            .with_context_deferred(|_| "syncing".to_string()),
+    )
+    .unwrap_err();
+    assert_eq!(count_of::<DeferredError>(), 1);
+
+    // Firing the hooks later does not count the error again
+    deferred.fire();
+    set_count_errors(false);
+    assert_eq!(count_of::<DeferredError>(), 1);
+}
+
+test_error!(FastError);
+
+#[test]
+fn fast_failures_are_counted_without_context() {
+    let _lock = LOCK.lock();
+    reset_error_counts();
+    set_count_errors(true);
+
+    let err = futures::executor::block_on(
+        async { Err::<(), _>(FastError("not found")) }
+            .with_context_if_slower_than(std::time::Duration::from_secs(3600), |_| {
+                "slow lookup".to_string()
+            }),
+    )
+    .unwrap_err();
+    set_count_errors(false);
+
+    assert_eq!(err.context(), None);
+    assert_eq!(count_of::<FastError>(), 1);
+}
+
+test_error!(RecoveredError);
+
+#[test]
+fn recovered_errors_are_counted() {
+    let _lock = LOCK.lock();
+    reset_error_counts();
+    set_count_errors(true);
+
+    let value = futures::executor::block_on(
+        async { Err::<u32, _>(RecoveredError("cache miss")) }.recover_with(|_| 0),
+    );
+    set_count_errors(false);
+
+    assert_eq!(value, 0);
+    assert_eq!(count_of::<RecoveredError>(), 1);
+}
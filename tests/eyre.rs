@@ -0,0 +1,48 @@
+#![cfg(feature = "eyre")]
+#[macro_use]
+mod common;
+
+use async_err::AsyncError;
+
+test_error!(EyreError);
+
+#[test]
+fn into_eyre_keeps_contexts_as_layers() {
+    let report = AsyncError::new(EyreError("disk full"))
+        .append_context("writing cache".to_string())
+        .with_context_template("saving profile {user}")
+        .with_metadata("user", "42")
+        .into_eyre();
+
+    let chain: Vec<String> = report.chain().map(ToString::to_string).collect();
+    assert_eq!(chain, ["saving profile 42", "writing cache", "disk full"]);
+
+    let debug = format!("{:?}", report);
+    assert!(
+        debug
+            .starts_with("saving profile 42\n\nCaused by:\n   0: writing cache\n   1: disk full\n"),
+        "{}",
+        debug
+    );
+    assert!(report.root_cause().downcast_ref::<EyreError>().is_some());
+}
+
+#[test]
+fn question_mark_keeps_the_async_error_as_one_layer() {
+    fn save() -> eyre::Result<()> {
+        Err(AsyncError::new(EyreError("disk full")).append_context("saving".to_string()))?;
+        Ok(())
+    }
+
+    let report = save().unwrap_err();
+    let chain: Vec<String> = report.chain().map(ToString::to_string).collect();
+    assert_eq!(chain, ["saving: disk full", "disk full"]);
+    assert!(report.downcast_ref::<AsyncError<EyreError>>().is_some());
+}
+
+#[test]
+fn into_eyre_without_context_is_the_inner_error() {
+    let report = AsyncError::new(EyreError("disk full")).into_eyre();
+    assert_eq!(report.to_string(), "disk full");
+    assert_eq!(report.chain().count(), 1);
+}
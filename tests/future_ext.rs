@@ -0,0 +1,1059 @@
+#[macro_use]
+mod common;
+
+use async_err::prelude::*;
+use futures::task::noop_waker_ref;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+use std::task::{Context, Poll};
+
+test_error!(RecoverError);
+
+#[tokio::test]
+async fn recover_with_returns_value_on_success() {
+    let value = async { Ok::<_, RecoverError>(7) }.recover_with(|_| 0).await;
+    assert_eq!(value, 7);
+}
+
+#[cfg(feature = "hooks")]
+#[tokio::test]
+async fn recover_with_fires_hook_once_and_recovers() {
+    let hook = common::CountingHook::register::<RecoverError>();
+
+    let value = async { Err::<u32, _>(RecoverError("cache miss")) }
+        .recover_with(|err| err.0.len() as u32)
+        .await;
+
+    assert_eq!(value, 10);
+    assert_eq!(hook.calls(), 1);
+    assert_eq!(hook.last().as_deref(), Some("cache miss"));
+}
+
+#[cfg(feature = "hooks")]
+test_error!(ObservedRecoverError);
+
+/// Hook recording whether each error it sees was marked as observed.
+#[cfg(feature = "hooks")]
+#[derive(Default)]
+struct ObservedHook(parking_lot::Mutex<Vec<bool>>);
+
+#[cfg(feature = "hooks")]
+impl AsyncErrorHook<ObservedRecoverError> for ObservedHook {
+    fn on_error(&self, error: &AsyncError<ObservedRecoverError>) {
+        self.0.lock().push(error.is_observed());
+    }
+}
+
+#[cfg(feature = "hooks")]
+#[tokio::test]
+async fn recover_with_marks_the_error_observed_before_hooks_fire() {
+    let hook = Arc::new(ObservedHook::default());
+    register_hook::<ObservedRecoverError>(hook.clone());
+
+    let value = async { Err::<u32, _>(ObservedRecoverError("cache miss")) }
+        .recover_with(|_| 0)
+        .await;
+    let _ = async { Err::<u32, _>(ObservedRecoverError("disk full")) }
+        .with_context(|_| "saving".to_string())
+        .await;
+
+    assert_eq!(value, 0);
+    assert_eq!(*hook.0.lock(), [true, false]);
+}
+
+test_error!(StreamError);
+
+#[tokio::test]
+async fn and_then_stream_async_yields_second_stage_items() {
+    use futures::StreamExt;
+
+    let items: Vec<_> = async { Ok::<_, StreamError>(3) }
+        .and_then_stream_async(|n| futures::stream::iter((1..=n).map(Ok::<_, StreamError>)))
+        .collect()
+        .await;
+    assert_eq!(items, [Ok(1), Ok(2), Ok(3)]);
+}
+
+#[tokio::test]
+async fn and_then_stream_async_yields_single_error_when_first_stage_fails() {
+    use futures::StreamExt;
+
+    let items: Vec<Result<u32, _>> = async { Err::<u32, _>(StreamError("no cursor")) }
+        .and_then_stream_async(|_| futures::stream::iter(vec![Ok(1)]))
+        .collect()
+        .await;
+    assert_eq!(items, [Err(StreamError("no cursor"))]);
+}
+
+test_error!(DebugContextError);
+
+#[tokio::test]
+async fn with_debug_context_attaches_debug_output() {
+    let err = async { Err::<(), _>(DebugContextError("disk full")) }
+        .with_debug_context()
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.context(), Some("DebugContextError(\"disk full\")"));
+    assert_eq!(err.inner_error(), &DebugContextError("disk full"));
+}
+
+test_error!(MergeError);
+
+#[tokio::test]
+async fn and_then_async_ctx_merge_prepends_first_stage_context() {
+    let err = async { Ok::<_, AsyncError<MergeError>>(42u32) }
+        .and_then_async_ctx_merge(
+            |id| format!("loading user {}", id),
+            |_| async {
+                Err::<(), _>(
+                    AsyncError::new(MergeError("not found"))
+                        .append_context("querying db".to_string()),
+                )
+            },
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "loading user 42: querying db: not found");
+    assert_eq!(err.contexts(), ["querying db", "loading user 42"]);
+}
+
+#[tokio::test]
+async fn and_then_async_ctx_merge_leaves_first_stage_errors_alone() {
+    let err = async {
+        Err::<u32, _>(
+            AsyncError::new(MergeError("offline")).append_context("connecting".to_string()),
+        )
+    }
+    .and_then_async_ctx_merge(
+        |_| unreachable!("first stage failed"),
+        |_| async { Ok::<(), AsyncError<MergeError>>(()) },
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(err.to_string(), "connecting: offline");
+}
+
+test_error!(DropError);
+
+/// Sets the flag when dropped.
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn with_context_drops_the_closure_on_ok() {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let capture = DropFlag(dropped.clone());
+    let mut fut = Box::pin(async { Ok::<_, DropError>(1) }.with_context(move |_| {
+        let _keep = &capture;
+        "unreachable".to_string()
+    }));
+
+    assert_eq!((&mut fut).await.unwrap(), 1);
+    assert!(
+        dropped.load(Ordering::SeqCst),
+        "closure kept alive after Ok"
+    );
+    drop(fut);
+}
+
+test_error!(LazyError);
+
+#[tokio::test]
+async fn ctx_lazy_only_evaluates_arguments_on_error() {
+    let calls = AtomicUsize::new(0);
+    let compute_id = || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        7
+    };
+
+    let value = async { Ok::<_, LazyError>("ok") }
+        .with_context(async_err::ctx_lazy!("id={}", compute_id()))
+        .await
+        .unwrap();
+    assert_eq!(value, "ok");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    let err = async { Err::<(), _>(LazyError("gone")) }
+        .with_context(async_err::ctx_lazy!("id={}", compute_id()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "id=7: gone");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+test_error!(DynConvertError);
+
+#[tokio::test]
+async fn into_dyn_converts_with_question_mark() {
+    use async_err::{DynResult, Severity};
+
+    async fn load() -> DynResult<()> {
+        async { Err::<(), _>(DynConvertError("corrupt")) }
+            .with_context(|_| "loading cache".to_string())
+            .await
+            .map_err(|err| {
+                err.with_severity(Severity::Warning)
+                    .with_metadata("key", "users")
+            })
+            .map_err(AsyncError::into_dyn)?;
+        Ok(())
+    }
+
+    let err = load().await.unwrap_err();
+    assert_eq!(err.to_string(), "loading cache: corrupt");
+    assert_eq!(err.severity(), Severity::Warning);
+    assert_eq!(err.meta("key"), Some("users"));
+    assert_eq!(
+        err.inner_error()
+            .get_ref()
+            .downcast_ref::<DynConvertError>(),
+        Some(&DynConvertError("corrupt"))
+    );
+}
+
+#[tokio::test]
+async fn question_mark_boxes_the_whole_error_into_boxed_error() {
+    use async_err::BoxedError;
+
+    async fn load() -> Result<(), BoxedError> {
+        async { Err::<(), _>(DynConvertError("corrupt")) }
+            .with_context(|_| "loading cache".to_string())
+            .await?;
+        Ok(())
+    }
+
+    let err = load().await.unwrap_err();
+    assert_eq!(err.to_string(), "loading cache: corrupt");
+    let wrapped = err
+        .get_ref()
+        .downcast_ref::<AsyncError<DynConvertError>>()
+        .unwrap();
+    assert_eq!(wrapped.context(), Some("loading cache"));
+    assert_eq!(wrapped.inner_error(), &DynConvertError("corrupt"));
+}
+
+test_error!(RepeatError);
+
+#[tokio::test]
+async fn repeat_until_async_stops_when_done() {
+    let attempts = AtomicUsize::new(0);
+    let value = async_err::repeat_until_async(
+        || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            async move { Ok::<_, RepeatError>(attempt) }
+        },
+        |attempt| *attempt == 3,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(value, 3);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn repeat_until_async_wraps_the_first_error_with_the_attempt() {
+    let attempts = AtomicUsize::new(0);
+    let err = async_err::repeat_until_async(
+        || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                match attempt {
+                    1 => Ok(false),
+                    _ => Err(RepeatError("job vanished")),
+                }
+            }
+        },
+        |done| *done,
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(err.to_string(), "failed on attempt 2: job vanished");
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn repeat_until_async_yields_between_rejected_attempts() {
+    let mut fut = Box::pin(async_err::repeat_until_async(
+        || async { Ok::<_, RepeatError>(()) },
+        |_| false,
+    ));
+    let mut cx = Context::from_waker(noop_waker_ref());
+
+    for _ in 0..3 {
+        assert!(matches!(fut.as_mut().poll(&mut cx), Poll::Pending));
+    }
+}
+
+test_error!(OuterError);
+
+#[tokio::test]
+async fn with_outer_context_stacks_instead_of_nesting() {
+    let inner =
+        async { Err::<(), _>(OuterError("refused")) }.with_context(|_| "connecting".to_string());
+
+    let result: Result<(), AsyncError<OuterError>> = inner
+        .with_outer_context(|err| format!("starting worker ({})", err.0))
+        .await;
+
+    let err = result.unwrap_err();
+    assert_eq!(err.inner_error(), &OuterError("refused"));
+    assert_eq!(err.contexts(), ["connecting", "starting worker (refused)"]);
+    assert_eq!(
+        err.to_string(),
+        "starting worker (refused): connecting: refused"
+    );
+}
+
+test_error!(AsyncContextError);
+
+#[tokio::test]
+async fn with_async_context_awaits_the_context_future() {
+    let err = async { Err::<(), _>(AsyncContextError("denied")) }
+        .with_async_context(|err| {
+            let reason = err.0;
+            async move {
+                tokio::task::yield_now().await;
+                format!("checking permissions ({})", reason)
+            }
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "checking permissions (denied): denied");
+}
+
+#[tokio::test]
+async fn with_async_context_uses_placeholder_when_context_future_panics() {
+    let err = async { Err::<(), _>(AsyncContextError("denied")) }
+        .with_async_context(|_| async {
+            tokio::task::yield_now().await;
+            panic!("context lookup failed");
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.inner_error(), &AsyncContextError("denied"));
+    assert_eq!(err.context(), Some("<context unavailable>"));
+}
+
+#[tokio::test]
+async fn with_async_context_uses_placeholder_when_closure_panics() {
+    let err = async { Err::<(), _>(AsyncContextError("denied")) }
+        .with_async_context(|_| -> std::future::Ready<String> { panic!("no context") })
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.context(), Some("<context unavailable>"));
+}
+
+test_error!(TimedError);
+
+#[tokio::test]
+async fn and_then_async_timed_report_measures_both_stages() {
+    use std::time::Duration;
+
+    let (value, timings) = async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Ok::<_, AsyncError<TimedError>>(2)
+    }
+    .and_then_async_timed_report(|n| async move {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        Ok::<_, AsyncError<TimedError>>(n * 2)
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(value, 4);
+    assert!(timings.first >= Duration::from_millis(20), "{:?}", timings);
+    assert!(timings.second >= Duration::from_millis(10), "{:?}", timings);
+}
+
+#[tokio::test]
+async fn and_then_async_timed_report_records_timings_on_failure() {
+    let err = async { Ok::<_, AsyncError<TimedError>>(()) }
+        .and_then_async_timed_report(|_| async {
+            Err::<(), _>(AsyncError::new(TimedError("stage two")))
+        })
+        .await
+        .unwrap_err();
+    assert!(err.meta("first_stage_duration").is_some());
+    assert!(err.meta("second_stage_duration").is_some());
+
+    let err = async { Err::<(), _>(AsyncError::new(TimedError("stage one"))) }
+        .and_then_async_timed_report(|_| async { Ok::<(), AsyncError<TimedError>>(()) })
+        .await
+        .unwrap_err();
+    assert!(err.meta("first_stage_duration").is_some());
+    assert_eq!(err.meta("second_stage_duration"), None);
+}
+
+test_error!(CancelError);
+
+#[test]
+fn on_cancel_runs_when_dropped_mid_flight() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    let mut chain = Box::pin(
+        async { Ok::<_, CancelError>(1) }
+            .and_then_async(|_| futures::future::pending::<Result<(), CancelError>>())
+            .on_cancel(move || flag.store(true, Ordering::SeqCst)),
+    );
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+    assert!(chain.as_mut().poll(&mut cx).is_pending());
+    assert!(!cancelled.load(Ordering::SeqCst));
+
+    drop(chain);
+    assert!(cancelled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn on_cancel_does_not_run_after_completion() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+
+    let value = async { Ok::<_, CancelError>(2) }
+        .and_then_async(|n| async move { Ok(n * 3) })
+        .on_cancel(move || flag.store(true, Ordering::SeqCst))
+        .await
+        .unwrap();
+
+    assert_eq!(value, 6);
+    assert!(!cancelled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn into_ok_collapses_infallible_results() {
+    let value: u32 = async { Ok::<_, std::convert::Infallible>(9) }
+        .into_ok()
+        .await;
+    assert_eq!(value, 9);
+
+    let chained = async { Ok::<_, std::convert::Infallible>("id") }
+        .and_then_async(|id| async move { Ok(id.len()) })
+        .into_ok()
+        .await;
+    assert_eq!(chained, 2);
+}
+
+test_error!(RetryError);
+
+#[tokio::test]
+async fn and_then_async_retry_second_succeeds_on_retry() {
+    let fetches = AtomicUsize::new(0);
+    let attempts = AtomicUsize::new(0);
+
+    let value = async {
+        fetches.fetch_add(1, Ordering::SeqCst);
+        Ok::<_, RetryError>(10)
+    }
+    .and_then_async_retry_second(3, |&n| {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        async move {
+            match attempt {
+                3 => Ok(n + 1),
+                _ => Err(RetryError("busy")),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(value, 11);
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn and_then_async_retry_second_reports_exhausted_attempts() {
+    let attempts = AtomicUsize::new(0);
+
+    let err = async { Ok::<_, RetryError>(()) }
+        .and_then_async_retry_second(2, |_| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async { Err::<(), _>(RetryError("busy")) }
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "second stage failed after 2 attempts: busy"
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+/// First-stage value that cannot be cloned, such as a fetched response body.
+#[derive(Debug)]
+struct Fetched {
+    body: String,
+}
+
+#[tokio::test]
+async fn and_then_async_retry_second_reuses_a_non_clone_value() {
+    let attempts = AtomicUsize::new(0);
+
+    let len = async {
+        Ok::<_, RetryError>(Fetched {
+            body: "payload".to_string(),
+        })
+    }
+    .and_then_async_retry_second(2, |fetched: &Fetched| {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        let len = fetched.body.len();
+        async move {
+            match attempt {
+                1 => Err(RetryError("busy")),
+                _ => Ok(len),
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(len, 7);
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn and_then_async_retry_second_does_not_retry_the_first_stage() {
+    let attempts = AtomicUsize::new(0);
+
+    let err = async { Err::<u32, _>(RetryError("fetch failed")) }
+        .and_then_async_retry_second(5, |&n| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async move { Ok::<_, RetryError>(n) }
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "first stage failed: fetch failed");
+    assert_eq!(attempts.load(Ordering::SeqCst), 0);
+}
+
+test_error!(DedupError);
+
+#[tokio::test]
+async fn with_context_dedup_skips_context_repeating_the_message() {
+    let err = async { Err::<(), _>(DedupError("not found")) }
+        .with_context_dedup(|err| err.to_string())
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.context(), None);
+    assert_eq!(err.to_string(), "not found");
+}
+
+#[tokio::test]
+async fn with_context_dedup_attaches_distinct_context() {
+    let err = async { Err::<(), _>(DedupError("not found")) }
+        .with_context_dedup(|_| "loading profile".to_string())
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.context(), Some("loading profile"));
+    assert_eq!(err.to_string(), "loading profile: not found");
+}
+
+#[cfg(feature = "hooks")]
+test_error!(DedupHookError);
+
+#[cfg(feature = "hooks")]
+#[tokio::test]
+async fn with_context_dedup_fires_hooks_either_way() {
+    let hook = common::CountingHook::register::<DedupHookError>();
+
+    let _ = async { Err::<(), _>(DedupHookError("dup")) }
+        .with_context_dedup(|err| err.to_string())
+        .await;
+    let _ = async { Err::<(), _>(DedupHookError("dup")) }
+        .with_context_dedup(|_| "other".to_string())
+        .await;
+
+    assert_eq!(hook.calls(), 2);
+}
+
+test_error!(ReplicaError);
+
+async fn replica(
+    delay_ms: u64,
+    outcome: Result<u32, &'static str>,
+    _guard: DropFlag,
+) -> Result<u32, ReplicaError> {
+    tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+    outcome.map_err(ReplicaError)
+}
+
+fn flag() -> (Arc<AtomicBool>, DropFlag) {
+    let dropped = Arc::new(AtomicBool::new(false));
+    (dropped.clone(), DropFlag(dropped))
+}
+
+#[tokio::test]
+async fn select_ok_async_returns_the_first_success() {
+    let (_, a) = flag();
+    let (_, b) = flag();
+    let (_, c) = flag();
+    let value = async_err::select_ok_async(vec![
+        replica(0, Err("refused"), a),
+        replica(50, Ok(1), b),
+        replica(10, Ok(2), c),
+    ])
+    .await
+    .unwrap();
+
+    assert_eq!(value, 2);
+}
+
+#[tokio::test]
+async fn select_ok_async_drops_the_losers_after_a_win() {
+    let (winner_dropped, winner) = flag();
+    let (loser_dropped, loser) = flag();
+    let mut race = Box::pin(async_err::select_ok_async(vec![
+        replica(0, Ok(1), winner),
+        replica(60_000, Ok(2), loser),
+    ]));
+
+    assert_eq!(race.as_mut().await.unwrap(), 1);
+    assert!(winner_dropped.load(Ordering::SeqCst));
+    assert!(
+        loser_dropped.load(Ordering::SeqCst),
+        "losing future kept alive after a win"
+    );
+}
+
+#[tokio::test]
+async fn select_ok_async_collects_every_error_when_all_fail() {
+    let (_, a) = flag();
+    let (_, b) = flag();
+    let err = async_err::select_ok_async(vec![
+        replica(10, Err("timeout"), a),
+        replica(0, Err("refused"), b),
+    ])
+    .await
+    .unwrap_err();
+
+    let errors: Vec<String> = err.errors().iter().map(ToString::to_string).collect();
+    assert_eq!(errors, ["future 0: timeout", "future 1: refused"]);
+}
+
+test_error!(CleanupError);
+
+#[tokio::test]
+async fn with_cleanup_runs_cleanup_on_second_stage_failure() {
+    let cleaned = Arc::new(std::sync::Mutex::new(None));
+    let slot = cleaned.clone();
+    let err = async { Ok::<_, CleanupError>("conn-1".to_string()) }
+        .and_then_async_with_cleanup(
+            |_conn| async { Err::<(), _>(CleanupError("query failed")) },
+            move |conn| async move { *slot.lock().unwrap() = Some(conn) },
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.0, "query failed");
+    assert_eq!(cleaned.lock().unwrap().as_deref(), Some("conn-1"));
+}
+
+#[tokio::test]
+async fn with_cleanup_skips_cleanup_on_success() {
+    let cleaned = Arc::new(AtomicBool::new(false));
+    let flag = cleaned.clone();
+    let value = async { Ok::<_, CleanupError>(2) }
+        .and_then_async_with_cleanup(
+            |conn| async move { Ok(conn * 10) },
+            move |_| async move { flag.store(true, Ordering::SeqCst) },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(value, 20);
+    assert!(!cleaned.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn with_cleanup_skips_cleanup_when_the_first_stage_fails() {
+    let cleaned = Arc::new(AtomicBool::new(false));
+    let flag = cleaned.clone();
+    let err = async { Err::<u32, _>(CleanupError("connect failed")) }
+        .and_then_async_with_cleanup(
+            |_| async { Ok::<(), _>(()) },
+            move |_| async move { flag.store(true, Ordering::SeqCst) },
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.0, "connect failed");
+    assert!(!cleaned.load(Ordering::SeqCst));
+}
+
+test_error!(SharedFetchError);
+
+#[tokio::test]
+async fn shareable_context_shares_one_failure_across_consumers() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let fetch = futures::FutureExt::shared(
+        async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err::<u32, _>(SharedFetchError("unreachable"))
+        }
+        .shareable_context(|_| "fetching config".to_string()),
+    );
+
+    let (a, b, c) = futures::join!(fetch.clone(), fetch.clone(), fetch);
+    let (a, b, c) = (a.unwrap_err(), b.unwrap_err(), c.unwrap_err());
+
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert!(a.ptr_eq(&b) && b.ptr_eq(&c));
+    assert_eq!(c.to_string(), "fetching config: unreachable");
+    assert_eq!(c.contexts(), ["fetching config"]);
+}
+
+#[tokio::test]
+async fn shareable_context_shares_success() {
+    let fetch = futures::FutureExt::shared(
+        async { Ok::<_, SharedFetchError>(7) }.shareable_context(|_| unreachable!()),
+    );
+    assert_eq!(fetch.clone().await.unwrap(), 7);
+    assert_eq!(fetch.await.unwrap(), 7);
+}
+
+test_error!(InspectError);
+
+#[tokio::test]
+async fn with_context_closure_runs_once_per_error() {
+    let calls = AtomicUsize::new(0);
+    let inspect = |err: &InspectError| {
+        calls.fetch_add(1, Ordering::SeqCst);
+        format!("saw {}", err)
+    };
+
+    for _ in 0..3 {
+        let err = async { Err::<(), _>(InspectError("timeout")) }
+            .with_context(inspect)
+            .await
+            .unwrap_err();
+        assert_eq!(err.context(), Some("saw timeout"));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn with_context_closure_does_not_run_on_success() {
+    let calls = AtomicUsize::new(0);
+    let value = async { Ok::<_, InspectError>(1) }
+        .with_context(|_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            "unused".to_string()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(value, 1);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+test_error!(RefStageError);
+
+#[derive(Debug)]
+struct Endpoint {
+    host: String,
+    port: u16,
+}
+
+#[tokio::test]
+async fn and_then_async_ref_borrows_the_first_value() {
+    let (endpoint, addr) = async {
+        Ok::<_, RefStageError>(Endpoint {
+            host: "db".to_string(),
+            port: 5432,
+        })
+    }
+    .and_then_async_ref(|endpoint| {
+        Box::pin(async move {
+            tokio::task::yield_now().await;
+            Ok(format!("{}:{}", endpoint.host, endpoint.port))
+        })
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(addr, "db:5432");
+    assert_eq!(endpoint.host, "db");
+}
+
+#[tokio::test]
+async fn and_then_async_ref_accepts_borrowed_values_and_non_send_stages() {
+    let hosts = vec!["db".to_string(), "cache".to_string()];
+    let (first, joined) = async { Ok::<_, RefStageError>(hosts.as_slice()) }
+        .and_then_async_ref(|first| {
+            let seen = std::rc::Rc::new(first.len());
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                Ok(format!("{} hosts: {}", seen, first.join(",")))
+            })
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(joined, "2 hosts: db,cache");
+    assert_eq!(first, ["db", "cache"]);
+}
+
+#[tokio::test]
+async fn and_then_async_ref_send_can_be_spawned() {
+    let chain = async {
+        Ok::<_, RefStageError>(Endpoint {
+            host: "db".to_string(),
+            port: 5432,
+        })
+    }
+    .and_then_async_ref_send(|endpoint| {
+        Box::pin(async move {
+            tokio::task::yield_now().await;
+            Ok(format!("{}:{}", endpoint.host, endpoint.port))
+        })
+    });
+    let (endpoint, addr) = tokio::spawn(chain).await.unwrap().unwrap();
+
+    assert_eq!(addr, "db:5432");
+    assert_eq!(endpoint.port, 5432);
+}
+
+#[tokio::test]
+async fn and_then_async_ref_reports_either_stage_failing() {
+    let err = async { Err::<Endpoint, _>(RefStageError("no config")) }
+        .and_then_async_ref(|_| Box::pin(async { Ok::<(), _>(()) }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.0, "no config");
+
+    let err = async {
+        Ok::<_, RefStageError>(Endpoint {
+            host: "db".to_string(),
+            port: 0,
+        })
+    }
+    .and_then_async_ref(|endpoint| {
+        Box::pin(async move {
+            if endpoint.port == 0 {
+                return Err(RefStageError("invalid port"));
+            }
+            Ok(())
+        })
+    })
+    .await
+    .unwrap_err();
+    assert_eq!(err.0, "invalid port");
+}
+
+#[tokio::test]
+async fn catch_panic_context_wraps_a_panic() {
+    let err = async {
+        tokio::task::yield_now().await;
+        if true {
+            panic!("index {} out of range", 7);
+        }
+        1
+    }
+    .catch_panic_context(|panic| format!("rendering page ({})", panic.message().len()))
+    .await
+    .unwrap_err();
+
+    assert_eq!(err.inner_error().message(), "index 7 out of range");
+    assert_eq!(err.context(), Some("rendering page (20)"));
+}
+
+#[tokio::test]
+async fn catch_panic_context_passes_values_through() {
+    let value = async { 42 }
+        .catch_panic_context(|_| unreachable!())
+        .await
+        .unwrap();
+    assert_eq!(value, 42);
+}
+
+#[tokio::test]
+async fn catch_panic_context_handles_non_string_payloads() {
+    let err = async { std::panic::panic_any(7u8) }
+        .catch_panic_context(|_| "decoding".to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(err.inner_error().message(), "Box<dyn Any>");
+}
+
+test_error!(NamedError);
+
+/// Public-API style function returning the wrapped future without naming `WithContext`.
+fn load_user(
+    id: u32,
+) -> impl Future<Output = Result<u32, async_err::AsyncError<NamedError>>> + Send {
+    async move {
+        if id == 0 {
+            return Err(NamedError("no such user"));
+        }
+        Ok(id)
+    }
+    .with_context_named(move |_| format!("loading user {}", id))
+}
+
+#[tokio::test]
+async fn with_context_named_can_be_returned_as_impl_future() {
+    assert_eq!(load_user(7).await.unwrap(), 7);
+
+    let err = tokio::spawn(load_user(0)).await.unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "loading user 0: no such user");
+}
+
+test_error!(SlowError);
+
+#[tokio::test]
+async fn with_context_if_slower_than_skips_fast_failures() {
+    let err = async { Err::<(), _>(SlowError("not found")) }
+        .with_context_if_slower_than(std::time::Duration::from_secs(3600), |_| {
+            "slow lookup".to_string()
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.context(), None);
+    assert_eq!(err.to_string(), "not found");
+}
+
+#[tokio::test]
+async fn with_context_if_slower_than_flags_slow_failures() {
+    let err = async {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        Err::<(), _>(SlowError("not found"))
+    }
+    .with_context_if_slower_than(std::time::Duration::from_millis(1), |_| {
+        "slow lookup".to_string()
+    })
+    .await
+    .unwrap_err();
+
+    assert_eq!(err.to_string(), "slow lookup: not found");
+}
+
+#[cfg(feature = "hooks")]
+test_error!(SlowHookError);
+
+#[cfg(feature = "hooks")]
+#[tokio::test]
+async fn with_context_if_slower_than_fires_hooks_either_way() {
+    let hook = common::CountingHook::register::<SlowHookError>();
+
+    let _ = async { Err::<(), _>(SlowHookError("fast")) }
+        .with_context_if_slower_than(std::time::Duration::from_secs(3600), |_| "slow".to_string())
+        .await;
+    assert_eq!(hook.calls(), 1);
+    assert_eq!(hook.last().as_deref(), Some("fast"));
+
+    let _ = async {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        Err::<(), _>(SlowHookError("late"))
+    }
+    .with_context_if_slower_than(std::time::Duration::from_millis(1), |_| "slow".to_string())
+    .await;
+    assert_eq!(hook.calls(), 2);
+}
+
+test_error!(PageError);
+
+#[tokio::test]
+async fn and_then_async_while_stops_on_a_sentinel() {
+    use std::ops::ControlFlow;
+
+    let called = AtomicBool::new(false);
+    let result = async { Ok::<Option<u32>, PageError>(None) }
+        .and_then_async_while(Option::is_some, |_| {
+            called.store(true, Ordering::SeqCst);
+            async { Ok::<_, PageError>("unused") }
+        })
+        .await
+        .unwrap();
+
+    assert!(matches!(result, ControlFlow::Break(None)));
+    assert!(!called.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn and_then_async_while_proceeds_when_accepted() {
+    use std::ops::ControlFlow;
+
+    let result = async { Ok::<_, PageError>(Some(2)) }
+        .and_then_async_while(Option::is_some, |cursor| async move {
+            Ok::<_, PageError>(format!("page {}", cursor.unwrap()))
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(result, ControlFlow::Continue("page 2".to_string()));
+}
+
+#[tokio::test]
+async fn and_then_async_while_passes_errors_through() {
+    let err = async { Err::<Option<u32>, _>(PageError("offline")) }
+        .and_then_async_while(Option::is_some, |_| async { Ok::<(), _>(()) })
+        .await
+        .unwrap_err();
+    assert_eq!(err.0, "offline");
+
+    let err = async { Ok::<_, PageError>(Some(1)) }
+        .and_then_async_while(Option::is_some, |_| async {
+            Err::<(), _>(PageError("page gone"))
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.0, "page gone");
+}
+
+test_error!(FnNameError);
+
+#[tokio::test]
+async fn with_fn_context_names_the_test_function() {
+    let err = async_err::with_fn_context!(async { Err::<(), _>(FnNameError("not found")) })
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err.context(),
+        Some("with_fn_context_names_the_test_function")
+    );
+}
+
+async fn load_settings() -> Result<(), async_err::AsyncError<FnNameError>> {
+    async_err::with_fn_context!(async { Err(FnNameError("missing key")) }).await
+}
+
+#[tokio::test]
+async fn with_fn_context_names_an_async_fn() {
+    let err = load_settings().await.unwrap_err();
+    assert_eq!(err.to_string(), "load_settings: missing key");
+}
+
+#[tokio::test]
+async fn with_fn_context_falls_back_to_file_and_line_in_closures() {
+    let run = || async_err::with_fn_context!(async { Err::<(), _>(FnNameError("gone")) });
+    let line = line!() - 1;
+    let err = run().await.unwrap_err();
+
+    assert_eq!(
+        err.context(),
+        Some(format!("tests/future_ext.rs:{}", line).as_str())
+    );
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "hooks")]
+#[macro_use]
+mod common;
+
+use async_err::{
+    error::max_context_depth,
+    hooks::{
+        format_hook_message, hook_timestamps_enabled, AsyncErrorHookDefault, HookConfig, HookFormat,
+    },
+    register_hook, AsyncError, AsyncErrorHook, Severity,
+};
+use parking_lot::Mutex;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Serializes the tests, which all change global hook configuration.
+static LOCK: Mutex<()> = Mutex::new(());
+
+test_error!(ConfigError);
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+fn count_warning(_message: &str) {
+    WARNINGS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn is_timestamped(severity: Severity) -> bool {
+    let err = AsyncError::new(ConfigError("x")).with_severity(severity);
+    !format_hook_message(&err).contains("] AsyncError Hook Triggered")
+}
+
+#[test]
+fn apply_installs_every_setting() {
+    let _lock = LOCK.lock();
+
+    HookConfig::new()
+        .timestamps(true)
+        .timestamp_threshold(Some(Severity::Critical))
+        .max_context_depth(5)
+        .warning_sink(Some(count_warning))
+        .apply();
+
+    assert!(hook_timestamps_enabled());
+    assert!(is_timestamped(Severity::Critical));
+    assert!(!is_timestamped(Severity::Warning));
+    assert_eq!(max_context_depth(), 5);
+    #[cfg(feature = "test-util")]
+    {
+        use std::any::TypeId;
+        let before = WARNINGS.load(Ordering::SeqCst);
+        async_err::test_util::record_hook_type_name(TypeId::of::<u8>(), "config::Dup");
+        async_err::test_util::record_hook_type_name(TypeId::of::<u16>(), "config::Dup");
+        assert_eq!(WARNINGS.load(Ordering::SeqCst), before + 1);
+    }
+
+    HookConfig::new()
+        .timestamps(false)
+        .timestamp_threshold(None)
+        .max_context_depth(usize::MAX)
+        .warning_sink(None)
+        .apply();
+    assert!(!hook_timestamps_enabled());
+    assert!(!is_timestamped(Severity::Critical));
+    assert_eq!(max_context_depth(), usize::MAX);
+}
+
+static SINK_OUTPUT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn capture(message: &str) {
+    SINK_OUTPUT.lock().push(message.to_string());
+}
+
+/// Hook relying on the default `on_error`.
+struct DefaultHook;
+
+impl AsyncErrorHook<SinkError> for DefaultHook {
+    fn on_error(&self, error: &AsyncError<SinkError>) {
+        AsyncErrorHookDefault::on_error(self, error);
+    }
+}
+
+test_error!(SinkError);
+
+#[test]
+fn apply_installs_output_settings() {
+    let _lock = LOCK.lock();
+    register_hook::<SinkError>(Arc::new(DefaultHook));
+
+    HookConfig::new()
+        .timestamps(false)
+        .format(HookFormat::Compact)
+        .sink(Some(capture))
+        .default_context("<unknown>")
+        .max_context_len(8)
+        .apply();
+
+    let _ = AsyncError::new(SinkError("refused")).with_context("connecting to db".to_string());
+    let bare = AsyncError::new(SinkError("timed out"));
+    assert_eq!(format_hook_message(&bare), "[ERROR] <unknown>: timed out");
+    assert_eq!(*SINK_OUTPUT.lock(), ["[ERROR] connect…: refused"]);
+
+    HookConfig::new()
+        .format(HookFormat::Pretty)
+        .sink(None)
+        .max_context_len(usize::MAX)
+        .apply();
+    assert!(format_hook_message(&bare).starts_with("[ERROR] AsyncError Hook Triggered\n"));
+    assert!(format_hook_message(&bare).contains("Context: <unknown>"));
+}
+
+#[test]
+fn unset_options_keep_their_current_value() {
+    let _lock = LOCK.lock();
+
+    HookConfig::new().max_context_depth(9).apply();
+    HookConfig::new().timestamps(false).apply();
+    assert_eq!(max_context_depth(), 9);
+    HookConfig::new().max_context_depth(usize::MAX).apply();
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn apply_installs_redaction() {
+    let _lock = LOCK.lock();
+
+    HookConfig::new()
+        .redaction(vec![regex::Regex::new(r"\d{4}").unwrap()])
+        .apply();
+    let err = AsyncError::new(ConfigError("pin 1234 rejected"));
+    assert!(format_hook_message(&err).contains("pin *** rejected"));
+    HookConfig::new().redaction(Vec::new()).apply();
+}
+
+/// Checks that timestamps and the context depth always come from the same config.
+struct ConsistencyHook {
+    torn: AtomicBool,
+}
+
+impl AsyncErrorHook<ConfigError> for ConsistencyHook {
+    fn on_error(&self, error: &AsyncError<ConfigError>) {
+        let timestamped = !format_hook_message(error).contains("] AsyncError Hook Triggered");
+        std::thread::yield_now();
+        let expected_depth = if timestamped { 10 } else { 20 };
+        if max_context_depth() != expected_depth {
+            self.torn.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[test]
+fn hooks_never_see_a_half_applied_config() {
+    let _lock = LOCK.lock();
+    HookConfig::new()
+        .timestamps(false)
+        .max_context_depth(20)
+        .apply();
+
+    let hook = Arc::new(ConsistencyHook {
+        torn: AtomicBool::new(false),
+    });
+    register_hook::<ConfigError>(hook.clone());
+
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let mut on = false;
+            while !done.load(Ordering::SeqCst) {
+                on = !on;
+                HookConfig::new()
+                    .timestamps(on)
+                    .max_context_depth(if on { 10 } else { 20 })
+                    .apply();
+            }
+        })
+    };
+    for _ in 0..500 {
+        let _ = AsyncError::new(ConfigError("x")).with_context("race".to_string());
+    }
+    done.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+    HookConfig::new()
+        .timestamps(false)
+        .max_context_depth(usize::MAX)
+        .apply();
+
+    assert!(!hook.torn.load(Ordering::SeqCst));
+}
+
+/// Applies a config from inside the hook, then formats with the config it started with.
+struct ReconfiguringHook {
+    message: Mutex<Option<String>>,
+}
+
+impl AsyncErrorHook<ReconfigError> for ReconfiguringHook {
+    fn on_error(&self, error: &AsyncError<ReconfigError>) {
+        HookConfig::new().format(HookFormat::Compact).apply();
+        *self.message.lock() = Some(format_hook_message(error));
+    }
+}
+
+test_error!(ReconfigError);
+
+#[test]
+fn apply_from_a_hook_takes_effect_after_it_returns() {
+    let _lock = LOCK.lock();
+    HookConfig::new().format(HookFormat::Pretty).apply();
+    let hook = Arc::new(ReconfiguringHook {
+        message: Mutex::new(None),
+    });
+    register_hook::<ReconfigError>(hook.clone());
+
+    let err = AsyncError::new(ReconfigError("refused")).with_context("connecting".to_string());
+    let seen = hook.message.lock().take().unwrap();
+    assert!(seen.starts_with("[ERROR] AsyncError Hook Triggered\n"));
+    assert_eq!(format_hook_message(&err), "[ERROR] connecting: refused");
+    HookConfig::new().format(HookFormat::Pretty).apply();
+}
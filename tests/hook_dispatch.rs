@@ -0,0 +1,339 @@
+//! Changes per-type dispatch modes and drains the global hook queue, so it runs as its
+//! own test binary.
+#![cfg(feature = "hooks")]
+#[macro_use]
+mod common;
+
+use async_err::hooks::{drain_hooks, hook_dispatch_mode, set_hook_dispatch_mode, HookDispatchMode};
+use async_err::{register_hook, AsyncError, AsyncErrorHook, Severity};
+use common::CountingHook;
+use futures::executor::block_on;
+use parking_lot::Mutex;
+use std::{
+    error::Error,
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Hook that waits for a release signal before recording what it saw.
+struct GateHook {
+    release: Mutex<mpsc::Receiver<()>>,
+    seen: Mutex<Option<(String, Severity, Option<String>)>>,
+    thread: Mutex<Option<String>>,
+}
+
+impl GateHook {
+    fn register<E: Error + 'static>() -> (Arc<Self>, mpsc::Sender<()>) {
+        let (release, gate) = mpsc::channel();
+        let hook = Arc::new(Self {
+            release: Mutex::new(gate),
+            seen: Mutex::new(None),
+            thread: Mutex::new(None),
+        });
+        register_hook::<E>(hook.clone());
+        (hook, release)
+    }
+}
+
+impl<E: Error + 'static> AsyncErrorHook<E> for GateHook {
+    fn on_error(&self, error: &AsyncError<E>) {
+        self.release.lock().recv().unwrap();
+        *self.thread.lock() = thread::current().name().map(str::to_string);
+        *self.seen.lock() = Some((
+            error.to_string(),
+            error.severity(),
+            error.meta("region").map(str::to_string),
+        ));
+    }
+}
+
+test_error!(SyncError);
+
+#[test]
+fn sync_mode_hooks_finish_before_with_context_returns() {
+    let _lock = LOCK.lock();
+    let hook = CountingHook::register::<SyncError>();
+
+    assert_eq!(hook_dispatch_mode::<SyncError>(), HookDispatchMode::Sync);
+    let _ = AsyncError::new(SyncError("refused")).with_context("connecting".to_string());
+    assert_eq!(hook.calls(), 1);
+    assert_eq!(hook.last().as_deref(), Some("connecting: refused"));
+}
+
+test_error!(DeferredError);
+
+#[test]
+fn after_return_hooks_run_later_on_a_snapshot() {
+    let _lock = LOCK.lock();
+    let (hook, release) = GateHook::register::<DeferredError>();
+    set_hook_dispatch_mode::<DeferredError>(HookDispatchMode::AfterReturn);
+    assert_eq!(
+        hook_dispatch_mode::<DeferredError>(),
+        HookDispatchMode::AfterReturn
+    );
+
+    // The hook is blocked until released, so returning proves it ran elsewhere
+    let err = AsyncError::new(DeferredError("timed out"))
+        .with_severity(Severity::Critical)
+        .with_metadata("region", "eu-west")
+        .with_context("uploading".to_string());
+    assert!(hook.seen.lock().is_none());
+    drop(err);
+
+    release.send(()).unwrap();
+    block_on(drain_hooks());
+    assert_eq!(
+        *hook.seen.lock(),
+        Some((
+            "uploading: timed out".to_string(),
+            Severity::Critical,
+            Some("eu-west".to_string()),
+        ))
+    );
+    assert_eq!(hook.thread.lock().as_deref(), Some("async-err-hooks"));
+}
+
+test_error!(CopiedError);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Category {
+    Storage,
+}
+
+/// Category, sources and display of an error, as seen by a hook.
+type Seen = (Option<Category>, Vec<String>, String);
+
+/// Hook recording what the copy it receives carries.
+#[derive(Default)]
+struct CopyHook {
+    seen: Mutex<Option<Seen>>,
+}
+
+impl AsyncErrorHook<CopiedError> for CopyHook {
+    fn on_error(&self, error: &AsyncError<CopiedError>) {
+        *self.seen.lock() = Some((
+            error.category::<Category>(),
+            error.sources().map(|src| src.to_string()).collect(),
+            error.to_string(),
+        ));
+    }
+}
+
+#[test]
+fn after_return_copy_keeps_category_sources_and_rendered_contexts() {
+    let _lock = LOCK.lock();
+    let hook = Arc::new(CopyHook::default());
+    register_hook::<CopiedError>(hook.clone());
+    set_hook_dispatch_mode::<CopiedError>(HookDispatchMode::AfterReturn);
+
+    let _ = AsyncError::new(CopiedError("primary down"))
+        .with_category(Category::Storage)
+        .with_additional_source("replica down".into())
+        .with_context_template("writing {key}")
+        .with_metadata("key", "users")
+        .with_context("syncing".to_string());
+    block_on(drain_hooks());
+
+    assert_eq!(
+        *hook.seen.lock(),
+        Some((
+            Some(Category::Storage),
+            vec!["primary down".to_string(), "replica down".to_string()],
+            "syncing: writing users: primary down".to_string(),
+        ))
+    );
+}
+
+test_error!(SwitchError);
+
+#[test]
+fn switching_back_to_sync_runs_hooks_inline_again() {
+    let _lock = LOCK.lock();
+    let hook = CountingHook::register::<SwitchError>();
+
+    set_hook_dispatch_mode::<SwitchError>(HookDispatchMode::AfterReturn);
+    let _ = AsyncError::new(SwitchError("first")).with_context("background".to_string());
+    block_on(drain_hooks());
+    assert_eq!(hook.calls(), 1);
+
+    set_hook_dispatch_mode::<SwitchError>(HookDispatchMode::Sync);
+    assert_eq!(hook_dispatch_mode::<SwitchError>(), HookDispatchMode::Sync);
+    let _ = AsyncError::new(SwitchError("second")).with_context("inline".to_string());
+    assert_eq!(hook.calls(), 2);
+    assert_eq!(hook.last().as_deref(), Some("inline: second"));
+}
+
+test_error!(OrderedError);
+
+#[test]
+fn after_return_hooks_see_errors_in_order() {
+    let _lock = LOCK.lock();
+    let hook = Arc::new(OrderHook::default());
+    register_hook::<OrderedError>(hook.clone());
+    set_hook_dispatch_mode::<OrderedError>(HookDispatchMode::AfterReturn);
+
+    for context in ["one", "two", "three"] {
+        let _ = AsyncError::new(OrderedError("failed")).with_context(context.to_string());
+    }
+    block_on(drain_hooks());
+    assert_eq!(*hook.0.lock(), ["one", "two", "three"]);
+}
+
+#[derive(Default)]
+struct OrderHook(Mutex<Vec<String>>);
+
+impl<E: Error + 'static> AsyncErrorHook<E> for OrderHook {
+    fn on_error(&self, error: &AsyncError<E>) {
+        self.0
+            .lock()
+            .push(error.context().unwrap_or_default().to_string());
+    }
+}
+
+test_error!(LoopError);
+
+struct RewrapHook(CountingHook);
+
+impl AsyncErrorHook<LoopError> for RewrapHook {
+    fn on_error(&self, error: &AsyncError<LoopError>) {
+        self.0.on_error(error);
+        let _ = AsyncError::new(LoopError("from hook")).with_context("rewrapped".to_string());
+    }
+}
+
+#[test]
+fn same_type_errors_raised_by_background_hooks_are_skipped() {
+    let _lock = LOCK.lock();
+    let hook = Arc::new(RewrapHook(CountingHook::default()));
+    register_hook::<LoopError>(hook.clone());
+    set_hook_dispatch_mode::<LoopError>(HookDispatchMode::AfterReturn);
+
+    let _ = AsyncError::new(LoopError("original")).with_context("outer".to_string());
+    block_on(drain_hooks());
+    assert_eq!(hook.0.calls(), 1);
+    assert_eq!(hook.0.last().as_deref(), Some("outer: original"));
+}
+
+test_error!(HookPanicError);
+
+struct PanickingHook;
+
+impl AsyncErrorHook<HookPanicError> for PanickingHook {
+    fn on_error(&self, error: &AsyncError<HookPanicError>) {
+        if error.context() == Some("explode") {
+            panic!("hook failed");
+        }
+    }
+}
+
+#[test]
+fn a_panicking_background_hook_does_not_stop_later_dispatch() {
+    let _lock = LOCK.lock();
+    register_hook::<HookPanicError>(Arc::new(PanickingHook));
+    let hook = CountingHook::register::<HookPanicError>();
+    set_hook_dispatch_mode::<HookPanicError>(HookDispatchMode::AfterReturn);
+
+    let _ = AsyncError::new(HookPanicError("first")).with_context("explode".to_string());
+    let _ = AsyncError::new(HookPanicError("second")).with_context("survive".to_string());
+    block_on(drain_hooks());
+    assert_eq!(hook.last().as_deref(), Some("survive: second"));
+}
+
+test_error!(EpochError);
+
+/// Hook announcing that it started, then blocking until released.
+struct BlockingHook {
+    started: Mutex<mpsc::Sender<()>>,
+    release: Mutex<mpsc::Receiver<()>>,
+}
+
+impl<E: Error + 'static> AsyncErrorHook<E> for BlockingHook {
+    fn on_error(&self, _error: &AsyncError<E>) {
+        self.started.lock().send(()).unwrap();
+        self.release.lock().recv().unwrap();
+    }
+}
+
+#[test]
+fn drain_hooks_ignores_invocations_started_after_it() {
+    let _lock = LOCK.lock();
+    let (started, started_rx) = mpsc::channel();
+    let (release, release_rx) = mpsc::channel();
+    register_hook::<EpochError>(Arc::new(BlockingHook {
+        started: Mutex::new(started),
+        release: Mutex::new(release_rx),
+    }));
+    let raise = || {
+        thread::spawn(|| {
+            let _ = AsyncError::new(EpochError("failed")).with_context("raising".to_string());
+        })
+    };
+
+    let before = raise();
+    started_rx.recv().unwrap();
+    let drain = drain_hooks();
+    let after = raise();
+    started_rx.recv().unwrap();
+
+    release.send(()).unwrap();
+    before.join().unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), drain).await })
+        .expect("drain_hooks waited for an invocation started after it");
+
+    release.send(()).unwrap();
+    after.join().unwrap();
+}
+
+test_error!(OverlapError);
+
+#[test]
+fn overlapping_drains_each_wait_only_for_their_own_invocations() {
+    let _lock = LOCK.lock();
+    let (started, started_rx) = mpsc::channel();
+    let (release, release_rx) = mpsc::channel();
+    register_hook::<OverlapError>(Arc::new(BlockingHook {
+        started: Mutex::new(started),
+        release: Mutex::new(release_rx),
+    }));
+    let raise = || {
+        thread::spawn(|| {
+            let _ = AsyncError::new(OverlapError("failed")).with_context("raising".to_string());
+        })
+    };
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+
+    let first = raise();
+    started_rx.recv().unwrap();
+    let first_drain = drain_hooks();
+    let second = raise();
+    started_rx.recv().unwrap();
+    let mut second_drain = Box::pin(drain_hooks());
+
+    release.send(()).unwrap();
+    first.join().unwrap();
+    runtime
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), first_drain).await })
+        .expect("drain_hooks waited for an invocation started after it");
+    runtime
+        .block_on(async {
+            tokio::time::timeout(Duration::from_millis(50), &mut second_drain).await
+        })
+        .expect_err("drain_hooks returned while an earlier invocation was running");
+
+    release.send(()).unwrap();
+    second.join().unwrap();
+    runtime
+        .block_on(async { tokio::time::timeout(Duration::from_secs(5), second_drain).await })
+        .expect("drain_hooks did not resolve once its invocations finished");
+}
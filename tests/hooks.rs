@@ -0,0 +1,416 @@
+#![cfg(feature = "hooks")]
+#[macro_use]
+mod common;
+
+use async_err::{register_hook, AsyncError, AsyncErrorHook};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+test_error!(ReentrantError);
+test_error!(OtherError);
+
+/// Wraps a new error of the same type from inside the hook.
+struct ReentrantHook {
+    calls: AtomicUsize,
+}
+
+impl AsyncErrorHook<ReentrantError> for ReentrantHook {
+    fn on_error(&self, _error: &AsyncError<ReentrantError>) {
+        if self.calls.fetch_add(1, Ordering::SeqCst) > 100 {
+            panic!("unbounded hook recursion");
+        }
+        let _ = AsyncError::new(ReentrantError("nested")).with_context("in hook".to_string());
+        let _ = AsyncError::new(OtherError("other")).with_context("in hook".to_string());
+    }
+}
+
+#[test]
+fn same_type_reentrancy_is_bounded_but_other_types_still_fire() {
+    let hook = Arc::new(ReentrantHook {
+        calls: AtomicUsize::new(0),
+    });
+    register_hook::<ReentrantError>(hook.clone());
+    let other = common::CountingHook::register::<OtherError>();
+
+    let _ = AsyncError::new(ReentrantError("outer")).with_context("top".to_string());
+
+    assert_eq!(hook.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(other.calls(), 1);
+
+    // The guard is released afterwards, so the next error fires the hook again
+    let _ = AsyncError::new(ReentrantError("again")).with_context("top".to_string());
+    assert_eq!(hook.calls.load(Ordering::SeqCst), 2);
+}
+
+test_error!(SeverityError);
+
+#[test]
+fn default_hook_message_is_prefixed_with_severity() {
+    use async_err::{hooks::format_hook_message, Severity};
+
+    for (severity, prefix) in [
+        (Severity::Warning, "[WARN] "),
+        (Severity::Error, "[ERROR] "),
+        (Severity::Critical, "[CRITICAL] "),
+    ] {
+        let err = AsyncError::new(SeverityError("boom")).with_severity(severity);
+        let message = format_hook_message(&err);
+        assert!(message.starts_with(prefix), "{}", message);
+    }
+}
+
+test_error!(MacroErrorA);
+test_error!(MacroErrorB);
+
+#[test]
+fn register_hooks_macro_registers_for_each_type() {
+    let hook = Arc::new(common::CountingHook::default());
+    async_err::register_hooks!(hook => [MacroErrorA, MacroErrorB]);
+
+    let _ = AsyncError::new(MacroErrorA("a")).with_context("first".to_string());
+    assert_eq!(hook.calls(), 1);
+    assert_eq!(hook.last().as_deref(), Some("first: a"));
+
+    let _ = AsyncError::new(MacroErrorB("b")).with_context("second".to_string());
+    assert_eq!(hook.calls(), 2);
+    assert_eq!(hook.last().as_deref(), Some("second: b"));
+}
+
+test_error!(IntrospectError);
+
+#[test]
+fn is_hook_registered_tracks_the_instance() {
+    use async_err::hooks::is_hook_registered;
+
+    let hook: Arc<dyn AsyncErrorHook<IntrospectError>> = Arc::new(common::CountingHook::default());
+    let other: Arc<dyn AsyncErrorHook<IntrospectError>> = Arc::new(common::CountingHook::default());
+    assert!(!is_hook_registered(&hook));
+
+    register_hook(hook.clone());
+
+    assert!(is_hook_registered(&hook));
+    assert!(!is_hook_registered(&other));
+}
+
+test_error!(FilterError);
+
+/// A second concrete hook type, distinct from `CountingHook`.
+struct SilentHook;
+
+impl AsyncErrorHook<FilterError> for SilentHook {
+    fn on_error(&self, _error: &AsyncError<FilterError>) {}
+}
+
+#[test]
+fn get_hooks_of_type_returns_only_matching_hooks() {
+    use async_err::hooks::get_hooks_of_type;
+
+    let counting = common::CountingHook::register::<FilterError>();
+    register_hook::<FilterError>(Arc::new(SilentHook));
+
+    let found = get_hooks_of_type::<FilterError, common::CountingHook>();
+    assert_eq!(found.len(), 1);
+    assert!(Arc::ptr_eq(&found[0], &counting));
+    assert_eq!(get_hooks_of_type::<FilterError, SilentHook>().len(), 1);
+}
+
+/// Shared trait implemented by every error of the domain.
+trait DomainError: std::error::Error + Send + Sync {}
+
+#[derive(Debug)]
+enum StorageError {
+    Full,
+}
+
+#[derive(Debug)]
+enum AuthError {
+    Expired,
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("storage full")
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("token expired")
+    }
+}
+
+impl std::error::Error for StorageError {}
+impl std::error::Error for AuthError {}
+impl DomainError for StorageError {}
+impl DomainError for AuthError {}
+
+#[test]
+fn one_domain_hook_sees_every_domain_error() {
+    use async_err::error::{domain_error, DomainErrorBox};
+
+    let hook = common::CountingHook::register::<DomainErrorBox<dyn DomainError>>();
+
+    let storage = domain_error::<dyn DomainError>(Box::new(StorageError::Full))
+        .with_context("saving upload".to_string());
+    assert_eq!(hook.last().as_deref(), Some("saving upload: storage full"));
+
+    let auth = domain_error::<dyn DomainError>(Box::new(AuthError::Expired))
+        .with_context("refreshing session".to_string());
+    assert_eq!(
+        hook.last().as_deref(),
+        Some("refreshing session: token expired")
+    );
+
+    assert_eq!(hook.calls(), 2);
+    assert_eq!(storage.inner_error().get_ref().to_string(), "storage full");
+    assert_eq!(auth.inner_error().get_ref().to_string(), "token expired");
+}
+
+test_error!(DrainError);
+
+/// Sleeps inside `on_error`, counting invocations started and finished.
+#[derive(Default)]
+struct SlowHook {
+    started: AtomicUsize,
+    finished: AtomicUsize,
+}
+
+impl AsyncErrorHook<DrainError> for SlowHook {
+    fn on_error(&self, _error: &AsyncError<DrainError>) {
+        self.started.fetch_add(1, Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        self.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn drain_hooks_waits_for_hooks_on_other_threads() {
+    let hook = Arc::new(SlowHook::default());
+    register_hook::<DrainError>(hook.clone());
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(|| {
+                let _ = AsyncError::new(DrainError("lost")).with_context("shutdown".to_string());
+            })
+        })
+        .collect();
+    while hook.started.load(Ordering::SeqCst) < 4 {
+        std::thread::yield_now();
+    }
+
+    async_err::hooks::drain_hooks().await;
+
+    assert_eq!(hook.finished.load(Ordering::SeqCst), 4);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[tokio::test]
+async fn drain_hooks_resolves_when_idle() {
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        async_err::hooks::drain_hooks(),
+    )
+    .await
+    .expect("drain_hooks should not block without hooks in flight");
+}
+
+test_error!(BoxedA);
+test_error!(BoxedB);
+
+/// Records every type-erased error message.
+#[derive(Default)]
+struct DynHook(parking_lot::Mutex<Vec<String>>);
+
+impl AsyncErrorHook<async_err::BoxedError> for DynHook {
+    fn on_error(&self, error: &async_err::DynAsyncError) {
+        self.0.lock().push(error.to_string());
+    }
+}
+
+#[test]
+fn dyn_hook_fires_for_every_boxed_error() {
+    use async_err::register_dyn_hook;
+
+    let hook = Arc::new(DynHook::default());
+    register_dyn_hook(hook.clone());
+
+    let _ = AsyncError::new(BoxedA("first"))
+        .into_dyn()
+        .with_context("a".to_string());
+    let _ = AsyncError::new(BoxedB("second"))
+        .into_dyn()
+        .with_context("b".to_string());
+
+    let seen = hook.0.lock().clone();
+    assert!(seen.contains(&"a: first".to_string()), "{:?}", seen);
+    assert!(seen.contains(&"b: second".to_string()), "{:?}", seen);
+}
+
+#[cfg(feature = "hook-metrics")]
+mod metrics {
+    use async_err::{hooks::hook_timings, register_hook, AsyncError, AsyncErrorHook};
+    use std::{any::TypeId, sync::Arc, time::Duration};
+
+    test_error!(TimedHookError);
+
+    struct SleepyHook;
+
+    impl AsyncErrorHook<TimedHookError> for SleepyHook {
+        fn on_error(&self, _error: &AsyncError<TimedHookError>) {
+            std::thread::sleep(Duration::from_millis(15));
+        }
+    }
+
+    fn recorded() -> Option<Duration> {
+        hook_timings()
+            .into_iter()
+            .find(|(hook_type, _)| *hook_type == TypeId::of::<SleepyHook>())
+            .map(|(_, total)| total)
+    }
+
+    #[test]
+    fn slow_hook_time_is_recorded() {
+        assert_eq!(recorded(), None);
+        register_hook::<TimedHookError>(Arc::new(SleepyHook));
+
+        for _ in 0..2 {
+            let _ = AsyncError::new(TimedHookError("slow")).with_context("timing".to_string());
+        }
+
+        assert!(recorded().unwrap() >= Duration::from_millis(30));
+    }
+}
+
+test_error!(BatchError);
+
+/// Records its index each time it fires.
+struct OrderHook(usize, Arc<parking_lot::Mutex<Vec<usize>>>);
+
+impl AsyncErrorHook<BatchError> for OrderHook {
+    fn on_error(&self, _error: &AsyncError<BatchError>) {
+        self.1.lock().push(self.0);
+    }
+}
+
+#[test]
+fn register_hooks_batch_keeps_count_and_order() {
+    use async_err::hooks::{get_hooks, invoke_hooks, register_hooks_batch};
+
+    let fired = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let hooks: Vec<Arc<dyn AsyncErrorHook<BatchError>>> = (0..5)
+        .map(|i| Arc::new(OrderHook(i, fired.clone())) as _)
+        .collect();
+    // The repeated instance is skipped, like with `register_hook`
+    let mut batch = hooks.clone();
+    batch.push(hooks[2].clone());
+    register_hooks_batch(&batch);
+
+    let registered = get_hooks::<BatchError>();
+    assert_eq!(registered.len(), 5);
+    assert!(registered
+        .iter()
+        .zip(&hooks)
+        .all(|(registered, hook)| Arc::ptr_eq(registered, hook)));
+
+    invoke_hooks(&AsyncError::new(BatchError("startup")));
+    assert_eq!(*fired.lock(), [0, 1, 2, 3, 4]);
+}
+
+/// Distinct error type per `N`, for exercising many registries at once.
+#[derive(Debug)]
+struct Tagged<const N: usize>;
+
+impl<const N: usize> std::fmt::Display for Tagged<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tagged {}", N)
+    }
+}
+
+impl<const N: usize> std::error::Error for Tagged<N> {}
+
+const STRESS_ITERATIONS: usize = 2_000;
+
+/// Registers a counting hook for `Tagged<N>` once all threads are ready, then wraps errors.
+fn stress<const N: usize>(
+    barrier: Arc<std::sync::Barrier>,
+) -> std::thread::JoinHandle<Arc<common::CountingHook>> {
+    std::thread::spawn(move || {
+        barrier.wait();
+        let hook = common::CountingHook::register::<Tagged<N>>();
+        for _ in 0..STRESS_ITERATIONS {
+            let _ = AsyncError::new(Tagged::<N>).with_context("stress".to_string());
+        }
+        hook
+    })
+}
+
+#[test]
+fn concurrent_errors_of_many_types_lose_no_invocations() {
+    let barrier = Arc::new(std::sync::Barrier::new(16));
+    let handles = [
+        stress::<0>(barrier.clone()),
+        stress::<1>(barrier.clone()),
+        stress::<2>(barrier.clone()),
+        stress::<3>(barrier.clone()),
+        stress::<4>(barrier.clone()),
+        stress::<5>(barrier.clone()),
+        stress::<6>(barrier.clone()),
+        stress::<7>(barrier.clone()),
+        stress::<8>(barrier.clone()),
+        stress::<9>(barrier.clone()),
+        stress::<10>(barrier.clone()),
+        stress::<11>(barrier.clone()),
+        stress::<12>(barrier.clone()),
+        stress::<13>(barrier.clone()),
+        stress::<14>(barrier.clone()),
+        stress::<15>(barrier),
+    ];
+
+    for handle in handles {
+        let hook = handle.join().unwrap();
+        assert_eq!(hook.calls(), STRESS_ITERATIONS);
+        assert!(hook.last().unwrap().starts_with("stress: tagged "));
+    }
+}
+
+test_error!(DeferredError);
+
+#[tokio::test]
+async fn deferred_hooks_fire_once_when_asked() {
+    use async_err::AsyncResultExt;
+
+    let hook = common::CountingHook::register::<DeferredError>();
+    let deferred = async { Err::<(), _>(DeferredError("timeout")) }
+        .with_context_deferred(|_| "syncing".to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(hook.calls(), 0);
+    assert_eq!(deferred.to_string(), "syncing: timeout");
+
+    deferred.fire();
+    deferred.fire();
+    assert_eq!(hook.calls(), 1);
+    assert_eq!(hook.last().as_deref(), Some("syncing: timeout"));
+}
+
+test_error!(UnfiredError);
+
+#[tokio::test]
+async fn deferred_hooks_do_not_fire_when_unwrapped() {
+    use async_err::AsyncResultExt;
+
+    let hook = common::CountingHook::register::<UnfiredError>();
+    let err = async { Err::<(), _>(UnfiredError("timeout")) }
+        .with_context_deferred(|_| "syncing".to_string())
+        .await
+        .unwrap_err()
+        .into_error();
+
+    assert_eq!(err.context(), Some("syncing"));
+    assert_eq!(hook.calls(), 0);
+}
@@ -0,0 +1,45 @@
+//! Switches hook invocation off globally, so it runs as its own test binary.
+#![cfg(feature = "hooks")]
+#[macro_use]
+mod common;
+
+use async_err::hooks::{hooks_enabled, invoke_hooks, set_hooks_enabled};
+use async_err::AsyncError;
+use common::CountingHook;
+use parking_lot::Mutex;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+test_error!(ToggleError);
+test_error!(DirectError);
+
+#[test]
+fn disabled_hooks_do_not_fire_until_re_enabled() {
+    let _lock = LOCK.lock();
+    let hook = CountingHook::register::<ToggleError>();
+
+    set_hooks_enabled(false);
+    assert!(!hooks_enabled());
+    let _ = AsyncError::new(ToggleError("quiet")).with_context("benchmark".to_string());
+    assert_eq!(hook.calls(), 0);
+
+    set_hooks_enabled(true);
+    assert!(hooks_enabled());
+    let _ = AsyncError::new(ToggleError("loud")).with_context("production".to_string());
+    assert_eq!(hook.calls(), 1);
+}
+
+#[test]
+fn disabling_also_skips_direct_invocation() {
+    let _lock = LOCK.lock();
+    let hook = CountingHook::register::<DirectError>();
+    let err = AsyncError::new(DirectError("direct"));
+
+    set_hooks_enabled(false);
+    invoke_hooks(&err);
+    set_hooks_enabled(true);
+    assert_eq!(hook.calls(), 0);
+
+    invoke_hooks(&err);
+    assert_eq!(hook.calls(), 1);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "regex")]
+#[macro_use]
+mod common;
+
+use async_err::{redact::set_redaction, AsyncError};
+use parking_lot::Mutex;
+use regex::Regex;
+
+/// Serializes the tests, which all change the global redaction patterns.
+static LOCK: Mutex<()> = Mutex::new(());
+
+test_error!(LoginError);
+
+fn email() -> Regex {
+    Regex::new(r"[\w.+-]+@[\w-]+\.[\w.]+").unwrap()
+}
+
+#[cfg(feature = "hooks")]
+#[test]
+fn hook_output_redacts_context_and_message() {
+    let _lock = LOCK.lock();
+    set_redaction(vec![email()]);
+
+    let err = AsyncError::new(LoginError("no account for bob@example.com"))
+        .append_context("login as alice@example.org".to_string());
+    let message = async_err::hooks::format_hook_message(&err);
+    set_redaction(Vec::new());
+
+    assert!(message.contains("login as ***"), "{}", message);
+    assert!(message.contains("no account for ***"), "{}", message);
+    assert!(!message.contains('@'), "{}", message);
+}
+
+#[test]
+fn redacted_display_masks_while_display_does_not() {
+    let _lock = LOCK.lock();
+    set_redaction(vec![email()]);
+
+    let err = AsyncError::new(LoginError("bad password"))
+        .append_context("login as alice@example.org".to_string());
+    let redacted = err.redacted_display();
+    set_redaction(Vec::new());
+
+    assert_eq!(redacted, "login as ***: bad password");
+    assert_eq!(err.to_string(), "login as alice@example.org: bad password");
+}
+
+#[test]
+fn empty_patterns_disable_redaction() {
+    let _lock = LOCK.lock();
+    set_redaction(Vec::new());
+
+    let err = AsyncError::new(LoginError("bob@example.com"));
+    assert_eq!(err.redacted_display(), "bob@example.com");
+}
+
+#[test]
+fn redacted_display_follows_debug_rendering() {
+    let _lock = LOCK.lock();
+    set_redaction(vec![email()]);
+
+    let err = AsyncError::new(LoginError("no account for bob@example.com"))
+        .append_context("login as alice@example.org".to_string())
+        .with_debug_rendering();
+    let redacted = err.redacted_display();
+    #[cfg(feature = "hooks")]
+    let message = async_err::hooks::format_hook_message(&err);
+    set_redaction(Vec::new());
+
+    assert_eq!(
+        redacted,
+        r#"login as ***: LoginError("no account for ***")"#
+    );
+    #[cfg(feature = "hooks")]
+    assert!(
+        message.contains(r#"Inner error: LoginError("no account for ***")"#),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "sink")]
+#[macro_use]
+mod common;
+
+use async_err::sink_ext::AsyncSinkExt;
+use futures::{Sink, SinkExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+test_error!(FlushError);
+
+/// Accepts items but fails every flush.
+#[derive(Default)]
+struct FailingFlush {
+    buffered: Vec<u32>,
+}
+
+impl Sink<u32> for FailingFlush {
+    type Error = FlushError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), FlushError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: u32) -> Result<(), FlushError> {
+        self.buffered.push(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), FlushError>> {
+        Poll::Ready(Err(FlushError("broken pipe")))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), FlushError>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[tokio::test]
+async fn flush_error_is_wrapped_with_context() {
+    let mut sink =
+        FailingFlush::default().sink_with_context(|err| format!("writing batch ({})", err));
+
+    sink.feed(1).await.unwrap();
+    let err = sink.flush().await.unwrap_err();
+
+    assert_eq!(err.inner_error(), &FlushError("broken pipe"));
+    assert_eq!(err.context(), Some("writing batch (broken pipe)"));
+}
+
+#[tokio::test]
+async fn close_error_is_wrapped_with_context() {
+    let mut sink = FailingFlush::default().sink_with_context(|_| "closing".to_string());
+
+    let err = sink.close().await.unwrap_err();
+    assert_eq!(err.to_string(), "closing: broken pipe");
+}
@@ -0,0 +1,81 @@
+#[macro_use]
+mod common;
+
+use async_err::AsyncTryStreamExt;
+use futures::stream::{self, StreamExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+test_error!(RowError);
+
+#[tokio::test]
+async fn try_collect_with_context_collects_all_items() {
+    let rows: Vec<u32> = stream::iter([Ok::<_, RowError>(1), Ok(2), Ok(3)])
+        .try_collect_with_context(|_| unreachable!())
+        .await
+        .unwrap();
+    assert_eq!(rows, [1, 2, 3]);
+}
+
+#[tokio::test]
+async fn try_collect_with_context_stops_at_the_first_error() {
+    let pulled = AtomicUsize::new(0);
+    let rows = stream::iter([Ok(1), Ok(2), Err(RowError("reset")), Ok(4)])
+        .inspect(|_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+        })
+        .try_collect_with_context::<_, Vec<u32>>(|err| format!("collecting rows ({})", err));
+
+    let err = rows.await.unwrap_err();
+    assert_eq!(err.inner_error(), &RowError("reset"));
+    assert_eq!(err.context(), Some("collecting rows (reset)"));
+    assert_eq!(pulled.load(Ordering::SeqCst), 3);
+}
+
+test_error!(LineError);
+
+#[cfg(not(async_err_unstable))]
+#[tokio::test]
+async fn with_context_each_wraps_every_error_item() {
+    use async_err::AsyncIterResultExt;
+
+    // Produces lines lazily, failing on every odd line
+    let lines = stream::unfold(0u32, |line| async move {
+        (line < 4).then(|| {
+            let item = match line % 2 {
+                0 => Ok(line),
+                _ => Err(LineError("malformed")),
+            };
+            (item, line + 1)
+        })
+    });
+
+    let mut seen = 0;
+    let results: Vec<Result<u32, String>> = lines
+        .with_context_each(|_| {
+            seen += 1;
+            format!("parse error #{}", seen)
+        })
+        .map(|item| item.map_err(|err| err.to_string()))
+        .collect()
+        .await;
+
+    assert_eq!(
+        results,
+        [
+            Ok(0),
+            Err("parse error #1: malformed".to_string()),
+            Ok(2),
+            Err("parse error #2: malformed".to_string()),
+        ]
+    );
+}
+
+#[cfg(not(async_err_unstable))]
+#[test]
+fn with_context_each_keeps_the_size_hint() {
+    use async_err::AsyncIterResultExt;
+
+    let items = stream::iter([Ok::<_, LineError>(1), Err(LineError("bad"))])
+        .with_context_each(|_| "row".to_string());
+    assert_eq!(futures::Stream::size_hint(&items), (2, Some(2)));
+}
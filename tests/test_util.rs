@@ -0,0 +1,95 @@
+#![cfg(feature = "test-util")]
+#[macro_use]
+mod common;
+
+use async_err::error::{max_context_depth, set_dedup_adjacent_contexts};
+use async_err::hooks::{
+    clear_hook_warning_sink, get_hooks, hooks_enabled, set_hook_warning_sink, set_hooks_enabled,
+};
+use async_err::test_util::{record_hook_type_name, HookTestGuard};
+use async_err::{
+    error_counts, set_app_version, set_count_errors, set_max_context_depth, AsyncError,
+};
+use parking_lot::Mutex;
+use std::any::TypeId;
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn capture_warning(message: &str) {
+    WARNINGS.lock().push(message.to_string());
+}
+
+#[test]
+fn type_name_collision_warns_once() {
+    let _guard = HookTestGuard::new();
+    WARNINGS.lock().clear();
+    set_hook_warning_sink(capture_warning);
+
+    assert!(!record_hook_type_name(TypeId::of::<u8>(), "dup::Error"));
+    assert!(!record_hook_type_name(TypeId::of::<u8>(), "dup::Error"));
+    assert!(record_hook_type_name(TypeId::of::<u16>(), "dup::Error"));
+    assert!(!record_hook_type_name(TypeId::of::<u32>(), "dup::Error"));
+
+    let warnings = WARNINGS.lock();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("`dup::Error`"));
+}
+
+/// Sink that uninstalls itself after the first warning.
+fn capture_once(message: &str) {
+    clear_hook_warning_sink();
+    capture_warning(message);
+}
+
+#[test]
+fn warning_sink_can_replace_itself() {
+    let _guard = HookTestGuard::new();
+    WARNINGS.lock().clear();
+    set_hook_warning_sink(capture_once);
+
+    assert!(!record_hook_type_name(TypeId::of::<u8>(), "once::Error"));
+    assert!(record_hook_type_name(TypeId::of::<u16>(), "once::Error"));
+    assert_eq!(WARNINGS.lock().len(), 1);
+}
+
+test_error!(SharedError);
+
+/// Checks no state leaked in from the other guarded test, then leaves some behind.
+fn register_and_check_isolated() {
+    let _guard = HookTestGuard::new();
+    assert!(get_hooks::<SharedError>().is_empty());
+    assert!(hooks_enabled());
+    assert_eq!(max_context_depth(), usize::MAX);
+    assert!(error_counts().is_empty());
+    let err = AsyncError::new(SharedError("x"))
+        .with_context("same".to_string())
+        .append_context("same".to_string());
+    assert_eq!(err.version(), None);
+    assert_eq!(err.contexts(), ["same", "same"]);
+    assert!(error_counts().is_empty(), "counting starts disabled");
+    #[cfg(feature = "regex")]
+    assert_eq!(err.redacted_display(), "same: same: x");
+
+    let hook = common::CountingHook::register::<SharedError>();
+    set_hooks_enabled(false);
+    set_max_context_depth(3);
+    set_app_version("9.9.9");
+    set_dedup_adjacent_contexts(true);
+    set_count_errors(true);
+    let _ = AsyncError::new(SharedError("x")).with_context("counted".to_string());
+    assert_eq!(error_counts().len(), 1);
+    #[cfg(feature = "regex")]
+    async_err::redact::set_redaction(vec![regex::Regex::new("x").unwrap()]);
+    assert_eq!(get_hooks::<SharedError>().len(), 1);
+    drop(hook);
+}
+
+#[test]
+fn guarded_test_one_sees_no_hooks_from_others() {
+    register_and_check_isolated();
+}
+
+#[test]
+fn guarded_test_two_sees_no_hooks_from_others() {
+    register_and_check_isolated();
+}
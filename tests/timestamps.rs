@@ -0,0 +1,61 @@
+//! Changes the global timestamp settings, so it runs as its own test binary.
+#![cfg(feature = "hooks")]
+#[macro_use]
+mod common;
+
+use async_err::hooks::{
+    clear_timestamp_severity_threshold, disable_hook_timestamps, enable_hook_timestamps,
+    format_hook_message, set_timestamp_severity_threshold,
+};
+use async_err::{AsyncError, Severity};
+use parking_lot::Mutex;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+test_error!(StampError);
+
+fn is_timestamped(severity: Severity) -> bool {
+    let err = AsyncError::new(StampError("disk")).with_severity(severity);
+    let message = format_hook_message(&err);
+    let untimed = format!("[{}] AsyncError Hook Triggered", severity.label());
+    assert!(message.contains("AsyncError Hook Triggered"), "{}", message);
+    !message.starts_with(&untimed)
+}
+
+#[test]
+fn threshold_limits_timestamps_to_severe_errors() {
+    let _lock = LOCK.lock();
+    enable_hook_timestamps();
+    set_timestamp_severity_threshold(Severity::Critical);
+
+    let stamped = [Severity::Warning, Severity::Error, Severity::Critical].map(is_timestamped);
+
+    clear_timestamp_severity_threshold();
+    disable_hook_timestamps();
+    assert_eq!(stamped, [false, false, true]);
+}
+
+#[test]
+fn clearing_the_threshold_timestamps_every_error() {
+    let _lock = LOCK.lock();
+    enable_hook_timestamps();
+    set_timestamp_severity_threshold(Severity::Error);
+    clear_timestamp_severity_threshold();
+
+    let stamped = [Severity::Warning, Severity::Critical].map(is_timestamped);
+
+    disable_hook_timestamps();
+    assert_eq!(stamped, [true, true]);
+}
+
+#[test]
+fn threshold_has_no_effect_while_timestamps_are_disabled() {
+    let _lock = LOCK.lock();
+    disable_hook_timestamps();
+    set_timestamp_severity_threshold(Severity::Warning);
+
+    let stamped = is_timestamped(Severity::Critical);
+
+    clear_timestamp_severity_threshold();
+    assert!(!stamped);
+}
@@ -0,0 +1,244 @@
+#![cfg(feature = "tokio")]
+#[macro_use]
+mod common;
+
+use async_err::tokio_ext::AsyncJoinExt;
+use std::time::Duration;
+
+test_error!(TaskError);
+
+#[tokio::test]
+async fn erroring_task_keeps_its_error() {
+    let handle = tokio::spawn(async { Err::<(), _>(TaskError("disk full")) });
+    let err = handle
+        .join_with_context(|_| "worker".to_string())
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.contexts(), ["worker"]);
+    assert!(err
+        .inner_error()
+        .get_ref()
+        .downcast_ref::<TaskError>()
+        .is_some());
+}
+
+#[tokio::test]
+async fn panicking_task_is_reported_as_panicked() {
+    let handle = tokio::spawn(async {
+        if true {
+            panic!("bad state");
+        }
+        Ok::<(), TaskError>(())
+    });
+    let err = handle
+        .join_with_context(|_| "worker".to_string())
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.contexts(), ["task panicked", "worker"]);
+    let rendered = err.to_string();
+    assert!(rendered.starts_with("worker: task panicked: "));
+    assert!(rendered.contains("bad state"));
+}
+
+#[tokio::test]
+async fn cancelled_task_is_reported_as_cancelled() {
+    let handle = tokio::spawn(async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        Ok::<(), TaskError>(())
+    });
+    handle.abort();
+    let err = handle
+        .join_with_context(|_| "worker".to_string())
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.contexts(), ["task was cancelled", "worker"]);
+}
+
+#[tokio::test]
+async fn successful_task_returns_its_value() {
+    let handle = tokio::spawn(async { Ok::<_, TaskError>(5) });
+    let value = handle.join_with_context(|_| unreachable!()).await.unwrap();
+    assert_eq!(value, 5);
+}
+
+test_error!(TaskNameError);
+
+#[tokio::test]
+async fn with_task_name_records_the_task_id() {
+    let (id, err) = tokio::spawn(async {
+        let err = async_err::AsyncError::new(TaskNameError("stalled")).with_task_name();
+        (tokio::task::id(), err)
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(err.meta("task_id"), Some(id.to_string().as_str()));
+}
+
+#[test]
+fn with_task_name_is_a_no_op_outside_a_task() {
+    let err = async_err::AsyncError::new(TaskNameError("stalled")).with_task_name();
+    assert_eq!(err.meta("task_id"), None);
+}
+
+test_error!(BlockingError);
+
+#[tokio::test]
+async fn blocking_with_context_returns_the_value() {
+    let value = async_err::tokio_ext::blocking_with_context(
+        || Ok::<_, BlockingError>((1..=4).product::<u32>()),
+        |_| unreachable!(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value, 24);
+}
+
+#[tokio::test]
+async fn blocking_with_context_wraps_the_error() {
+    let err = async_err::tokio_ext::blocking_with_context(
+        || Err::<(), _>(BlockingError("checksum mismatch")),
+        |err| format!("hashing file ({})", err),
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "hashing file (checksum mismatch): checksum mismatch"
+    );
+    assert!(err
+        .inner_error()
+        .get_ref()
+        .downcast_ref::<BlockingError>()
+        .is_some());
+}
+
+#[tokio::test]
+async fn blocking_with_context_reports_a_panic() {
+    let err = async_err::tokio_ext::blocking_with_context(
+        || -> Result<(), BlockingError> { panic!("index out of bounds") },
+        |_| "hashing file".to_string(),
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(err.contexts(), ["task panicked", "hashing file"]);
+}
+
+#[cfg(feature = "operations")]
+mod operations {
+    use async_err::{tokio_ext::with_operation, AsyncError};
+
+    test_error!(OperationError);
+
+    #[tokio::test]
+    async fn errors_inherit_nested_operations() {
+        let err = with_operation("loading config", async {
+            with_operation("reading file", async {
+                AsyncError::new(OperationError("permission denied"))
+                    .append_context("opening".to_string())
+            })
+            .await
+        })
+        .await;
+
+        assert_eq!(err.operations(), ["loading config", "reading file"]);
+        assert_eq!(
+            err.to_string(),
+            "loading config: reading file: opening: permission denied"
+        );
+    }
+
+    #[tokio::test]
+    async fn as_kv_lists_the_operations() {
+        let err = with_operation("loading config", async {
+            with_operation("reading file", async {
+                AsyncError::new(OperationError("denied"))
+            })
+            .await
+        })
+        .await;
+
+        assert_eq!(
+            err.as_kv(),
+            [
+                ("error", "denied".to_string()),
+                ("operations", "loading config: reading file".to_string()),
+                ("severity", "ERROR".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(any(feature = "hooks", feature = "regex", feature = "valuable"))]
+    async fn nested_error() -> AsyncError<OperationError> {
+        with_operation("user 1234", async {
+            with_operation("loading profile", async {
+                AsyncError::new(OperationError("denied")).append_context("opening".to_string())
+            })
+            .await
+        })
+        .await
+    }
+
+    #[cfg(feature = "hooks")]
+    #[tokio::test]
+    async fn hook_messages_list_the_operations() {
+        let err = nested_error().await;
+        assert_eq!(
+            async_err::hooks::format_hook_message(&err),
+            "[ERROR] AsyncError Hook Triggered\n  Operations: user 1234: loading profile\n  \
+             Context: opening\n  Inner error: denied\n------------------------------"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[tokio::test]
+    async fn redacted_display_redacts_the_operations() {
+        let err = nested_error().await;
+        async_err::redact::set_redaction(vec![regex::Regex::new(r"\d{4}").unwrap()]);
+        let redacted = err.redacted_display();
+        async_err::redact::set_redaction(Vec::new());
+        assert_eq!(redacted, "user ***: loading profile: opening: denied");
+    }
+
+    #[cfg(feature = "valuable")]
+    #[tokio::test]
+    async fn valuable_fields_list_the_operations() {
+        use valuable::{NamedValues, Valuable, Value, Visit};
+
+        /// Collects the items of the `operations` field.
+        #[derive(Default)]
+        struct OperationsVisitor(Vec<String>);
+
+        impl Visit for OperationsVisitor {
+            fn visit_named_fields(&mut self, fields: &NamedValues<'_>) {
+                if let Some(Value::Listable(operations)) = fields.get_by_name("operations") {
+                    operations.visit(self);
+                }
+            }
+
+            fn visit_value(&mut self, value: Value<'_>) {
+                if let Value::String(operation) = value {
+                    self.0.push(operation.to_string());
+                }
+            }
+        }
+
+        let err = nested_error().await;
+        let mut visitor = OperationsVisitor::default();
+        err.visit(&mut visitor);
+        assert_eq!(visitor.0, ["user 1234", "loading profile"]);
+    }
+
+    #[tokio::test]
+    async fn operations_end_with_their_scope() {
+        with_operation("first", async {}).await;
+        let err = AsyncError::new(OperationError("late"));
+        assert!(err.operations().is_empty());
+        assert_eq!(err.to_string(), "late");
+    }
+}
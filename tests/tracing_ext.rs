@@ -0,0 +1,137 @@
+#![cfg(feature = "tracing")]
+#[macro_use]
+mod common;
+
+use async_err::{AsyncError, Severity};
+use common::recording::RecordingSubscriber;
+use tracing::Level;
+
+#[test]
+fn severity_maps_to_tracing_level() {
+    assert_eq!(Severity::Warning.level(), Level::WARN);
+    assert_eq!(Severity::Error.level(), Level::ERROR);
+    assert_eq!(Severity::Critical.level(), Level::ERROR);
+}
+
+test_error!(RecordError);
+
+#[test]
+fn record_into_populates_span_fields() {
+    let subscriber = RecordingSubscriber::default();
+
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = tracing::info_span!(
+            "load",
+            error.message = tracing::field::Empty,
+            error.context = tracing::field::Empty,
+            error.code = tracing::field::Empty,
+            error.severity = tracing::field::Empty,
+        );
+        AsyncError::new(RecordError("timed out"))
+            .append_context("fetching profile".to_string())
+            .with_code("E1042")
+            .with_severity(Severity::Warning)
+            .record_into(&span);
+    });
+
+    let span = subscriber.span("load").expect("span recorded");
+    assert_eq!(span.field("error.message"), Some("timed out"));
+    assert_eq!(span.field("error.context"), Some("fetching profile"));
+    assert_eq!(span.field("error.code"), Some("E1042"));
+    assert_eq!(span.field("error.severity"), Some("WARN"));
+}
+
+#[test]
+fn record_into_renders_context_templates() {
+    let subscriber = RecordingSubscriber::default();
+
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = tracing::info_span!("fetch", error.context = tracing::field::Empty);
+        AsyncError::new(RecordError("timed out"))
+            .with_context_template("fetching user {user_id}")
+            .with_metadata("user_id", "42")
+            .record_into(&span);
+    });
+
+    let span = subscriber.span("fetch").expect("span recorded");
+    assert_eq!(span.field("error.context"), Some("fetching user 42"));
+}
+
+#[cfg(feature = "hooks")]
+mod hook {
+    use super::common::recording::RecordingSubscriber;
+    use async_err::{register_hook, AsyncError, Severity, TracingHook};
+    use std::sync::Arc;
+    use tracing::Level;
+
+    test_error!(LevelError);
+
+    #[test]
+    fn tracing_hook_emits_at_severity_level() {
+        register_hook::<LevelError>(Arc::new(TracingHook));
+        let subscriber = RecordingSubscriber::default();
+
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            for severity in [Severity::Warning, Severity::Error, Severity::Critical] {
+                let _ = AsyncError::new(LevelError("slow disk"))
+                    .with_code("E0007")
+                    .with_severity(severity)
+                    .with_context("saving".to_string());
+            }
+        });
+
+        let events = subscriber.events();
+        let levels: Vec<Level> = events.iter().map(|event| event.level).collect();
+        assert_eq!(levels, [Level::WARN, Level::ERROR, Level::ERROR]);
+        assert_eq!(events[0].field("error.message"), Some("slow disk"));
+        assert_eq!(events[0].field("error.context"), Some("saving"));
+        assert_eq!(events[0].field("error.code"), Some("E0007"));
+        assert_eq!(events[2].field("error.severity"), Some("CRITICAL"));
+    }
+}
+
+test_error!(SpanError);
+
+#[test]
+fn with_context_span_records_the_error_on_failure() {
+    use async_err::AsyncResultExt;
+
+    let subscriber = RecordingSubscriber::default();
+    let err = tracing::subscriber::with_default(subscriber.clone(), || {
+        futures::executor::block_on(
+            async { Err::<(), _>(SpanError("refused")) }
+                .with_context_span("fetch_user", |_| "fetching user".to_string()),
+        )
+    })
+    .unwrap_err();
+
+    assert_eq!(err.to_string(), "fetching user: refused");
+    let span = subscriber
+        .span("async_err.operation")
+        .expect("span recorded");
+    assert!(span.entered >= 1, "span never entered");
+    assert_eq!(span.field("operation"), Some("fetch_user"));
+    assert_eq!(span.field("error.message"), Some("refused"));
+    assert_eq!(span.field("error.context"), Some("fetching user"));
+    assert_eq!(span.field("error.severity"), Some("ERROR"));
+}
+
+#[test]
+fn with_context_span_leaves_error_fields_empty_on_success() {
+    use async_err::AsyncResultExt;
+
+    let subscriber = RecordingSubscriber::default();
+    let value = tracing::subscriber::with_default(subscriber.clone(), || {
+        futures::executor::block_on(
+            async { Ok::<_, SpanError>(3) }.with_context_span("count", |_| unreachable!()),
+        )
+    })
+    .unwrap();
+
+    assert_eq!(value, 3);
+    let span = subscriber
+        .span("async_err.operation")
+        .expect("span recorded");
+    assert!(span.entered >= 1);
+    assert_eq!(span.field("error.message"), None);
+}
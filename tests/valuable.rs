@@ -0,0 +1,78 @@
+#![cfg(feature = "valuable")]
+#[macro_use]
+mod common;
+
+use async_err::{AsyncError, Severity};
+use valuable::{NamedValues, Valuable, Value, Visit};
+
+test_error!(ValuableError);
+
+/// Records the string and unit fields of a structure, plus the entries of its map fields.
+#[derive(Default)]
+struct FieldVisitor {
+    strings: Vec<(String, Option<String>)>,
+    entries: Vec<(String, String)>,
+}
+
+impl Visit for FieldVisitor {
+    fn visit_named_fields(&mut self, fields: &NamedValues<'_>) {
+        for (field, value) in fields {
+            match value {
+                Value::String(s) => self
+                    .strings
+                    .push((field.name().into(), Some(s.to_string()))),
+                Value::Unit => self.strings.push((field.name().into(), None)),
+                Value::Mappable(map) => map.visit(self),
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_entry(&mut self, key: Value<'_>, value: Value<'_>) {
+        if let (Value::String(key), Value::String(value)) = (key, value) {
+            self.entries.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    fn visit_value(&mut self, _value: Value<'_>) {}
+}
+
+impl FieldVisitor {
+    fn field(&self, name: &str) -> Option<Option<&str>> {
+        self.strings
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_deref())
+    }
+}
+
+#[test]
+fn visitor_extracts_the_structured_fields() {
+    let err = AsyncError::new(ValuableError("disk full"))
+        .append_context("opening".to_string())
+        .append_context("saving".to_string())
+        .with_severity(Severity::Critical)
+        .with_code("E_DISK")
+        .with_metadata("path", "/tmp/out");
+
+    let mut visitor = FieldVisitor::default();
+    err.visit(&mut visitor);
+
+    assert_eq!(visitor.field("message"), Some(Some("disk full")));
+    assert_eq!(visitor.field("context"), Some(Some("saving")));
+    assert_eq!(visitor.field("code"), Some(Some("E_DISK")));
+    assert_eq!(visitor.field("severity"), Some(Some("CRITICAL")));
+    assert_eq!(
+        visitor.entries,
+        [("path".to_string(), "/tmp/out".to_string())]
+    );
+}
+
+#[test]
+fn missing_context_is_unit() {
+    let err = AsyncError::new(ValuableError("disk full"));
+    let mut visitor = FieldVisitor::default();
+    err.visit(&mut visitor);
+    assert_eq!(visitor.field("context"), Some(None));
+    assert_eq!(visitor.field("code"), Some(None));
+}